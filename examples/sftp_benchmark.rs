@@ -0,0 +1,105 @@
+//! This example measures SFTP transfer speed with different settings.
+//!
+//! Basic usage:
+//! ```
+//! cargo run --release --example sftp_benchmark -- ssh://root@127.0.0.1:2222 --size-mb 256
+//! ```
+
+use std::{
+    env,
+    fs::File,
+    io::{BufWriter, Write},
+    time::Instant,
+};
+
+use clap::Parser;
+use roguewave::{Session, TransferOptions};
+
+#[derive(Debug, Parser)]
+struct Command {
+    /// Remote server to use.
+    /// The format is the same as the `destination` argument to `ssh`. It may be
+    /// specified as either `[user@]hostname` or a URI of the form `ssh://[user@]hostname[:port]`.
+    destination: String,
+    /// Size of the test file in megabytes.
+    #[arg(long, default_value_t = 64)]
+    size_mb: usize,
+    /// Window sizes to test.
+    #[arg(long, value_delimiter = ',', default_value = "1,4,16,64")]
+    windows: Vec<usize>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    // Enable logging by default.
+    if env::var("RUST_LOG").is_err() {
+        env::set_var("RUST_LOG", "warn")
+    }
+    // Set up logger.
+    env_logger::builder()
+        .format_target(false)
+        .format_module_path(false)
+        .init();
+
+    let command: Command = Command::parse();
+    let mut session = Session::connect(&command.destination).await?;
+
+    // Generate a test file with pseudo-random content.
+    let local_path = env::temp_dir().join("roguewave-sftp-benchmark");
+    let mut file = BufWriter::new(File::create(&local_path)?);
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    for _ in 0..command.size_mb * 1024 * 1024 / 8 {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        file.write_all(&state.to_le_bytes())?;
+    }
+    file.flush()?;
+    drop(file);
+    let remote_path = "/tmp/roguewave-sftp-benchmark";
+
+    let started = Instant::now();
+    session
+        .fs()
+        .write(remote_path, std::fs::read(&local_path)?)
+        .await?;
+    report("fs().write", command.size_mb, started);
+
+    for &window in &command.windows {
+        let options = TransferOptions {
+            window,
+            ..TransferOptions::default()
+        };
+        let started = Instant::now();
+        session
+            .upload_file_parallel(&local_path, remote_path, options)
+            .await?;
+        report(
+            &format!("upload, window {window}"),
+            command.size_mb,
+            started,
+        );
+
+        let started = Instant::now();
+        session
+            .download_file_parallel(remote_path, &local_path, options)
+            .await?;
+        report(
+            &format!("download, window {window}"),
+            command.size_mb,
+            started,
+        );
+    }
+
+    session.command(["rm", "-f", remote_path]).run().await?;
+    std::fs::remove_file(&local_path)?;
+    Ok(())
+}
+
+fn report(name: &str, size_mb: usize, started: Instant) {
+    let seconds = started.elapsed().as_secs_f64();
+    println!(
+        "{name}: {seconds:.2} s, {:.1} MB/s",
+        size_mb as f64 / seconds
+    );
+}
@@ -1,13 +1,31 @@
 use anyhow::{bail, Context};
-use log::log;
-use openssh::Stdio;
+use log::{log, warn};
+use openssh::{ChildStdin, ChildStdout, Stdio};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
 use std::{
     ffi::{OsStr, OsString},
     fmt,
+    io,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufWriter},
+    sync::mpsc,
+    task::JoinHandle,
 };
-use tokio::io::{AsyncRead, AsyncReadExt};
 
-use crate::Session;
+use crate::{
+    logging::{LogContext, LogFormat},
+    rate_limit::RateLimitPermit,
+    recipes::window::{MaintenanceWindow, OutsideWindow},
+    timings::OperationKind,
+    transcript::TranscriptEntry,
+    Session,
+};
 
 struct Arg {
     kind: ArgKind,
@@ -58,17 +76,283 @@ impl fmt::Debug for Arg {
     }
 }
 
+impl Arg {
+    /// Representation of the argument for structured logs.
+    fn log_value(&self) -> String {
+        if let Some(placeholder) = &self.display_placeholder {
+            placeholder.clone()
+        } else {
+            match &self.kind {
+                ArgKind::Escaped(arg) => arg.clone(),
+                ArgKind::Raw(arg) => arg.to_string_lossy().into_owned(),
+            }
+        }
+    }
+}
+
 /// A remote command executor.
 ///
 /// Use `Session::command` or `Session::raw_command` to create a new command.
 /// The command, its stdin and stdout will be logged. The logging level can be adjusted.
 pub struct Command<'a> {
-    session: &'a Session,
+    pub(crate) session: &'a Session,
     command: Vec<Arg>,
     command_log_level: log::Level,
     stdout_log_level: log::Level,
     stderr_log_level: log::Level,
     allow_failure: bool,
+    success_predicates: Vec<SuccessPredicate<'a>>,
+    stdin: Option<CommandStdin<'a>>,
+    timeout: Option<Duration>,
+    kill_after: Duration,
+    pty: bool,
+    retry: Option<RetryPolicy>,
+    window: Option<(MaintenanceWindow, OutsideWindow)>,
+    output_limit: Option<(usize, OutputOverflow)>,
+    stdout_file: Option<PathBuf>,
+}
+
+type SuccessPredicate<'a> = Box<dyn Fn(&CommandOutput) -> bool + Send + 'a>;
+/// Output lines are sent to the `run_streaming` callback along with the stream variant.
+type LineSender = (mpsc::UnboundedSender<OutputLine>, fn(String) -> OutputLine);
+/// Captured output and the file with the output exceeding the size limit.
+type CapturedOutput = (String, Option<PathBuf>);
+
+const DEFAULT_KILL_AFTER: Duration = Duration::from_secs(5);
+const LOCAL_TIMEOUT_MARGIN: Duration = Duration::from_secs(10);
+/// Exit codes of `timeout` when the command times out (after `SIGTERM` and after `SIGKILL`).
+const TIMEOUT_EXIT_CODES: [i32; 2] = [124, 137];
+
+/// Error returned when a command doesn't finish within its timeout.
+///
+/// Use `anyhow::Error::downcast_ref` to check for it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CommandTimeout {
+    /// The timeout that was exceeded.
+    pub timeout: Duration,
+}
+
+impl fmt::Display for CommandTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "command timed out after {:?}", self.timeout)
+    }
+}
+
+impl std::error::Error for CommandTimeout {}
+
+/// A line of command output passed to the `Command::run_streaming` callback,
+/// without the trailing newline.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OutputLine {
+    /// A line of stdout.
+    Stdout(String),
+    /// A line of stderr.
+    Stderr(String),
+}
+
+/// What to do with output exceeding the limit set by `Command::max_output_size`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutputOverflow {
+    /// Discard the rest of the output and log a warning.
+    Truncate,
+    /// Fail the command.
+    Fail,
+    /// Write the rest of the output to a local temporary file. Its path is returned in
+    /// `CommandOutput::stdout_overflow` or `CommandOutput::stderr_overflow`.
+    /// The file is not deleted automatically.
+    SpillToFile,
+}
+
+/// How `Command::retry_with_backoff` retries a failed command.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of retries after the first attempt.
+    pub retries: u32,
+    /// Delay before the first retry.
+    pub initial_delay: Duration,
+    /// Factor applied to the delay after each retry.
+    pub multiplier: f64,
+    /// Upper bound of the delay.
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Retry up to `retries` times, starting with a 1 second delay and doubling it
+    /// up to 30 seconds.
+    pub fn new(retries: u32) -> Self {
+        Self {
+            retries,
+            initial_delay: Duration::from_secs(1),
+            multiplier: 2.0,
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+/// A background process started with `Command::spawn_detached`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DetachedProcess {
+    pid: u32,
+    log_path: String,
+}
+
+impl DetachedProcess {
+    /// Refer to a process started earlier, e.g. in another run, by its PID and log file.
+    pub fn new(pid: u32, log_path: impl Into<String>) -> Self {
+        Self {
+            pid,
+            log_path: log_path.into(),
+        }
+    }
+
+    /// Remote PID of the process. It's also the ID of its process group.
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// Remote file receiving stdout and stderr of the process.
+    pub fn log_path(&self) -> &str {
+        &self.log_path
+    }
+
+    /// Check if the process is still running.
+    pub async fn is_running(&self, session: &Session) -> anyhow::Result<bool> {
+        let code = session
+            .command(["kill", "-0", &self.pid.to_string()])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        Ok(code == 0)
+    }
+
+    /// Send `signal` (e.g. `TERM` or `HUP`) to the process group of the process,
+    /// which also reaches its children. Does nothing if the process is not running.
+    pub async fn signal(&self, session: &Session, signal: &str) -> anyhow::Result<()> {
+        if !signal.chars().all(|c| c.is_ascii_alphanumeric()) {
+            bail!("invalid signal: {signal:?}");
+        }
+        if self.is_running(session).await? {
+            session
+                .command([
+                    "kill".to_string(),
+                    format!("-{signal}"),
+                    "--".into(),
+                    format!("-{}", self.pid),
+                ])
+                .run()
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Terminate the process and its children with `SIGTERM`.
+    pub async fn kill(&self, session: &Session) -> anyhow::Result<()> {
+        self.signal(session, "TERM").await
+    }
+}
+
+enum CommandStdin<'a> {
+    Data(Vec<u8>),
+    Reader(Box<dyn AsyncRead + Send + Unpin + 'a>),
+    Channel(mpsc::UnboundedReceiver<Vec<u8>>),
+}
+
+/// Writes to the standard input of a running command. Created by `Command::stdin_sender`.
+///
+/// The command's stdin is closed when all senders are dropped.
+#[derive(Debug, Clone)]
+pub struct StdinSender(mpsc::UnboundedSender<Vec<u8>>);
+
+impl StdinSender {
+    /// Queue `data` to be written to the command's stdin.
+    /// Returns `false` if the command has already finished.
+    ///
+    /// This doesn't block, so it can be called from a `run_streaming` callback,
+    /// e.g. to answer a prompt.
+    pub fn send(&self, data: impl Into<Vec<u8>>) -> bool {
+        self.0.send(data.into()).is_ok()
+    }
+}
+
+/// A running command started with `Command::spawn`.
+///
+/// Stdout is passed to the caller as is, without logging. Stderr is logged and captured
+/// in the background, like with `Command::run`.
+pub struct CommandChild<'a> {
+    command: Command<'a>,
+    child: openssh::Child<&'a openssh::Session>,
+    stdin: ChildStdin,
+    stdout: ChildStdout,
+    stderr_task: JoinHandle<anyhow::Result<CapturedOutput>>,
+    log_context: LogContext,
+    started_at: SystemTime,
+    started: Instant,
+    _permit: RateLimitPermit,
+}
+
+impl CommandChild<'_> {
+    /// Standard input of the command. Call `shutdown` on it to send EOF.
+    pub fn stdin(&mut self) -> &mut (impl AsyncWrite + Unpin) {
+        &mut self.stdin
+    }
+
+    /// Standard output of the command.
+    pub fn stdout(&mut self) -> &mut (impl AsyncRead + Unpin) {
+        &mut self.stdout
+    }
+
+    /// Close stdin and wait for the command to finish.
+    ///
+    /// Stdout that hasn't been read through `stdout` is logged and returned in the output.
+    /// Exit code and output checks are applied as in `Command::run`.
+    pub async fn wait(self) -> anyhow::Result<CommandOutput> {
+        let Self {
+            command,
+            child,
+            stdin,
+            stdout,
+            stderr_task,
+            log_context,
+            started_at,
+            started,
+            _permit,
+        } = self;
+        drop(stdin);
+        let finished = async {
+            let (stdout, status) = tokio::join!(
+                handle_output(
+                    stdout,
+                    command.stdout_log_level,
+                    log_context.clone(),
+                    "stdout",
+                    None,
+                    command.output_limit,
+                ),
+                child.wait(),
+            );
+            anyhow::Ok((stdout?, status?))
+        };
+        let (stdout, status) = match command.timeout {
+            Some(timeout) => tokio::time::timeout(
+                timeout + command.kill_after + LOCAL_TIMEOUT_MARGIN,
+                finished,
+            )
+            .await
+            .map_err(|_| CommandTimeout { timeout })??,
+            None => finished.await?,
+        };
+        let (stdout, stdout_overflow) = stdout;
+        let (stderr, stderr_overflow) = stderr_task.await??;
+        let output = CommandOutput {
+            exit_code: status.code().context("missing exit code")?,
+            stdout,
+            stderr,
+            stdout_overflow,
+            stderr_overflow,
+        };
+        command.finish(&log_context, started_at, started, output)
+    }
 }
 
 impl<'a> Command<'a> {
@@ -93,6 +377,19 @@ impl<'a> Command<'a> {
         self
     }
 
+    /// Append a raw argument that is shown as `display` in logs.
+    pub(crate) fn raw_arg_displayed_as(
+        mut self,
+        arg: impl AsRef<OsStr>,
+        display: impl Into<String>,
+    ) -> Self {
+        self.command.push(Arg {
+            kind: ArgKind::raw(arg),
+            display_placeholder: Some(display.into()),
+        });
+        self
+    }
+
     /// Append multiple arguments to the command.
     pub fn args(mut self, args: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
         self.command
@@ -125,6 +422,46 @@ impl<'a> Command<'a> {
         self
     }
 
+    /// Run the command in the remote directory `path`.
+    ///
+    /// Like other wrappers, this applies to the arguments added so far, so call it before
+    /// `user`: a command run as another user starts in the user's home directory otherwise.
+    pub fn current_dir(self, path: impl AsRef<str>) -> Self {
+        self.prepend_args([
+            "sh",
+            "-c",
+            "cd -- \"$1\" && shift && exec \"$@\"",
+            "sh",
+            path.as_ref(),
+        ])
+    }
+
+    /// Run the command with the lowest CPU and I/O priority (`nice -n 19 ionice -c 3`),
+    /// so that maintenance tasks don't slow down other workloads.
+    pub fn low_priority(self) -> Self {
+        self.prepend_args(["nice", "-n", "19", "ionice", "-c", "3"])
+    }
+
+    /// Run the command in a transient systemd scope with resource limits
+    /// (`systemd-run --scope`). `cpu_quota` is a percentage of one CPU (e.g. `200` allows
+    /// two full CPUs) and `memory_max` is in bytes. Requires root privileges.
+    pub fn limits(self, cpu_quota: Option<u32>, memory_max: Option<u64>) -> Self {
+        let mut args = vec![
+            "systemd-run".to_string(),
+            "--scope".into(),
+            "--quiet".into(),
+            "--collect".into(),
+        ];
+        if let Some(cpu_quota) = cpu_quota {
+            args.push(format!("--property=CPUQuota={cpu_quota}%"));
+        }
+        if let Some(memory_max) = memory_max {
+            args.push(format!("--property=MemoryMax={memory_max}"));
+        }
+        args.push("--".into());
+        self.prepend_args(args)
+    }
+
     /// Mark the command as possibly expecting a failure.
     /// If `allow_failure` is called before `run`, `run` will no longer return
     /// an error on non-zero exit code.
@@ -133,6 +470,118 @@ impl<'a> Command<'a> {
         self
     }
 
+    /// Add a check for the command output. If `predicate` returns `false`, `run` will
+    /// return an error even if the exit code is zero.
+    ///
+    /// The check is applied in addition to the exit code check and is not affected
+    /// by `allow_failure`.
+    pub fn succeed_if(mut self, predicate: impl Fn(&CommandOutput) -> bool + Send + 'a) -> Self {
+        self.success_predicates.push(Box::new(predicate));
+        self
+    }
+
+    /// Treat the command as failed if its stdout contains `pattern`.
+    ///
+    /// Equivalent to `succeed_if(|output| !output.stdout.contains(pattern))`.
+    pub fn fail_if_stdout_contains(self, pattern: impl Into<String>) -> Self {
+        let pattern = pattern.into();
+        self.succeed_if(move |output| !output.stdout.contains(&pattern))
+    }
+
+    /// Pass `data` (a string or bytes) to the standard input of the command.
+    /// By default, the command's stdin is empty.
+    pub fn stdin(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.stdin = Some(CommandStdin::Data(data.into()));
+        self
+    }
+
+    /// Stream the standard input of the command from `reader`, e.g. a local file.
+    pub fn stdin_reader(mut self, reader: impl AsyncRead + Send + Unpin + 'a) -> Self {
+        self.stdin = Some(CommandStdin::Reader(Box::new(reader)));
+        self
+    }
+
+    /// Create a sender for writing to the standard input of the command while it runs.
+    pub fn stdin_sender(mut self) -> (Self, StdinSender) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.stdin = Some(CommandStdin::Channel(receiver));
+        (self, StdinSender(sender))
+    }
+
+    /// Run the command in a pseudo-terminal, for tools that behave differently
+    /// without one (e.g. `sudo -S` or interactive installers).
+    ///
+    /// The terminal is allocated on the remote host with `script` from util-linux.
+    /// Stdout and stderr are merged into stdout, and lines end with `\r\n`.
+    /// Use `stdin_sender` to type into the terminal while the command runs.
+    pub fn pty(mut self, enabled: bool) -> Self {
+        self.pty = enabled;
+        self
+    }
+
+    /// Retry the command up to `retries` times if it fails, with the default
+    /// backoff of `RetryPolicy::new`.
+    pub fn retries(self, retries: u32) -> Self {
+        self.retry_with_backoff(RetryPolicy::new(retries))
+    }
+
+    /// Retry the command according to `policy` if it fails for any reason: a non-zero
+    /// exit code, a failed `succeed_if` check, a timeout or a connection error.
+    /// Each failed attempt is logged as a warning.
+    ///
+    /// Can't be combined with `stdin_reader` or `stdin_sender` because streamed input
+    /// can't be replayed.
+    pub fn retry_with_backoff(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Capture at most `max_size` bytes of stdout and of stderr, e.g. to protect against
+    /// commands printing gigabytes of logs. `on_overflow` determines what happens to
+    /// the rest of the output.
+    ///
+    /// The whole output is still read from the command and logged according to the log
+    /// levels, so consider hiding it as well.
+    pub fn max_output_size(mut self, max_size: usize, on_overflow: OutputOverflow) -> Self {
+        self.output_limit = Some((max_size, on_overflow));
+        self
+    }
+
+    /// Write stdout of the command to a local file instead of capturing it, e.g. to fetch
+    /// a database dump with `pg_dump` or a large log with `cat`. The data is streamed
+    /// to the file as is, so it may be binary. The file is replaced if it exists.
+    ///
+    /// `CommandOutput::stdout` is empty in this case, and stdout is not logged
+    /// or passed to the `run_streaming` callback.
+    pub fn stdout_to_file(mut self, local_path: impl Into<PathBuf>) -> Self {
+        self.stdout_file = Some(local_path.into());
+        self
+    }
+
+    /// Only run the command while `window` is open according to the remote clock.
+    /// If it's closed, fail or wait for it depending on `policy`.
+    /// See `Session::only_during`.
+    pub fn only_during(mut self, window: MaintenanceWindow, policy: OutsideWindow) -> Self {
+        self.window = Some((window, policy));
+        self
+    }
+
+    /// Abort the command if it doesn't finish within `timeout`. The remote process is sent
+    /// `SIGTERM` and, if it's still running after the grace period set by `kill_after`
+    /// (5 seconds by default), `SIGKILL`. `run` then returns a [`CommandTimeout`] error.
+    ///
+    /// Implemented with the `timeout` utility wrapping the whole command.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set the grace period between `SIGTERM` and `SIGKILL` for `timeout`.
+    pub fn kill_after(mut self, grace_period: Duration) -> Self {
+        self.kill_after = grace_period;
+        self
+    }
+
     /// Execute the command and capture the output.
     ///
     /// By default, non-exit error code will cause `run` to return an error.
@@ -142,54 +591,307 @@ impl<'a> Command<'a> {
     ///
     /// Non-unicode output in stdout or stderr will result in an error.
     pub async fn run(self) -> anyhow::Result<CommandOutput> {
+        self.run_inner(None).await
+    }
+
+    /// Execute the command like `run`, calling `on_line` for each line of stdout and stderr
+    /// as soon as it arrives, e.g. to report progress or react to prompts.
+    /// The full output is still captured and returned at the end.
+    pub async fn run_streaming(
+        self,
+        mut on_line: impl FnMut(OutputLine) + Send,
+    ) -> anyhow::Result<CommandOutput> {
+        self.run_inner(Some(&mut on_line)).await
+    }
+
+    async fn run_inner(
+        mut self,
+        mut on_line: Option<&mut (dyn FnMut(OutputLine) + Send + '_)>,
+    ) -> anyhow::Result<CommandOutput> {
         if self.command.is_empty() {
             bail!("cannot run empty command");
         }
-        log!(self.command_log_level, "running {:?}", self.command);
-        let mut cmd = match &self.command[0].kind {
-            ArgKind::Escaped(cmd) => self.session.inner.command(cmd),
-            ArgKind::Raw(cmd) => self.session.inner.raw_command(cmd),
+        if let Some((window, policy)) = &self.window {
+            // Boxed because checking the window runs another command.
+            Box::pin(self.session.only_during(window, *policy)).await?;
+        }
+        let Some(policy) = self.retry else {
+            let stdin = self.stdin.take();
+            return self.run_once(stdin, on_line).await;
         };
-        for arg in &self.command[1..] {
-            match &arg.kind {
-                ArgKind::Escaped(arg) => {
-                    cmd.arg(arg);
-                }
-                ArgKind::Raw(arg) => {
-                    cmd.raw_arg(arg);
-                }
+        let data = match self.stdin.take() {
+            Some(CommandStdin::Data(data)) => Some(data),
+            Some(_) => bail!("retries are not supported with streamed stdin"),
+            None => None,
+        };
+        let mut delay = policy.initial_delay;
+        let mut attempt = 0;
+        loop {
+            let stdin = data.clone().map(CommandStdin::Data);
+            let err = match self.run_once(stdin, on_line.as_deref_mut()).await {
+                Ok(output) => return Ok(output),
+                Err(err) => err,
+            };
+            if attempt >= policy.retries {
+                return Err(err);
+            }
+            attempt += 1;
+            let log_context = self.session.log_context();
+            match log_context.format {
+                LogFormat::Text => warn!(
+                    "{}command failed: {:#}, retrying in {:?} (attempt {}/{})",
+                    log_context.prefix, err, delay, attempt, policy.retries
+                ),
+                LogFormat::Json => log_context.json(
+                    log::Level::Warn,
+                    "retry",
+                    json!({
+                        "error": format!("{err:#}"),
+                        "delay_secs": delay.as_secs_f64(),
+                        "attempt": attempt,
+                        "retries": policy.retries,
+                    }),
+                ),
             }
+            tokio::time::sleep(delay).await;
+            delay = delay.mul_f64(policy.multiplier).min(policy.max_delay);
         }
-        cmd.stdin(Stdio::null());
+    }
+
+    async fn run_once(
+        &mut self,
+        stdin: Option<CommandStdin<'a>>,
+        on_line: Option<&mut (dyn FnMut(OutputLine) + Send + '_)>,
+    ) -> anyhow::Result<CommandOutput> {
+        let log_context = self.session.log_context();
+        self.log_start(&log_context);
+        let mut cmd = self.remote_command()?;
+        let _permit = self.session.throttle().await;
+        let started_at = SystemTime::now();
+        let started = Instant::now();
+        cmd.stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        });
         cmd.stderr(Stdio::piped());
         cmd.stdout(Stdio::piped());
         let mut child = cmd.spawn().await?;
+        let stdin_writer = child.stdin().take();
         let stderr_reader = child.stderr().take().context("missing stderr")?;
         let stdout_reader = child.stdout().take().context("missing stdout")?;
+        let (line_sender, mut line_receiver) = match on_line {
+            Some(_) => {
+                let (sender, receiver) = mpsc::unbounded_channel();
+                (Some(sender), Some(receiver))
+            }
+            None => (None, None),
+        };
         let stderr_task = tokio::spawn(handle_output(
             stderr_reader,
             self.stderr_log_level,
-            "stderr: ",
+            log_context.clone(),
+            "stderr",
+            line_sender
+                .clone()
+                .map(|sender| (sender, OutputLine::Stderr as fn(_) -> _)),
+            self.output_limit,
         ));
-        let stdout_task = tokio::spawn(handle_output(
-            stdout_reader,
-            self.stdout_log_level,
-            "stdout: ",
-        ));
-        let status = child.wait().await?;
-        let exit_code = status.code().context("missing exit code")?;
-        if !self.allow_failure && exit_code != 0 {
-            bail!("failed with exit code {}", exit_code);
+        let stdout_task = match &self.stdout_file {
+            Some(path) => {
+                // Output lines are not forwarded, so the sender must not block `forward_lines`.
+                drop(line_sender);
+                tokio::spawn(copy_output_to_file(stdout_reader, path.clone()))
+            }
+            None => tokio::spawn(handle_output(
+                stdout_reader,
+                self.stdout_log_level,
+                log_context.clone(),
+                "stdout",
+                line_sender.map(|sender| (sender, OutputLine::Stdout as fn(_) -> _)),
+                self.output_limit,
+            )),
+        };
+        // Lines are forwarded until both output tasks finish and drop their senders.
+        let forward_lines = async {
+            if let (Some(receiver), Some(on_line)) = (&mut line_receiver, on_line) {
+                while let Some(line) = receiver.recv().await {
+                    on_line(line);
+                }
+            }
+        };
+        let write_stdin = async {
+            let (Some(mut writer), Some(stdin)) = (stdin_writer, stdin) else {
+                return Ok(());
+            };
+            match stdin {
+                CommandStdin::Data(data) => writer.write_all(&data).await?,
+                CommandStdin::Reader(mut reader) => {
+                    tokio::io::copy(&mut reader, &mut writer).await?;
+                }
+                CommandStdin::Channel(mut receiver) => {
+                    while let Some(data) = receiver.recv().await {
+                        writer.write_all(&data).await?;
+                        writer.flush().await?;
+                    }
+                }
+            }
+            writer.shutdown().await
+        };
+        // Stop writing stdin once the command exits, e.g. if a `StdinSender` is still alive.
+        let wait = async {
+            tokio::pin!(write_stdin);
+            let status = child.wait();
+            tokio::pin!(status);
+            tokio::select! {
+                status = &mut status => (Ok(()), status),
+                write_result = &mut write_stdin => (write_result, status.await),
+            }
+        };
+        let finished = async {
+            let ((write_result, status), ()) = tokio::join!(wait, forward_lines);
+            (write_result, status)
+        };
+        let (write_result, status) = match self.timeout {
+            // Normally the remote `timeout` stops the command. The local timeout is a fallback
+            // for an unresponsive connection.
+            Some(timeout) => {
+                tokio::time::timeout(timeout + self.kill_after + LOCAL_TIMEOUT_MARGIN, finished)
+                    .await
+                    .map_err(|_| CommandTimeout { timeout })?
+            }
+            None => finished.await,
+        };
+        let status = status?;
+        match write_result {
+            // The command may exit without reading all of its input.
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {}
+            result => result.context("failed to write stdin")?,
         }
-        Ok(CommandOutput {
+        let exit_code = status.code().context("missing exit code")?;
+        let (stdout, stdout_overflow) = stdout_task.await??;
+        let (stderr, stderr_overflow) = stderr_task.await??;
+        let output = CommandOutput {
             exit_code,
-            stdout: stdout_task.await??,
-            stderr: stderr_task.await??,
+            stdout,
+            stderr,
+            stdout_overflow,
+            stderr_overflow,
+        };
+        self.finish(&log_context, started_at, started, output)
+    }
+
+    /// Start the command in the background and return immediately, without waiting
+    /// for it to finish. The process is detached with `nohup` and `setsid`, so it keeps
+    /// running after the session is closed, e.g. to start a server.
+    ///
+    /// Stdout and stderr are appended to the remote file `log_path`.
+    /// Stdin, timeout, retries, PTY mode, output limits and `stdout_to_file` don't apply
+    /// to detached commands, and setting them results in an error.
+    pub async fn spawn_detached(self, log_path: &str) -> anyhow::Result<DetachedProcess> {
+        if self.command.is_empty() {
+            bail!("cannot run empty command");
+        }
+        let unsupported = [
+            ("stdin", self.stdin.is_some()),
+            ("timeout", self.timeout.is_some()),
+            ("PTY mode", self.pty),
+            ("retries", self.retry.is_some()),
+            ("output size limit", self.output_limit.is_some()),
+            ("stdout_to_file", self.stdout_file.is_some()),
+        ];
+        if let Some((option, _)) = unsupported.iter().find(|(_, is_set)| *is_set) {
+            bail!("{option} is not supported for detached commands");
+        }
+        if let Some((window, policy)) = &self.window {
+            self.session.only_during(window, *policy).await?;
+        }
+        self.log_start(&self.session.log_context());
+        // `setsid` doesn't fork because the background job is not a process group leader,
+        // so `$!` is the PID of the command itself and of its new process group.
+        let script = format!(
+            "nohup setsid {} >> {} 2>&1 < /dev/null & echo $!",
+            self.shell_script(),
+            shell_quote(log_path)
+        );
+        // The launcher runs as a regular command, so it waits for a throttle permit
+        // and is recorded in the stats and transcript.
+        let output = self
+            .session
+            .command(["sh", "-c", &script])
+            .hide_command()
+            .hide_stdout()
+            .run()
+            .await?;
+        let pid = output
+            .stdout
+            .trim()
+            .parse()
+            .context("failed to parse PID of detached process")?;
+        Ok(DetachedProcess {
+            pid,
+            log_path: log_path.into(),
+        })
+    }
+
+    /// Start the command and return a handle to interact with it while it's running,
+    /// e.g. to drive an interactive tool like `fdisk` by writing to its stdin and reading
+    /// its stdout. Call `CommandChild::wait` to finish the command.
+    ///
+    /// Use `run_streaming` with `stdin_sender` instead if the output can be processed
+    /// line by line. Stdin set on the command and retries are not supported.
+    pub async fn spawn(self) -> anyhow::Result<CommandChild<'a>> {
+        if self.command.is_empty() {
+            bail!("cannot run empty command");
+        }
+        if self.stdin.is_some() {
+            bail!("use CommandChild::stdin to write input of spawned commands");
+        }
+        if self.retry.is_some() {
+            bail!("retries are not supported for spawned commands");
+        }
+        if self.stdout_file.is_some() {
+            bail!("stdout_to_file is not supported for spawned commands");
+        }
+        if let Some((window, policy)) = &self.window {
+            self.session.only_during(window, *policy).await?;
+        }
+        let log_context = self.session.log_context();
+        self.log_start(&log_context);
+        let mut cmd = self.remote_command()?;
+        let permit = self.session.throttle().await;
+        let started_at = SystemTime::now();
+        let started = Instant::now();
+        cmd.stdin(Stdio::piped());
+        cmd.stderr(Stdio::piped());
+        cmd.stdout(Stdio::piped());
+        let mut child = cmd.spawn().await?;
+        let stdin = child.stdin().take().context("missing stdin")?;
+        let stdout = child.stdout().take().context("missing stdout")?;
+        let stderr_reader = child.stderr().take().context("missing stderr")?;
+        let stderr_task = tokio::spawn(handle_output(
+            stderr_reader,
+            self.stderr_log_level,
+            log_context.clone(),
+            "stderr",
+            None,
+            self.output_limit,
+        ));
+        Ok(CommandChild {
+            command: self,
+            child,
+            stdin,
+            stdout,
+            stderr_task,
+            log_context,
+            started_at,
+            started,
+            _permit: permit,
         })
     }
 
     /// Execute the command and return the exit code.
-    /// Implies `allow_failure`.
+    /// Implies `allow_failure`. Output checks added by `succeed_if` still apply.
     pub async fn exit_code(self) -> anyhow::Result<i32> {
         self.allow_failure()
             .run()
@@ -197,6 +899,128 @@ impl<'a> Command<'a> {
             .map(|output| output.exit_code)
     }
 
+    /// Build the command to execute, including the `timeout` and PTY wrappers.
+    fn remote_command(&self) -> anyhow::Result<openssh::Command<'a>> {
+        let mut wrapper = match &self.session.run {
+            // Allows `run` to stop the command on Ctrl-C.
+            Some(run) => run.command_wrapper().map(Arg::escaped).to_vec(),
+            None => Vec::new(),
+        };
+        if let Some(timeout) = self.timeout {
+            wrapper.extend([
+                Arg::escaped("timeout"),
+                Arg::escaped(format!("--kill-after={}s", self.kill_after.as_secs_f64())),
+                Arg::escaped(format!("{}s", timeout.as_secs_f64())),
+            ]);
+        }
+        let pty_command;
+        let command = if self.pty {
+            let script = self.shell_script();
+            pty_command = ["script", "--quiet", "--return", "--flush", "--command"]
+                .into_iter()
+                .map(Arg::escaped)
+                .chain([Arg::escaped(script), Arg::escaped("/dev/null")])
+                .collect::<Vec<_>>();
+            &pty_command
+        } else {
+            &self.command
+        };
+        let mut args = wrapper.iter().chain(command);
+        let mut cmd = match &args.next().context("missing command")?.kind {
+            ArgKind::Escaped(cmd) => self.session.inner.command(cmd),
+            ArgKind::Raw(cmd) => self.session.inner.raw_command(cmd),
+        };
+        for arg in args {
+            match &arg.kind {
+                ArgKind::Escaped(arg) => {
+                    cmd.arg(arg);
+                }
+                ArgKind::Raw(arg) => {
+                    cmd.raw_arg(arg);
+                }
+            }
+        }
+        Ok(cmd)
+    }
+
+    /// Record the finished command and check its exit code and output.
+    fn finish(
+        &self,
+        log_context: &LogContext,
+        started_at: SystemTime,
+        started: Instant,
+        output: CommandOutput,
+    ) -> anyhow::Result<CommandOutput> {
+        let exit_code = output.exit_code;
+        let duration = started.elapsed();
+        self.session.record_timing(
+            OperationKind::Command,
+            format!("{:?}", self.command),
+            duration,
+        );
+        if let Some(transcript) = &self.session.transcript {
+            transcript.record(TranscriptEntry {
+                host: &log_context.host,
+                command: &self.command.iter().map(Arg::log_value).collect::<Vec<_>>(),
+                started_at,
+                duration,
+                output: &output,
+            })?;
+        }
+        if let Some(timeout) = self.timeout {
+            if TIMEOUT_EXIT_CODES.contains(&exit_code) && duration >= timeout {
+                self.session.stats.record_command(true);
+                return Err(CommandTimeout { timeout }.into());
+            }
+        }
+        let exit_code_failed = !self.allow_failure && exit_code != 0;
+        let checks_passed = self
+            .success_predicates
+            .iter()
+            .all(|predicate| predicate(&output));
+        self.session
+            .stats
+            .record_command(exit_code_failed || !checks_passed);
+        if exit_code_failed {
+            bail!("failed with exit code {}", exit_code);
+        }
+        if !checks_passed {
+            bail!("command output did not pass the success check");
+        }
+        Ok(output)
+    }
+
+    fn log_start(&self, log_context: &LogContext) {
+        match log_context.format {
+            LogFormat::Text => log!(
+                self.command_log_level,
+                "{}running {:?}",
+                log_context.prefix,
+                self.command
+            ),
+            LogFormat::Json => log_context.json(
+                self.command_log_level,
+                "command",
+                json!({
+                    "command": self.command.iter().map(Arg::log_value).collect::<Vec<_>>(),
+                    "redacted": self.command.iter().any(|arg| arg.display_placeholder.is_some()),
+                }),
+            ),
+        }
+    }
+
+    /// The command as a single POSIX shell command line.
+    fn shell_script(&self) -> String {
+        self.command
+            .iter()
+            .map(|arg| match &arg.kind {
+                ArgKind::Escaped(arg) => shell_quote(arg),
+                ArgKind::Raw(arg) => arg.to_string_lossy().into_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
     /// Lower stdout and stderr logs to `Trace`.
     pub fn hide_all_output(self) -> Self {
         self.hide_stdout().hide_stderr()
@@ -242,9 +1066,23 @@ impl<'a> Command<'a> {
 async fn handle_output(
     reader: impl AsyncRead,
     log_level: log::Level,
-    prefix: &str,
-) -> anyhow::Result<String> {
-    let mut output = String::new();
+    log_context: LogContext,
+    stream: &str,
+    line_sender: Option<LineSender>,
+    limit: Option<(usize, OutputOverflow)>,
+) -> anyhow::Result<CapturedOutput> {
+    let send_line = |line: &str| {
+        if let Some((sender, make_line)) = &line_sender {
+            // The receiver is gone only if the command was aborted.
+            let _ = sender.send(make_line(line.into()));
+        }
+    };
+    let mut output = OutputCapture {
+        text: String::new(),
+        limit,
+        overflowed: false,
+        spill: None,
+    };
     let mut vec = Vec::new();
     tokio::pin!(reader);
     loop {
@@ -254,21 +1092,188 @@ async fn handle_output(
         }
         while let Some(index) = vec.iter().position(|i| *i == b'\n') {
             let line = std::str::from_utf8(&vec[..=index])?;
-            log!(log_level, "{}{}", prefix, &line[..line.len() - 1]);
-            output.push_str(line);
+            log_output_line(
+                &log_context,
+                log_level,
+                stream,
+                &line[..line.len() - 1],
+                false,
+            );
+            send_line(&line[..line.len() - 1]);
+            output.push(line).await?;
             vec.drain(..=index);
         }
     }
     if !vec.is_empty() {
         let line = std::str::from_utf8(&vec)?;
-        log!(log_level, "{}{}[eof]", prefix, line);
-        output.push_str(line);
+        log_output_line(&log_context, log_level, stream, line, true);
+        send_line(line);
+        output.push(line).await?;
+    }
+    output.finish(&log_context, stream).await
+}
+
+/// Writes stdout to a local file for `Command::stdout_to_file`.
+async fn copy_output_to_file(
+    reader: impl AsyncRead,
+    path: PathBuf,
+) -> anyhow::Result<CapturedOutput> {
+    let file = File::create(&path)
+        .await
+        .with_context(|| format!("failed to create {path:?}"))?;
+    let mut file = BufWriter::new(file);
+    let mut buf = vec![0; 64 * 1024];
+    tokio::pin!(reader);
+    loop {
+        let size = reader.read(&mut buf).await?;
+        if size == 0 {
+            break;
+        }
+        file.write_all(&buf[..size])
+            .await
+            .with_context(|| format!("failed to write {path:?}"))?;
+    }
+    file.flush()
+        .await
+        .with_context(|| format!("failed to write {path:?}"))?;
+    Ok((String::new(), None))
+}
+
+/// Collects command output, applying the limit set by `Command::max_output_size`.
+struct OutputCapture {
+    text: String,
+    limit: Option<(usize, OutputOverflow)>,
+    overflowed: bool,
+    spill: Option<(PathBuf, BufWriter<File>)>,
+}
+
+impl OutputCapture {
+    async fn push(&mut self, data: &str) -> anyhow::Result<()> {
+        let Some((max_size, on_overflow)) = self.limit else {
+            self.text.push_str(data);
+            return Ok(());
+        };
+        let mut fits = max_size.saturating_sub(self.text.len()).min(data.len());
+        while !data.is_char_boundary(fits) {
+            fits -= 1;
+        }
+        self.text.push_str(&data[..fits]);
+        let rest = &data[fits..];
+        if rest.is_empty() {
+            return Ok(());
+        }
+        if !self.overflowed {
+            self.overflowed = true;
+            if on_overflow == OutputOverflow::SpillToFile {
+                let path = spill_file_path();
+                let file = File::create_new(&path)
+                    .await
+                    .with_context(|| format!("failed to create {path:?}"))?;
+                self.spill = Some((path, BufWriter::new(file)));
+            }
+        }
+        // The rest of the output is still read so that the command doesn't block
+        // on a full pipe.
+        if let Some((path, file)) = &mut self.spill {
+            file.write_all(rest.as_bytes())
+                .await
+                .with_context(|| format!("failed to write {path:?}"))?;
+        }
+        Ok(())
+    }
+
+    async fn finish(
+        self,
+        log_context: &LogContext,
+        stream: &str,
+    ) -> anyhow::Result<CapturedOutput> {
+        let Some((max_size, on_overflow)) = self.limit.filter(|_| self.overflowed) else {
+            return Ok((self.text, None));
+        };
+        let spill_path = match self.spill {
+            Some((path, mut file)) => {
+                file.flush()
+                    .await
+                    .with_context(|| format!("failed to write {path:?}"))?;
+                Some(path)
+            }
+            None => None,
+        };
+        if on_overflow == OutputOverflow::Fail {
+            bail!("{stream} exceeded the limit of {max_size} bytes");
+        }
+        match log_context.format {
+            LogFormat::Text => match &spill_path {
+                Some(path) => warn!(
+                    "{}{stream} exceeded {max_size} bytes, the rest is saved to {path:?}",
+                    log_context.prefix
+                ),
+                None => warn!(
+                    "{}{stream} exceeded {max_size} bytes, the rest is discarded",
+                    log_context.prefix
+                ),
+            },
+            LogFormat::Json => log_context.json(
+                log::Level::Warn,
+                "output_overflow",
+                json!({
+                    "stream": stream,
+                    "max_size": max_size,
+                    "spill_path": spill_path,
+                }),
+            ),
+        }
+        Ok((self.text, spill_path))
     }
-    Ok(output)
+}
+
+fn spill_file_path() -> PathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    std::env::temp_dir().join(format!(
+        "roguewave-output-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+fn log_output_line(
+    log_context: &LogContext,
+    log_level: log::Level,
+    stream: &str,
+    line: &str,
+    eof: bool,
+) {
+    match log_context.format {
+        LogFormat::Text => {
+            let eof_marker = if eof { "[eof]" } else { "" };
+            log!(
+                log_level,
+                "{}{}: {}{}",
+                log_context.prefix,
+                stream,
+                line,
+                eof_marker
+            );
+        }
+        LogFormat::Json => log_context.json(
+            log_level,
+            "output",
+            json!({
+                "stream": stream,
+                "line": line,
+                "eof": eof,
+            }),
+        ),
+    }
+}
+
+/// Quote a string for use as a single word in a POSIX shell command.
+pub(crate) fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
 }
 
 /// Information about an output of an executed command.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct CommandOutput {
     /// Exit code (zero typically means success).
     pub exit_code: i32,
@@ -276,6 +1281,39 @@ pub struct CommandOutput {
     pub stdout: String,
     /// Captured stderr (non-unicode output will result in an error).
     pub stderr: String,
+    /// Local file with stdout exceeding the limit set by `Command::max_output_size`
+    /// with `OutputOverflow::SpillToFile`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdout_overflow: Option<PathBuf>,
+    /// Local file with stderr exceeding the limit set by `Command::max_output_size`
+    /// with `OutputOverflow::SpillToFile`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr_overflow: Option<PathBuf>,
+}
+
+impl CommandOutput {
+    /// Stdout with leading and trailing whitespace removed.
+    pub fn stdout_trimmed(&self) -> &str {
+        self.stdout.trim()
+    }
+
+    /// Iterate over lines of stdout.
+    pub fn stdout_lines(&self) -> impl Iterator<Item = &str> {
+        self.stdout.lines()
+    }
+
+    /// Last non-empty line of stdout.
+    pub fn last_line(&self) -> Option<&str> {
+        self.stdout
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+    }
+
+    /// Parse stdout as JSON.
+    pub fn json<T: DeserializeOwned>(&self) -> anyhow::Result<T> {
+        serde_json::from_str(&self.stdout).context("failed to parse command output as JSON")
+    }
 }
 
 impl Session {
@@ -288,6 +1326,15 @@ impl Session {
             stdout_log_level: log::Level::Info,
             stderr_log_level: log::Level::Error,
             allow_failure: false,
+            success_predicates: Vec::new(),
+            stdin: None,
+            timeout: None,
+            kill_after: DEFAULT_KILL_AFTER,
+            pty: false,
+            retry: None,
+            window: None,
+            output_limit: None,
+            stdout_file: None,
         }
     }
 
@@ -304,6 +1351,36 @@ impl Session {
             stdout_log_level: log::Level::Info,
             stderr_log_level: log::Level::Error,
             allow_failure: false,
+            success_predicates: Vec::new(),
+            stdin: None,
+            timeout: None,
+            kill_after: DEFAULT_KILL_AFTER,
+            pty: false,
+            retry: None,
+            window: None,
+            output_limit: None,
+            stdout_file: None,
+        }
+    }
+
+    /// Prepare a multi-line bash script for execution. The script is passed to `bash -s`
+    /// on stdin, so it doesn't need to be escaped. Arguments added to the returned command
+    /// are available in the script as `$1`, `$2` and so on.
+    ///
+    /// Commands in the script share bash's stdin, so redirect their input from `/dev/null`
+    /// if they might read it. The script itself is logged at `Debug` level.
+    pub fn shell_script(&self, script: &str) -> Command<'_> {
+        let log_context = self.log_context();
+        match log_context.format {
+            LogFormat::Text => {
+                for line in script.lines() {
+                    log!(log::Level::Debug, "{}script: {}", log_context.prefix, line);
+                }
+            }
+            LogFormat::Json => {
+                log_context.json(log::Level::Debug, "script", json!({ "script": script }))
+            }
         }
+        self.command(["bash", "-s", "--"]).stdin(script)
     }
 }
@@ -0,0 +1,228 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{bail, Context};
+use log::{debug, info};
+use openssh::Stdio;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    sync::mpsc,
+};
+
+use crate::{
+    command::shell_quote, timings::OperationKind, transcript::TranscriptEntry, CommandOutput,
+    Session,
+};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+impl Session {
+    /// Prepare an interactive command that will be driven by matching its output
+    /// and sending responses to its stdin.
+    pub fn expect<S: AsRef<str>, I: IntoIterator<Item = S>>(&self, command: I) -> Expect<'_> {
+        Expect {
+            session: self,
+            command: command.into_iter().map(|s| s.as_ref().into()).collect(),
+            steps: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
+            pty: false,
+        }
+    }
+}
+
+struct Step {
+    pattern: String,
+    response: String,
+    secret: bool,
+}
+
+/// An interactive command driven by expected output. Use `Session::expect` to create it.
+pub struct Expect<'a> {
+    session: &'a Session,
+    command: Vec<String>,
+    steps: Vec<Step>,
+    timeout: Duration,
+    pty: bool,
+}
+
+impl Expect<'_> {
+    /// Wait until the output (stdout or stderr) contains `pattern`, then send `response`
+    /// followed by a newline. Steps are executed in the order they were added.
+    pub fn respond(mut self, pattern: impl Into<String>, response: impl Into<String>) -> Self {
+        self.steps.push(Step {
+            pattern: pattern.into(),
+            response: response.into(),
+            secret: false,
+        });
+        self
+    }
+
+    /// Same as `respond`, but the response is not logged.
+    pub fn respond_secret(
+        mut self,
+        pattern: impl Into<String>,
+        response: impl Into<String>,
+    ) -> Self {
+        self.steps.push(Step {
+            pattern: pattern.into(),
+            response: response.into(),
+            secret: true,
+        });
+        self
+    }
+
+    /// Set the maximum time to wait for each pattern (default is 30 seconds).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Run the command in a pseudo-terminal for programs that read from the terminal
+    /// directly (e.g. `passwd`). Requires `script` (util-linux) on the remote host.
+    ///
+    /// In this mode, stdout and stderr are merged.
+    pub fn pty(mut self) -> Self {
+        self.pty = true;
+        self
+    }
+
+    /// Execute the command and respond to its prompts.
+    ///
+    /// Fails if a pattern doesn't appear within the timeout or if the command exits
+    /// with a non-zero code.
+    pub async fn run(self) -> anyhow::Result<CommandOutput> {
+        if self.command.is_empty() {
+            bail!("cannot run empty command");
+        }
+        let prefix = &self.session.log_prefix;
+        info!("{prefix}running interactive {:?}", self.command);
+        let mut cmd = if self.pty {
+            let command = self
+                .command
+                .iter()
+                .map(|arg| shell_quote(arg))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let mut cmd = self.session.inner.command("script");
+            cmd.args(["--quiet", "--return", "--command", &command, "/dev/null"]);
+            cmd
+        } else {
+            let mut cmd = self.session.inner.command(&self.command[0]);
+            cmd.args(&self.command[1..]);
+            cmd
+        };
+        let _permit = self.session.throttle().await;
+        let started_at = SystemTime::now();
+        let started = Instant::now();
+        cmd.stdin(Stdio::piped());
+        cmd.stdout(Stdio::piped());
+        cmd.stderr(Stdio::piped());
+        let mut child = cmd.spawn().await?;
+        let mut stdin = child.stdin().take().context("missing stdin")?;
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let stdout_task = tokio::spawn(forward_output(
+            child.stdout().take().context("missing stdout")?,
+            0,
+            sender.clone(),
+        ));
+        let stderr_task = tokio::spawn(forward_output(
+            child.stderr().take().context("missing stderr")?,
+            1,
+            sender,
+        ));
+
+        let mut outputs = [Vec::new(), Vec::new()];
+        // Output seen since the last match.
+        let mut unmatched = Vec::new();
+        for step in &self.steps {
+            let match_end = loop {
+                if let Some(index) = find(&unmatched, step.pattern.as_bytes()) {
+                    break index + step.pattern.len();
+                }
+                let chunk = tokio::time::timeout(self.timeout, receiver.recv())
+                    .await
+                    .with_context(|| format!("timed out waiting for {:?}", step.pattern))?;
+                let Some((stream, data)) = chunk else {
+                    bail!("command exited before printing {:?}", step.pattern);
+                };
+                unmatched.extend_from_slice(&data);
+                outputs[stream].extend_from_slice(&data);
+            };
+            // Output after the match may already contain the next prompt.
+            unmatched.drain(..match_end);
+            let display = if step.secret {
+                "<redacted>"
+            } else {
+                &step.response
+            };
+            info!("{prefix}matched {:?}, sending {display:?}", step.pattern);
+            stdin
+                .write_all(format!("{}\n", step.response).as_bytes())
+                .await?;
+            stdin.flush().await?;
+        }
+        drop(stdin);
+        while let Some((stream, data)) = receiver.recv().await {
+            outputs[stream].extend_from_slice(&data);
+        }
+        stdout_task.await??;
+        stderr_task.await??;
+        let status = child.wait().await?;
+        let exit_code = status.code().context("missing exit code")?;
+        let [stdout, stderr] = outputs;
+        let output = CommandOutput {
+            exit_code,
+            stdout: String::from_utf8(stdout)?,
+            stderr: String::from_utf8(stderr)?,
+            stdout_overflow: None,
+            stderr_overflow: None,
+        };
+        debug!("{prefix}interactive output: {:?}", output.stdout);
+        let duration = started.elapsed();
+        self.session.record_timing(
+            OperationKind::Command,
+            format!("{:?}", self.command),
+            duration,
+        );
+        if let Some(transcript) = &self.session.transcript {
+            transcript.record(TranscriptEntry {
+                host: &self.session.log_context().host,
+                command: &self.command,
+                started_at,
+                duration,
+                output: &output,
+            })?;
+        }
+        self.session.stats.record_command(exit_code != 0);
+        if exit_code != 0 {
+            bail!("failed with exit code {}", exit_code);
+        }
+        Ok(output)
+    }
+}
+
+async fn forward_output(
+    reader: impl AsyncRead,
+    stream: usize,
+    sender: mpsc::UnboundedSender<(usize, Vec<u8>)>,
+) -> anyhow::Result<()> {
+    tokio::pin!(reader);
+    let mut buf = vec![0; 4096];
+    loop {
+        let size = reader.read(&mut buf).await?;
+        if size == 0 {
+            return Ok(());
+        }
+        // The receiver may be gone if the command is aborted.
+        let _ = sender.send((stream, buf[..size].to_vec()));
+    }
+}
+
+/// Position of the first occurrence of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() {
+        return Some(0);
+    }
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
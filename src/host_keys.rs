@@ -0,0 +1,93 @@
+use std::{
+    env, fs, process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context};
+use log::info;
+use openssh::KnownHosts;
+
+use crate::{LocalCommand, Session};
+
+impl Session {
+    /// Initialize a SSH session, accepting only host keys with the specified fingerprints
+    /// (e.g. `SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s`, as printed by `ssh-keygen -l`).
+    ///
+    /// `known_hosts` files are not consulted for the host key. Instead, the host keys are fetched
+    /// with `ssh-keyscan`, and only keys matching `fingerprints` are trusted for the connection.
+    /// Fails if the server doesn't present any of the expected keys.
+    ///
+    /// Requires `ssh-keyscan` and `ssh-keygen` to be available locally.
+    /// See `connect` for the format of `destination`.
+    pub async fn connect_with_pinned_host_keys(
+        destination: impl AsRef<str>,
+        fingerprints: &[&str],
+    ) -> anyhow::Result<Self> {
+        if fingerprints.is_empty() {
+            bail!("no host key fingerprints specified");
+        }
+        let destination = destination.as_ref();
+        let builder = openssh::SessionBuilder::default();
+        let (resolved, resolved_destination) = builder.resolve(destination);
+        let host = resolved_destination
+            .rsplit_once('@')
+            .map_or(resolved_destination, |(_, host)| host);
+        let mut keyscan = LocalCommand::new(["ssh-keyscan", "-q"]);
+        if let Some(port) = resolved.get_port() {
+            keyscan = keyscan.args(["-p", port]);
+        }
+        let keys = keyscan
+            .args(["--", host])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?
+            .stdout;
+
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+        let known_hosts_path =
+            env::temp_dir().join(format!("roguewave-known-hosts-{}-{nanos}", process::id()));
+        let result = async {
+            let mut trusted = String::new();
+            let mut presented = Vec::new();
+            for line in keys.lines().filter(|line| !line.starts_with('#')) {
+                fs::write(&known_hosts_path, format!("{line}\n"))?;
+                let output = LocalCommand::new(["ssh-keygen", "-l", "-f"])
+                    .arg(known_hosts_path.to_str().context("non-utf8 path")?)
+                    .hide_command()
+                    .hide_all_output()
+                    .run()
+                    .await?;
+                // Format: `256 SHA256:... host (ED25519)`
+                let fingerprint = output
+                    .stdout
+                    .split_whitespace()
+                    .nth(1)
+                    .context("unexpected ssh-keygen output")?
+                    .to_string();
+                if fingerprints.contains(&fingerprint.as_str()) {
+                    trusted.push_str(line);
+                    trusted.push('\n');
+                }
+                presented.push(fingerprint);
+            }
+            if trusted.is_empty() {
+                bail!(
+                    "host key mismatch for {host}: server presented {presented:?}, \
+                    expected one of {fingerprints:?}"
+                );
+            }
+            fs::write(&known_hosts_path, trusted)?;
+            let mut builder = openssh::SessionBuilder::default();
+            builder
+                .known_hosts_check(KnownHosts::Strict)
+                .user_known_hosts_file(&known_hosts_path);
+            Self::from_openssh_builder(builder, destination).await
+        }
+        .await;
+        let _ = fs::remove_file(&known_hosts_path);
+        let session = result?;
+        info!("host key of {host} matches the pinned fingerprint");
+        Ok(session)
+    }
+}
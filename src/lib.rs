@@ -120,20 +120,106 @@
 //! }
 //! ```
 
-use std::{path::Path, sync::Arc};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
 
 use anyhow::Context;
+use log::{info, warn};
 use openssh::{KnownHosts, Stdio};
 use openssh_sftp_client::{error::SftpErrorKind, fs::Fs, Error, Sftp};
+use serde_json::json;
 use type_map::concurrent::TypeMap;
 
+use crate::{recipes::files::path_str, runner::RunState, summary::SessionStats, tasks::TaskSet};
+
 mod command;
+mod expect;
+mod host_keys;
 mod local;
+mod logging;
+pub mod notify;
+pub mod pool;
+mod rate_limit;
 mod recipes;
+mod runner;
+pub mod secrets;
+mod summary;
+mod tasks;
+mod timings;
+mod transcript;
 
-pub use command::{Command, CommandOutput};
+pub use command::{
+    Command, CommandChild, CommandOutput, CommandTimeout, DetachedProcess, OutputLine,
+    OutputOverflow, RetryPolicy, StdinSender,
+};
+pub use expect::Expect;
 pub use local::LocalCommand;
-pub use recipes::{apt::Apt, postgres::Postgres};
+pub use logging::LogFormat;
+pub use rate_limit::{set_global_rate_limiter, RateLimiter};
+pub use recipes::{
+    acl::{Acl, AclEntry, AclPermissions, AclTag},
+    aide::{Aide, AideReport},
+    apparmor::{AppArmor, AppArmorMode},
+    apt::{Apt, InstallProgress, InstalledPackage, UpgradablePackage},
+    attrs::{FileAttribute, SpecialBits},
+    batch::Batch,
+    bundle::Bundle,
+    clamav::{ClamAv, ClamDetection, ClamScanSummary},
+    clock::{ClockSkew, SkewAction},
+    cloud::{CloudMetadata, CloudProvider},
+    cloud_init::{CloudInit, CloudInitState, CloudInitStatus},
+    disk::BlockDevice,
+    dnf::{Dnf, DnfSecurityUpdate},
+    dns::Dns,
+    dotfiles::Dotfiles,
+    env_file::EnvFile,
+    etckeeper::Etckeeper,
+    firewall::{Firewall, IptablesFamily, PendingFirewallChange},
+    haproxy::{Haproxy, ServerState},
+    java::{Java, JavaService, JdkSource},
+    jobs::{JobState, JobStatus, Jobs},
+    lock::{RemoteLock, RemoteLockGuard},
+    maintenance::SessionFuture,
+    memcached::{Memcached, MemcachedConfig},
+    mise::Mise,
+    mongodb::{MongoDb, MongoDbConfig},
+    monitoring::{Datadog, MonitoringAgent},
+    msmtp::{Msmtp, MsmtpConfig},
+    net::{ListeningPort, Net, Protocol},
+    netplan::{
+        BridgeConfig, EthernetConfig, InterfaceSettings, Netplan, NetplanConfig, VlanConfig,
+    },
+    nfs::{Nfs, NfsExport},
+    nvidia::{GpuInfo, Nvidia},
+    openrc::{OpenRc, OpenRcScript},
+    os::{Distro, OsRelease},
+    patching::{PatchedPackage, SecurityPatchReport},
+    pm2::{Pm2, Pm2App, Pm2Process},
+    postfix::{Postfix, PostfixRelayConfig},
+    postgres::{Postgres, ReplicationConfig},
+    powershell::{powershell_quote, Choco},
+    quota::{Quota, QuotaKind, QuotaLimits, QuotaUsage},
+    samba::{Samba, SambaShare},
+    schedule::ScheduledJob,
+    search_engine::{ClusterHealth, HealthStatus, SearchEngine, SearchEngineKind},
+    selinux::{Selinux, SelinuxMode},
+    sftp_user::{SftpUser, SftpUsers},
+    sqlite::{Sqlite, SqliteRow},
+    tail::{Tail, TailLines},
+    tailscale::Tailscale,
+    transfer::TransferOptions,
+    transient_unit::{TransientUnit, TransientUnitStatus},
+    user_systemd::UserSystemd,
+    version::{VersionMismatch, VersionSource},
+    window::{MaintenanceWindow, OutsideWindow, Weekday},
+    zypper::Zypper,
+};
+pub use runner::{on_interrupt, run};
+pub use summary::SessionSummary;
+pub use timings::{OperationKind, Timing};
+pub use transcript::{Transcript, TranscriptFormat};
 
 /// A SSH session to a remote host.
 pub struct Session {
@@ -141,11 +227,96 @@ pub struct Session {
     port: Option<u16>,
     destination: String,
     inner: Arc<openssh::Session>,
+    sftp: Option<SftpChannel>,
+    cache: TypeMap,
+    log_format: LogFormat,
+    log_prefix: String,
+    step: Option<String>,
+    transcript: Option<Transcript>,
+    timings: Mutex<Vec<Timing>>,
+    stats: Arc<SessionStats>,
+    log_summary_on_drop: bool,
+    rate_limiter: Option<RateLimiter>,
+    tasks: TaskSet,
+    /// The `run` call that the session was opened in.
+    run: Option<Arc<RunState>>,
+}
+
+/// Whether a session opens the SFTP subsystem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SftpMode {
+    /// Open SFTP and fail to connect if it's not available.
+    #[default]
+    Required,
+    /// Open SFTP if it's available. Otherwise, file operations such as `read_file` and
+    /// `write_file` are performed with shell commands, and `fs` and `sftp` are not available.
+    Optional,
+    /// Don't open SFTP. File operations are performed with shell commands.
+    Disabled,
+}
+
+/// The SFTP subsystem of a session.
+struct SftpChannel {
     #[allow(dead_code)]
-    sftp_child: openssh::Child<Arc<openssh::Session>>,
+    child: openssh::Child<Arc<openssh::Session>>,
     sftp: Sftp,
     fs: Fs,
-    cache: TypeMap,
+}
+
+impl SftpChannel {
+    async fn open(session: Arc<openssh::Session>) -> anyhow::Result<Self> {
+        let mut child = openssh::Session::to_subsystem(session, "sftp")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .await?;
+        let sftp = Sftp::new(
+            child.stdin().take().unwrap(),
+            child.stdout().take().unwrap(),
+            Default::default(),
+        )
+        .await?;
+        Ok(Self {
+            child,
+            fs: sftp.fs(),
+            sftp,
+        })
+    }
+}
+
+/// Parts of a session needed to open a new session over the same connection.
+struct ForkParts {
+    inner: Arc<openssh::Session>,
+    user: Option<String>,
+    port: Option<u16>,
+    destination: String,
+    log_format: LogFormat,
+    log_prefix: String,
+    step: Option<String>,
+    transcript: Option<Transcript>,
+    rate_limiter: Option<RateLimiter>,
+    sftp_mode: SftpMode,
+    run: Option<Arc<RunState>>,
+}
+
+impl ForkParts {
+    async fn connect(self) -> anyhow::Result<Session> {
+        let mut session = Session::from_connection(
+            self.inner,
+            self.user,
+            self.port,
+            self.destination,
+            self.sftp_mode,
+            self.run.or_else(RunState::current),
+        )
+        .await?;
+        session.log_format = self.log_format;
+        session.log_prefix = self.log_prefix;
+        session.step = self.step;
+        session.transcript = self.transcript;
+        session.rate_limiter = self.rate_limiter;
+        Ok(session)
+    }
 }
 
 impl Session {
@@ -163,6 +334,32 @@ impl Session {
         Self::from_openssh_builder(builder, destination).await
     }
 
+    /// Initialize a SSH session with SSH-level compression enabled for all commands and
+    /// transfers. This helps on low-bandwidth links but slows down fast ones.
+    ///
+    /// See `connect` for the format of `destination`.
+    pub async fn connect_compressed(destination: impl AsRef<str>) -> anyhow::Result<Self> {
+        let mut builder = openssh::SessionBuilder::default();
+        builder
+            .known_hosts_check(KnownHosts::Strict)
+            .compression(true);
+        Self::from_openssh_builder(builder, destination).await
+    }
+
+    /// Initialize a SSH session that only executes commands and never opens the SFTP
+    /// subsystem, e.g. for network appliances and git servers that reject subsystem requests.
+    ///
+    /// File operations such as `read_file` and `write_file` are performed with shell
+    /// commands, and `fs` and `sftp` are not available. See `connect` for the format
+    /// of `destination`.
+    pub async fn connect_command_only(destination: impl AsRef<str>) -> anyhow::Result<Self> {
+        let mut builder = openssh::SessionBuilder::default();
+        builder.known_hosts_check(KnownHosts::Strict);
+        let (builder, destination) = builder.resolve(destination.as_ref());
+        let session = builder.connect_mux(destination).await?;
+        Self::from_connected_builder(session, &builder, destination, SftpMode::Disabled).await
+    }
+
     /// Initialize a SSH session from a pre-configured builder.
     /// Allows specifying settings such as port, known hosts policy, etc.
     ///
@@ -177,54 +374,174 @@ impl Session {
     pub async fn from_openssh_builder(
         builder: openssh::SessionBuilder,
         destination: impl AsRef<str>,
+    ) -> anyhow::Result<Self> {
+        Self::from_openssh_builder_with_sftp_mode(builder, destination, SftpMode::Required).await
+    }
+
+    /// Initialize a SSH session from a pre-configured builder, choosing whether SFTP
+    /// is required. Use `SftpMode::Optional` for hosts that may not provide SFTP,
+    /// e.g. minimal containers.
+    ///
+    /// See `from_openssh_builder` for details.
+    pub async fn from_openssh_builder_with_sftp_mode(
+        builder: openssh::SessionBuilder,
+        destination: impl AsRef<str>,
+        sftp_mode: SftpMode,
     ) -> anyhow::Result<Self> {
         let (builder, destination) = builder.resolve(destination.as_ref());
         let session = builder.connect_mux(destination).await?;
-        let session = Arc::new(session);
-        let mut sftp_child = openssh::Session::to_subsystem(session.clone(), "sftp")
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .spawn()
-            .await?;
+        Self::from_connected_builder(session, &builder, destination, sftp_mode).await
+    }
 
-        let sftp = Sftp::new(
-            sftp_child.stdin().take().unwrap(),
-            sftp_child.stdout().take().unwrap(),
-            Default::default(),
+    async fn from_connected_builder(
+        session: openssh::Session,
+        builder: &openssh::SessionBuilder,
+        destination: &str,
+        sftp_mode: SftpMode,
+    ) -> anyhow::Result<Self> {
+        let port = builder
+            .get_port()
+            .map(|s| s.parse())
+            .transpose()
+            .context("invalid port")?;
+        Self::from_connection(
+            Arc::new(session),
+            builder.get_user().map(Into::into),
+            port,
+            destination.into(),
+            sftp_mode,
+            RunState::current(),
         )
-        .await?;
+        .await
+    }
+
+    /// Open another session that shares the SSH connection with this session.
+    ///
+    /// The new session has its own SFTP channel, cache and timings, and inherits
+    /// logging settings. It can be moved to another task to run commands concurrently.
+    pub async fn fork(&self) -> anyhow::Result<Self> {
+        self.fork_parts().connect().await
+    }
+
+    fn fork_parts(&self) -> ForkParts {
+        ForkParts {
+            inner: self.inner.clone(),
+            user: self.user.clone(),
+            port: self.port,
+            destination: self.destination.clone(),
+            log_format: self.log_format,
+            log_prefix: self.log_prefix.clone(),
+            step: self.step.clone(),
+            transcript: self.transcript.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            // Don't retry opening SFTP if it's unavailable or disabled.
+            sftp_mode: if self.has_sftp() {
+                SftpMode::Required
+            } else {
+                SftpMode::Disabled
+            },
+            run: self.run.clone(),
+        }
+    }
+
+    async fn from_connection(
+        session: Arc<openssh::Session>,
+        user: Option<String>,
+        port: Option<u16>,
+        destination: String,
+        sftp_mode: SftpMode,
+        run: Option<Arc<RunState>>,
+    ) -> anyhow::Result<Self> {
+        let sftp = match sftp_mode {
+            SftpMode::Required => Some(SftpChannel::open(session.clone()).await?),
+            // Some hosts (e.g. minimal containers and network appliances) don't provide SFTP.
+            // File operations fall back to shell commands there.
+            SftpMode::Optional => match SftpChannel::open(session.clone()).await {
+                Ok(sftp) => Some(sftp),
+                Err(err) => {
+                    warn!(
+                        "SFTP is not available on {destination}, \
+                         using shell commands for file operations: {err:#}"
+                    );
+                    None
+                }
+            },
+            SftpMode::Disabled => None,
+        };
 
+        let stats = Arc::<SessionStats>::default();
+        if let Some(run) = &run {
+            run.register_session(&destination, &session, &stats);
+        }
         Ok(Session {
-            user: builder.get_user().map(Into::into),
-            port: builder
-                .get_port()
-                .map(|s| s.parse())
-                .transpose()
-                .context("invalid port")?,
-            destination: destination.into(),
+            user,
+            port,
+            destination,
             inner: session,
-            sftp_child,
-            fs: sftp.fs(),
             sftp,
             cache: TypeMap::new(),
+            log_format: LogFormat::default(),
+            log_prefix: String::new(),
+            step: None,
+            transcript: None,
+            timings: Mutex::new(Vec::new()),
+            stats,
+            log_summary_on_drop: false,
+            rate_limiter: None,
+            tasks: TaskSet::default(),
+            run,
         })
     }
 
     /// Access the SFTP subsystem - a file-oriented channel to a remote host.
     ///
     /// See also `fs`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if SFTP was not opened for this session, which can only happen with
+    /// `SftpMode::Optional` or `SftpMode::Disabled`. Use `has_sftp` to check it.
     pub fn sftp(&mut self) -> &mut Sftp {
-        &mut self.sftp
+        &mut self.sftp_channel().sftp
     }
 
-    /// Perform operations on a remote filesystem.
+    /// Perform operations on a remote filesystem over SFTP.
+    ///
+    /// `read_file`, `write_file` and `remove_file` also work on hosts without SFTP.
+    ///
+    /// # Panics
+    ///
+    /// Panics if SFTP was not opened for this session, which can only happen with
+    /// `SftpMode::Optional` or `SftpMode::Disabled`. Use `has_sftp` to check it.
     pub fn fs(&mut self) -> &mut Fs {
-        &mut self.fs
+        &mut self.sftp_channel().fs
+    }
+
+    fn sftp_channel(&mut self) -> &mut SftpChannel {
+        self.sftp
+            .as_mut()
+            .expect("SFTP is not available for this session")
+    }
+
+    /// Check if SFTP is available for this session. If it's not, file operations
+    /// of the session are performed with shell commands.
+    pub fn has_sftp(&self) -> bool {
+        self.sftp.is_some()
     }
 
     /// Check if a path exists on a remote filesystem.
     pub async fn path_exists(&mut self, path: impl AsRef<Path>) -> anyhow::Result<bool> {
-        match self.fs().metadata(path).await {
+        let Some(sftp) = &mut self.sftp else {
+            let path = path_str(path.as_ref())?;
+            let code = self
+                .command(["test", "-e", path])
+                .hide_command()
+                .hide_all_output()
+                .exit_code()
+                .await?;
+            return Ok(code == 0);
+        };
+        match sftp.fs.metadata(path).await {
             Ok(_) => Ok(true),
             Err(Error::SftpError(SftpErrorKind::NoSuchFile, _)) => Ok(false),
             Err(err) => Err(err.into()),
@@ -237,4 +554,74 @@ impl Session {
     pub fn cache(&mut self) -> &mut TypeMap {
         &mut self.cache
     }
+
+    /// Set the format of command and output logs emitted by this session.
+    /// The default is `LogFormat::Text`.
+    pub fn set_log_format(&mut self, format: LogFormat) {
+        self.log_format = format;
+    }
+
+    /// Set a prefix that will be prepended to all text logs of commands and their output
+    /// executed in this session. This helps to distinguish logs of multiple sessions running
+    /// concurrently.
+    ///
+    /// See also `set_host_log_prefix`.
+    pub fn set_log_prefix(&mut self, prefix: impl Into<String>) {
+        self.log_prefix = prefix.into();
+    }
+
+    /// Prepend `[destination] ` to all text logs of commands and their output
+    /// executed in this session.
+    pub fn set_host_log_prefix(&mut self) {
+        self.log_prefix = format!("[{}] ", self.destination);
+    }
+
+    /// Start a named step of a run, e.g. `"install nginx"`. Until `finish_step` is called,
+    /// JSON log records of this session include a `step` field.
+    pub fn start_step(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        self.step = Some(name.clone());
+        let log_context = self.log_context();
+        match log_context.format {
+            LogFormat::Text => info!("{}step {name:?} started", log_context.prefix),
+            LogFormat::Json => log_context.json(log::Level::Info, "step_started", json!({})),
+        }
+    }
+
+    /// Log the outcome of the current step and clear it. Pass the result of the step,
+    /// e.g. of a recipe call.
+    pub fn finish_step<T>(&mut self, result: &anyhow::Result<T>) {
+        let log_context = self.log_context();
+        let name = self.step.take().unwrap_or_default();
+        match (log_context.format, result) {
+            (LogFormat::Text, Ok(_)) => info!("{}step {name:?} succeeded", log_context.prefix),
+            (LogFormat::Text, Err(err)) => {
+                warn!("{}step {name:?} failed: {err:#}", log_context.prefix);
+            }
+            (LogFormat::Json, Ok(_)) => log_context.json(
+                log::Level::Info,
+                "step_finished",
+                json!({ "success": true }),
+            ),
+            (LogFormat::Json, Err(err)) => log_context.json(
+                log::Level::Warn,
+                "step_finished",
+                json!({ "success": false, "error": format!("{err:#}") }),
+            ),
+        }
+    }
+
+    /// Record all commands executed in this session and their full output to `transcript`.
+    pub fn set_transcript(&mut self, transcript: Transcript) {
+        self.transcript = Some(transcript);
+    }
+
+    fn log_context(&self) -> logging::LogContext {
+        logging::LogContext {
+            format: self.log_format,
+            host: self.destination.clone(),
+            prefix: self.log_prefix.clone(),
+            step: self.step.clone(),
+        }
+    }
 }
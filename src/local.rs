@@ -1,15 +1,19 @@
 use std::{
     fmt::Write,
-    io::{BufRead, BufReader, Read},
-    process::Stdio,
+    io::{self, BufRead, BufReader, Read, Write as _},
+    process::{Child, ExitStatus, Stdio},
     thread,
+    time::{Duration, Instant},
 };
 
 use anyhow::{anyhow, bail, Context};
 use log::log;
 use tokio::task::block_in_place;
 
-use crate::CommandOutput;
+use crate::{CommandOutput, CommandTimeout};
+
+const DEFAULT_KILL_AFTER: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 /// A local command executor with an interface similar to the remote command executor.
 ///
@@ -20,6 +24,11 @@ pub struct LocalCommand {
     stdout_log_level: log::Level,
     stderr_log_level: log::Level,
     allow_failure: bool,
+    redact_stdout: bool,
+    log_prefix: String,
+    timeout: Option<Duration>,
+    kill_after: Duration,
+    stdin: Option<Vec<u8>>,
 }
 
 impl LocalCommand {
@@ -31,6 +40,11 @@ impl LocalCommand {
             stdout_log_level: log::Level::Info,
             stderr_log_level: log::Level::Error,
             allow_failure: false,
+            redact_stdout: false,
+            log_prefix: String::new(),
+            timeout: None,
+            kill_after: DEFAULT_KILL_AFTER,
+            stdin: None,
         }
     }
 
@@ -47,6 +61,14 @@ impl LocalCommand {
         self
     }
 
+    /// Pass `data` (a string or bytes) to the standard input of the command.
+    /// By default, the command's stdin is empty. Unlike arguments, stdin is not visible
+    /// to other local users, so use it to pass secrets.
+    pub fn stdin(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.stdin = Some(data.into());
+        self
+    }
+
     /// Mark the command as possibly expecting a failure.
     /// If `allow_failure` is called before `run`, `run` will no longer return
     /// an error on non-zero exit code.
@@ -55,6 +77,20 @@ impl LocalCommand {
         self
     }
 
+    /// Abort the command if it doesn't finish within `timeout`. The process is sent
+    /// `SIGTERM` and, if it's still running after the grace period set by `kill_after`
+    /// (5 seconds by default), `SIGKILL`. `run` then returns a [`CommandTimeout`] error.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set the grace period between `SIGTERM` and `SIGKILL` for `timeout`.
+    pub fn kill_after(mut self, grace_period: Duration) -> Self {
+        self.kill_after = grace_period;
+        self
+    }
+
     /// Execute the command and capture the output.
     ///
     /// By default, non-exit error code will cause `run` to return an error.
@@ -69,24 +105,52 @@ impl LocalCommand {
         }
         log!(
             self.command_log_level,
-            "running local command: {:?}",
+            "{}running local command: {:?}",
+            self.log_prefix,
             self.command
         );
         let mut child = std::process::Command::new(&self.command[0])
             .args(&self.command[1..])
-            .stdin(Stdio::null())
+            .stdin(if self.stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()?;
 
+        let stdin_task = match (self.stdin, child.stdin.take()) {
+            (Some(data), Some(mut stdin)) => Some(thread::spawn(move || stdin.write_all(&data))),
+            _ => None,
+        };
+
         let stderr_reader = child.stderr.take().context("missing stderr")?;
         let stdout_reader = child.stdout.take().context("missing stdout")?;
-        let stderr_task =
-            thread::spawn(move || handle_output(stderr_reader, self.stderr_log_level, "stderr: "));
+        let stderr_prefix = format!("{}stderr: ", self.log_prefix);
+        let stderr_task = thread::spawn(move || {
+            handle_output(stderr_reader, Some(self.stderr_log_level), &stderr_prefix)
+        });
+        let stdout_prefix = format!("{}stdout: ", self.log_prefix);
+        let stdout_log_level = if self.redact_stdout {
+            None
+        } else {
+            Some(self.stdout_log_level)
+        };
         let stdout_task =
-            thread::spawn(move || handle_output(stdout_reader, self.stdout_log_level, "stdout: "));
+            thread::spawn(move || handle_output(stdout_reader, stdout_log_level, &stdout_prefix));
 
-        let status = block_in_place(|| child.wait())?;
+        let status = match self.timeout {
+            Some(timeout) => {
+                block_in_place(|| wait_with_timeout(&mut child, timeout, self.kill_after))?
+                    .ok_or(CommandTimeout { timeout })?
+            }
+            None => block_in_place(|| child.wait())?,
+        };
+        if let Some(stdin_task) = stdin_task {
+            // The command may exit without reading all of its input, so write errors are ignored.
+            let _ = block_in_place(|| stdin_task.join());
+        }
         let exit_code = status.code().context("missing exit code")?;
         if !self.allow_failure && exit_code != 0 {
             bail!("local command failed with exit code {}", exit_code);
@@ -97,6 +161,8 @@ impl LocalCommand {
                 .map_err(|_| anyhow!("local output handler panicked"))??,
             stderr: block_in_place(|| stderr_task.join())
                 .map_err(|_| anyhow!("local output handler panicked"))??,
+            stdout_overflow: None,
+            stderr_overflow: None,
         })
     }
 
@@ -126,6 +192,12 @@ impl LocalCommand {
         self
     }
 
+    /// Don't log stdout at any level. Use it for commands that output secrets.
+    pub fn redact_stdout(mut self) -> Self {
+        self.redact_stdout = true;
+        self
+    }
+
     /// Lower stderr logs to `Trace`.
     pub fn hide_stderr(mut self) -> Self {
         self.stderr_log_level = log::Level::Trace;
@@ -149,15 +221,72 @@ impl LocalCommand {
         self.command_log_level = level;
         self
     }
+
+    /// Set a prefix that will be prepended to logs of the command and its output.
+    pub fn log_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.log_prefix = prefix.into();
+        self
+    }
+}
+
+/// Render options for `curl --config -`, so that secrets passed through stdin
+/// don't appear in the local process list.
+pub(crate) fn curl_config<'a>(options: impl IntoIterator<Item = (&'a str, &'a str)>) -> String {
+    let mut config = String::new();
+    for (name, value) in options {
+        let value = value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r")
+            .replace('\t', "\\t");
+        config.push_str(&format!("{name} = \"{value}\"\n"));
+    }
+    config
 }
 
-fn handle_output(reader: impl Read, log_level: log::Level, prefix: &str) -> anyhow::Result<String> {
+fn handle_output(
+    reader: impl Read,
+    log_level: Option<log::Level>,
+    prefix: &str,
+) -> anyhow::Result<String> {
     let reader = BufReader::new(reader);
     let mut output = String::new();
     for line in reader.lines() {
         let line = line?;
         writeln!(output, "{}", line)?;
-        log!(log_level, "{}{}", prefix, &line);
+        if let Some(log_level) = log_level {
+            log!(log_level, "{}{}", prefix, &line);
+        }
     }
     Ok(output)
 }
+
+/// Wait for `child` to exit. If it doesn't exit within `timeout`, terminate it and return `None`.
+fn wait_with_timeout(
+    child: &mut Child,
+    timeout: Duration,
+    kill_after: Duration,
+) -> io::Result<Option<ExitStatus>> {
+    let started = Instant::now();
+    while started.elapsed() < timeout {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    // Send SIGTERM first so that the process can clean up.
+    let _ = std::process::Command::new("kill")
+        .args(["-TERM", &child.id().to_string()])
+        .status();
+    let terminated = Instant::now();
+    while terminated.elapsed() < kill_after {
+        if child.try_wait()?.is_some() {
+            return Ok(None);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    child.kill()?;
+    child.wait()?;
+    Ok(None)
+}
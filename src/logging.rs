@@ -0,0 +1,52 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::log;
+use serde_json::{json, Value};
+
+/// Format of command and output logs emitted by a `Session`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// Human-readable log lines.
+    #[default]
+    Text,
+    /// One JSON object per log record, suitable for ingestion into log aggregation systems.
+    ///
+    /// Each record contains `timestamp` (seconds since Unix epoch), `host` and `event` fields,
+    /// as well as event-specific fields. Redacted arguments are replaced by their placeholders
+    /// and the record is marked with `"redacted": true`. Records emitted within a step
+    /// (see `Session::start_step`) also contain a `step` field, and the outcome of a step
+    /// is logged as a `step_finished` event.
+    ///
+    /// Only command, output and step events are structured. Other messages of recipes
+    /// are logged as free text through the `log` crate.
+    Json,
+}
+
+/// Log settings of a session, captured by a command before execution.
+#[derive(Debug, Clone)]
+pub(crate) struct LogContext {
+    pub format: LogFormat,
+    pub host: String,
+    /// Prefix for text logs.
+    pub prefix: String,
+    /// Current step set by `Session::start_step`.
+    pub step: Option<String>,
+}
+
+impl LogContext {
+    /// Emit a JSON log record. `fields` must be a JSON object.
+    pub fn json(&self, level: log::Level, event: &str, mut fields: Value) {
+        if let Value::Object(map) = &mut fields {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0.0, |d| d.as_secs_f64());
+            map.insert("timestamp".into(), json!(timestamp));
+            map.insert("host".into(), json!(self.host));
+            map.insert("event".into(), json!(event));
+            if let Some(step) = &self.step {
+                map.insert("step".into(), json!(step));
+            }
+        }
+        log!(level, "{}", fields);
+    }
+}
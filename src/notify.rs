@@ -0,0 +1,186 @@
+//! Notifications about results of a run.
+//!
+//! `roguewave` doesn't have a built-in run framework, so notifications are sent explicitly:
+//! ```no_run
+//! use roguewave::notify::{report_result, Webhook, WebhookKind};
+//!
+//! # async fn deploy() -> anyhow::Result<()> { Ok(()) }
+//! # #[tokio::main]
+//! # async fn main() -> anyhow::Result<()> {
+//! let notifier = Webhook::new(WebhookKind::Slack, "https://hooks.slack.com/services/...");
+//! let result = deploy().await;
+//! report_result(&notifier, "production deploy", &result).await?;
+//! result
+//! # }
+//! ```
+//! Built-in notifiers use `curl` on the local machine.
+
+use std::{
+    env, fs,
+    future::Future,
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context};
+use serde_json::json;
+
+use crate::{local::curl_config, LocalCommand};
+
+/// A message about an outcome of a run or a step.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Notification {
+    /// Short summary.
+    pub title: String,
+    /// Details, e.g. the error message.
+    pub message: String,
+    /// Whether the outcome was successful.
+    pub success: bool,
+}
+
+/// A destination for notifications.
+pub trait Notifier {
+    /// Deliver a notification.
+    fn notify(
+        &self,
+        notification: &Notification,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send;
+}
+
+/// Send a success or failure notification depending on `result`.
+pub async fn report_result<T>(
+    notifier: &impl Notifier,
+    name: &str,
+    result: &anyhow::Result<T>,
+) -> anyhow::Result<()> {
+    let notification = match result {
+        Ok(_) => Notification {
+            title: format!("{name} succeeded"),
+            message: String::new(),
+            success: true,
+        },
+        Err(err) => Notification {
+            title: format!("{name} failed"),
+            message: format!("{err:#}"),
+            success: false,
+        },
+    };
+    notifier.notify(&notification).await
+}
+
+/// Format of a webhook payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WebhookKind {
+    /// Slack incoming webhook (`{"text": ...}`).
+    Slack,
+    /// Discord webhook (`{"content": ...}`).
+    Discord,
+    /// A JSON object with `title`, `message` and `success` fields.
+    Generic,
+}
+
+/// Sends notifications as JSON POST requests.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    kind: WebhookKind,
+    url: String,
+}
+
+impl Webhook {
+    /// Create a webhook notifier. The URL is never logged because it usually contains a token.
+    pub fn new(kind: WebhookKind, url: impl Into<String>) -> Self {
+        Self {
+            kind,
+            url: url.into(),
+        }
+    }
+}
+
+impl Notifier for Webhook {
+    async fn notify(&self, notification: &Notification) -> anyhow::Result<()> {
+        let text = if notification.message.is_empty() {
+            notification.title.clone()
+        } else {
+            format!("{}\n{}", notification.title, notification.message)
+        };
+        let payload = match self.kind {
+            WebhookKind::Slack => json!({ "text": text }),
+            WebhookKind::Discord => json!({ "content": text }),
+            WebhookKind::Generic => json!({
+                "title": notification.title,
+                "message": notification.message,
+                "success": notification.success,
+            }),
+        };
+        // The URL is passed through stdin to keep it out of the process list.
+        let payload = payload.to_string();
+        LocalCommand::new(["curl", "--fail", "--silent", "--show-error", "--config", "-"])
+            .stdin(curl_config([
+                ("url", self.url.as_str()),
+                ("header", "Content-Type: application/json"),
+                ("data", payload.as_str()),
+            ]))
+            .hide_command()
+            .hide_stdout()
+            .run()
+            .await?;
+        Ok(())
+    }
+}
+
+/// Sends notifications as emails over SMTP.
+///
+/// Titles, senders and recipients containing line breaks are rejected.
+#[derive(Debug, Clone)]
+pub struct Smtp {
+    /// SMTP server URL, e.g. `smtps://smtp.example.com:465` or `smtp://localhost`.
+    pub url: String,
+    /// Login and password for the SMTP server.
+    pub credentials: Option<(String, String)>,
+    /// Sender address.
+    pub from: String,
+    /// Recipient addresses.
+    pub to: Vec<String>,
+}
+
+impl Notifier for Smtp {
+    async fn notify(&self, notification: &Notification) -> anyhow::Result<()> {
+        for (name, value) in [("subject", &notification.title), ("sender", &self.from)]
+            .into_iter()
+            .chain(self.to.iter().map(|to| ("recipient", to)))
+        {
+            if value.contains(['\r', '\n']) {
+                bail!("mail {name} must not contain line breaks: {value:?}");
+            }
+        }
+        let mail = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\n\r\n{}\r\n",
+            self.from,
+            self.to.join(", "),
+            notification.title,
+            notification.message.replace('\n', "\r\n"),
+        );
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+        let mail_path = env::temp_dir().join(format!("roguewave-mail-{}-{nanos}", process::id()));
+        let mail_path_str = mail_path.to_str().context("non-utf8 path")?;
+        fs::write(&mail_path, mail)?;
+
+        let mut command = LocalCommand::new(["curl", "--fail", "--silent", "--show-error"])
+            .args(["--url", &self.url, "--mail-from", &self.from])
+            .hide_command();
+        for to in &self.to {
+            command = command.args(["--mail-rcpt", to]);
+        }
+        if let Some((user, password)) = &self.credentials {
+            // Credentials are passed through stdin to keep them out of the process list.
+            let user = format!("{user}:{password}");
+            command = command
+                .args(["--config", "-"])
+                .stdin(curl_config([("user", user.as_str())]));
+        }
+        let result = command.arg("--upload-file").arg(mail_path_str).run().await;
+        fs::remove_file(&mail_path)?;
+        result?;
+        Ok(())
+    }
+}
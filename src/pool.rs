@@ -0,0 +1,181 @@
+//! Reusable connections to many hosts.
+//!
+//! ```no_run
+//! use roguewave::pool::SessionPool;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> anyhow::Result<()> {
+//! let pool = SessionPool::new(50);
+//! for host in ["root@host1", "root@host2"] {
+//!     let session = pool.get(host).await?;
+//!     session.command(["uptime"]).run().await?;
+//! } // The session is returned to the pool here.
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use anyhow::Result;
+use openssh::{KnownHosts, SessionBuilder};
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+
+use crate::Session;
+
+type IdleSessions = Arc<Mutex<HashMap<String, Vec<(Session, OwnedSemaphorePermit)>>>>;
+
+/// A pool of sessions keyed by destination.
+///
+/// Connections are established lazily on first use and reused afterwards.
+/// The total number of open connections (busy and idle) is capped: when the limit is reached,
+/// idle connections to other hosts are closed, or `get` waits until a session is released.
+pub struct SessionPool {
+    builder: SessionBuilder,
+    semaphore: Arc<Semaphore>,
+    idle: IdleSessions,
+    /// Notified when a session is returned to the pool or closed.
+    released: Arc<Notify>,
+}
+
+impl SessionPool {
+    /// Create a pool that keeps at most `max_connections` connections open.
+    /// Connections use the same settings as `Session::connect`.
+    pub fn new(max_connections: usize) -> Self {
+        let mut builder = SessionBuilder::default();
+        builder.known_hosts_check(KnownHosts::Strict);
+        Self::with_builder(builder, max_connections)
+    }
+
+    /// Create a pool that uses `builder` to open connections
+    /// (see `Session::from_openssh_builder`).
+    pub fn with_builder(builder: SessionBuilder, max_connections: usize) -> Self {
+        Self {
+            builder,
+            semaphore: Arc::new(Semaphore::new(max_connections)),
+            idle: Arc::default(),
+            released: Arc::default(),
+        }
+    }
+
+    /// Get a session to `destination`, reusing an idle one or connecting if necessary.
+    /// The session is returned to the pool when the `PooledSession` is dropped.
+    pub async fn get(&self, destination: &str) -> Result<PooledSession> {
+        let permit = loop {
+            // Subscribe before checking, so that a release in between is not missed.
+            let released = self.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+            if let Some((session, permit)) = self.take_idle(Some(destination)) {
+                return Ok(self.pooled(destination, session, permit));
+            }
+            if let Ok(permit) = self.semaphore.clone().try_acquire_owned() {
+                break permit;
+            }
+            // Close an idle connection to another host and reuse its slot.
+            if let Some((session, permit)) = self.take_idle(None) {
+                drop(session);
+                break permit;
+            }
+            released.await;
+        };
+        let result = Session::from_openssh_builder(self.builder.clone(), destination).await;
+        let session = match result {
+            Ok(session) => session,
+            Err(err) => {
+                drop(permit);
+                self.released.notify_waiters();
+                return Err(err);
+            }
+        };
+        Ok(self.pooled(destination, session, permit))
+    }
+
+    /// Close all idle connections.
+    pub fn close_idle(&self) {
+        self.idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        self.released.notify_waiters();
+    }
+
+    fn take_idle(&self, destination: Option<&str>) -> Option<(Session, OwnedSemaphorePermit)> {
+        let mut idle = self.idle.lock().unwrap_or_else(PoisonError::into_inner);
+        let key = match destination {
+            Some(destination) => destination.to_string(),
+            None => idle
+                .iter()
+                .find(|(_, sessions)| !sessions.is_empty())?
+                .0
+                .clone(),
+        };
+        let sessions = idle.get_mut(&key)?;
+        let item = sessions.pop();
+        if sessions.is_empty() {
+            idle.remove(&key);
+        }
+        item
+    }
+
+    fn pooled(
+        &self,
+        destination: &str,
+        session: Session,
+        permit: OwnedSemaphorePermit,
+    ) -> PooledSession {
+        PooledSession {
+            item: Some((session, permit)),
+            destination: destination.into(),
+            idle: self.idle.clone(),
+            released: self.released.clone(),
+        }
+    }
+}
+
+/// A session borrowed from a `SessionPool`. Dereferences to `Session`.
+pub struct PooledSession {
+    item: Option<(Session, OwnedSemaphorePermit)>,
+    destination: String,
+    idle: IdleSessions,
+    released: Arc<Notify>,
+}
+
+impl PooledSession {
+    /// Close the connection instead of returning it to the pool
+    /// (e.g. if the connection is known to be broken).
+    pub fn discard(mut self) {
+        self.item = None;
+    }
+}
+
+impl Deref for PooledSession {
+    type Target = Session;
+
+    fn deref(&self) -> &Session {
+        &self.item.as_ref().unwrap().0
+    }
+}
+
+impl DerefMut for PooledSession {
+    fn deref_mut(&mut self) -> &mut Session {
+        &mut self.item.as_mut().unwrap().0
+    }
+}
+
+impl Drop for PooledSession {
+    fn drop(&mut self) {
+        if let Some(item) = self.item.take() {
+            self.idle
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .entry(std::mem::take(&mut self.destination))
+                .or_default()
+                .push(item);
+        }
+        self.released.notify_waiters();
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use tokio::{
+    sync::{Mutex as AsyncMutex, OwnedSemaphorePermit, Semaphore},
+    time::Instant,
+};
+
+use crate::Session;
+
+static GLOBAL_RATE_LIMITER: Mutex<Option<RateLimiter>> = Mutex::new(None);
+
+/// Limits the rate of remote commands and file transfers.
+///
+/// A limiter can be shared between sessions (it's cheap to clone) or installed globally
+/// with [`set_global_rate_limiter`] to throttle a fleet-wide run, e.g. to avoid overloading
+/// a jump host or a package mirror.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    semaphore: Option<Arc<Semaphore>>,
+    min_interval: Duration,
+    next_start: Arc<AsyncMutex<Option<Instant>>>,
+}
+
+impl RateLimiter {
+    /// Create a limiter that doesn't limit anything yet.
+    pub fn new() -> Self {
+        Self {
+            semaphore: None,
+            min_interval: Duration::ZERO,
+            next_start: Arc::new(AsyncMutex::new(None)),
+        }
+    }
+
+    /// Allow at most `count` operations to run at the same time.
+    pub fn max_concurrent(mut self, count: usize) -> Self {
+        self.semaphore = Some(Arc::new(Semaphore::new(count.max(1))));
+        self
+    }
+
+    /// Start operations at least `interval` apart.
+    pub fn min_interval(mut self, interval: Duration) -> Self {
+        self.min_interval = interval;
+        self
+    }
+
+    /// Start at most `count` operations per second.
+    pub fn per_second(self, count: u32) -> Self {
+        self.min_interval(Duration::from_secs(1) / count.max(1))
+    }
+
+    async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        let permit = match &self.semaphore {
+            Some(semaphore) => Some(
+                semaphore
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("semaphore is never closed"),
+            ),
+            None => None,
+        };
+        if !self.min_interval.is_zero() {
+            let mut next_start = self.next_start.lock().await;
+            let now = Instant::now();
+            let start = next_start.map_or(now, |next| next.max(now));
+            *next_start = Some(start + self.min_interval);
+            drop(next_start);
+            tokio::time::sleep_until(start).await;
+        }
+        permit
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Apply `limiter` to commands and transfers of all sessions, in addition to their own
+/// limiters. Pass `None` to remove the global limiter.
+pub fn set_global_rate_limiter(limiter: Option<RateLimiter>) {
+    *GLOBAL_RATE_LIMITER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) = limiter;
+}
+
+/// Permits held while a rate-limited operation runs.
+pub(crate) struct RateLimitPermit {
+    _permits: Vec<OwnedSemaphorePermit>,
+}
+
+impl Session {
+    /// Apply `limiter` to commands and transfers of this session. Pass `None` to remove it.
+    /// Sessions created with `fork` inherit the limiter.
+    pub fn set_rate_limiter(&mut self, limiter: Option<RateLimiter>) {
+        self.rate_limiter = limiter;
+    }
+
+    /// Wait until the global and session limiters allow an operation to start.
+    pub(crate) async fn throttle(&self) -> RateLimitPermit {
+        let global = GLOBAL_RATE_LIMITER
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let mut permits = Vec::new();
+        for limiter in global.iter().chain(&self.rate_limiter) {
+            permits.extend(limiter.acquire().await);
+        }
+        RateLimitPermit { _permits: permits }
+    }
+}
@@ -0,0 +1,263 @@
+use std::fmt;
+
+use anyhow::{bail, Context, Result};
+use log::info;
+
+use crate::Session;
+
+/// Who an ACL entry applies to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AclTag {
+    /// The file owner (`None`) or a named user.
+    User(Option<String>),
+    /// The owning group (`None`) or a named group.
+    Group(Option<String>),
+    /// Maximum permissions for named users and groups and the owning group.
+    Mask,
+    /// Everyone else.
+    Other,
+}
+
+/// Permissions granted by an ACL entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct AclPermissions {
+    /// `r`
+    pub read: bool,
+    /// `w`
+    pub write: bool,
+    /// `x`
+    pub execute: bool,
+}
+
+impl AclPermissions {
+    /// Parse permissions in `rwx` format (e.g. `r-x`).
+    pub fn parse(value: &str) -> Result<Self> {
+        let chars: Vec<char> = value.chars().collect();
+        let [r, w, x] = chars[..] else {
+            bail!("invalid ACL permissions: {value:?}");
+        };
+        let flag = |c: char, expected: char| match c {
+            '-' => Ok(false),
+            _ if c == expected => Ok(true),
+            _ => bail!("invalid ACL permissions: {value:?}"),
+        };
+        Ok(Self {
+            read: flag(r, 'r')?,
+            write: flag(w, 'w')?,
+            execute: flag(x, 'x')?,
+        })
+    }
+}
+
+impl fmt::Display for AclPermissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            if self.read { 'r' } else { '-' },
+            if self.write { 'w' } else { '-' },
+            if self.execute { 'x' } else { '-' }
+        )
+    }
+}
+
+/// An entry of a POSIX access control list.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AclEntry {
+    /// Whether this is a default ACL entry (inherited by new files in a directory).
+    pub default: bool,
+    /// Who the entry applies to.
+    pub tag: AclTag,
+    /// Granted permissions.
+    pub permissions: AclPermissions,
+}
+
+impl AclEntry {
+    /// Parse an entry in `getfacl` format (e.g. `user:alice:r-x` or `default:group::r--`).
+    pub fn parse(value: &str) -> Result<Self> {
+        let (default, value) = match value.strip_prefix("default:") {
+            Some(value) => (true, value),
+            None => (false, value),
+        };
+        let parts: Vec<&str> = value.split(':').collect();
+        let [tag, qualifier, permissions] = parts[..] else {
+            bail!("invalid ACL entry: {value:?}");
+        };
+        let qualifier = (!qualifier.is_empty()).then(|| qualifier.to_string());
+        let tag = match tag {
+            "user" | "u" => AclTag::User(qualifier),
+            "group" | "g" => AclTag::Group(qualifier),
+            "mask" | "m" => AclTag::Mask,
+            "other" | "o" => AclTag::Other,
+            _ => bail!("invalid ACL entry: {value:?}"),
+        };
+        Ok(Self {
+            default,
+            tag,
+            permissions: AclPermissions::parse(permissions)?,
+        })
+    }
+
+    fn tag_parts(&self) -> (&str, &str) {
+        let (tag, qualifier) = match &self.tag {
+            AclTag::User(name) => ("user", name.as_deref()),
+            AclTag::Group(name) => ("group", name.as_deref()),
+            AclTag::Mask => ("mask", None),
+            AclTag::Other => ("other", None),
+        };
+        (tag, qualifier.unwrap_or_default())
+    }
+
+    fn tag_spec(&self) -> Result<String> {
+        let (tag, qualifier) = self.tag_parts();
+        if qualifier
+            .chars()
+            .any(|c| !(c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-'))
+        {
+            bail!("invalid user or group name: {qualifier:?}");
+        }
+        let prefix = if self.default { "default:" } else { "" };
+        Ok(format!("{prefix}{tag}:{qualifier}"))
+    }
+}
+
+impl fmt::Display for AclEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefix = if self.default { "default:" } else { "" };
+        let (tag, qualifier) = self.tag_parts();
+        write!(f, "{prefix}{tag}:{qualifier}:{}", self.permissions)
+    }
+}
+
+impl Session {
+    /// Manage POSIX ACLs and extended attributes of remote files.
+    ///
+    /// Requires `getfacl`/`setfacl` (`acl` package) and `getfattr`/`setfattr` (`attr` package).
+    pub fn acl(&mut self) -> Acl<'_> {
+        Acl(self)
+    }
+}
+
+/// Provides access to POSIX ACLs and extended attributes.
+pub struct Acl<'a>(&'a mut Session);
+
+impl Acl<'_> {
+    /// Read the ACL of a file, including default entries.
+    pub async fn get(&mut self, path: &str) -> Result<Vec<AclEntry>> {
+        let output = self
+            .0
+            .command([
+                "getfacl",
+                "--omit-header",
+                "--absolute-names",
+                "--no-effective",
+                "--",
+                path,
+            ])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?;
+        output
+            .stdout_lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(AclEntry::parse)
+            .collect()
+    }
+
+    /// Add or update ACL entries of a file (`setfacl --modify`). Entries that are already
+    /// present are not changed. Returns `true` if the ACL was changed.
+    pub async fn set(&mut self, path: &str, entries: &[AclEntry], recursive: bool) -> Result<bool> {
+        if !recursive {
+            let current = self.get(path).await?;
+            if entries.iter().all(|entry| current.contains(entry)) {
+                return Ok(false);
+            }
+        }
+        let spec = entries
+            .iter()
+            .map(|entry| Ok(format!("{}:{}", entry.tag_spec()?, entry.permissions)))
+            .collect::<Result<Vec<_>>>()?
+            .join(",");
+        let mut command = self.0.command(["setfacl"]);
+        if recursive {
+            command = command.arg("--recursive");
+        }
+        command.args(["--modify", &spec, "--", path]).run().await?;
+        info!("updated ACL of {path:?}");
+        Ok(true)
+    }
+
+    /// Remove ACL entries matching `entries` (permissions are ignored).
+    pub async fn remove(
+        &mut self,
+        path: &str,
+        entries: &[AclEntry],
+        recursive: bool,
+    ) -> Result<()> {
+        let spec = entries
+            .iter()
+            .map(AclEntry::tag_spec)
+            .collect::<Result<Vec<_>>>()?
+            .join(",");
+        let mut command = self.0.command(["setfacl"]);
+        if recursive {
+            command = command.arg("--recursive");
+        }
+        command.args(["--remove", &spec, "--", path]).run().await?;
+        Ok(())
+    }
+
+    /// Read an extended attribute (e.g. `user.checksum`). Returns `None` if it's not set.
+    pub async fn get_xattr(&mut self, path: &str, name: &str) -> Result<Option<String>> {
+        let output = self
+            .0
+            .command([
+                "getfattr",
+                "--absolute-names",
+                "--only-values",
+                "--name",
+                name,
+                "--",
+                path,
+            ])
+            .hide_command()
+            .hide_all_output()
+            .allow_failure()
+            .run()
+            .await?;
+        if output.exit_code != 0 {
+            if output.stderr.contains("No such attribute") {
+                return Ok(None);
+            }
+            bail!("getfattr failed: {}", output.stderr.trim());
+        }
+        Ok(Some(output.stdout))
+    }
+
+    /// Set an extended attribute. Returns `true` if the value was changed.
+    pub async fn set_xattr(&mut self, path: &str, name: &str, value: &str) -> Result<bool> {
+        if self.get_xattr(path, name).await?.as_deref() == Some(value) {
+            return Ok(false);
+        }
+        self.0
+            .command(["setfattr", "--name", name, "--value", value, "--", path])
+            .run()
+            .await
+            .context("setfattr failed")?;
+        Ok(true)
+    }
+
+    /// Remove an extended attribute. Returns `false` if it was not set.
+    pub async fn remove_xattr(&mut self, path: &str, name: &str) -> Result<bool> {
+        if self.get_xattr(path, name).await?.is_none() {
+            return Ok(false);
+        }
+        self.0
+            .command(["setfattr", "--remove", name, "--", path])
+            .run()
+            .await?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,169 @@
+use std::fmt::Write;
+
+use anyhow::{bail, Result};
+use log::{info, warn};
+
+use crate::{Distro, Session};
+
+const CONFIG_PATH: &str = "/etc/aide/aide.conf";
+const RULES_PATH: &str = "/etc/aide/aide.conf.d/90_roguewave_rules";
+const DATABASE_PATH: &str = "/var/lib/aide/aide.db";
+const CHECK_UNIT: &str = "roguewave-aide-check";
+/// `aide --check` exit codes of 14 and above indicate errors rather than changes.
+const FIRST_ERROR_EXIT_CODE: i32 = 14;
+
+/// Changes reported by `aide --check`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AideReport {
+    /// Paths that were added since the baseline was created.
+    pub added: Vec<String>,
+    /// Paths that were removed.
+    pub removed: Vec<String>,
+    /// Paths with changed content or attributes.
+    pub changed: Vec<String>,
+}
+
+impl AideReport {
+    /// Check if there are no changes.
+    pub fn is_clean(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Parse the `Added entries`, `Removed entries` and `Changed entries` sections
+    /// of `aide --check` output.
+    pub fn parse(output: &str) -> Self {
+        let mut report = Self::default();
+        let mut section: Option<&mut Vec<String>> = None;
+        for line in output.lines() {
+            let line = line.trim_end();
+            match line {
+                "Added entries:" => section = Some(&mut report.added),
+                "Removed entries:" => section = Some(&mut report.removed),
+                "Changed entries:" => section = Some(&mut report.changed),
+                _ if line.is_empty() || line.starts_with("---") => {}
+                // Entries look like `f++++++++++++++++: /etc/new-file`.
+                _ => match (&mut section, line.split_once(": /")) {
+                    (Some(paths), Some((_, path))) => paths.push(format!("/{path}")),
+                    // Detailed information follows the summary.
+                    _ => section = None,
+                },
+            }
+        }
+        report
+    }
+}
+
+impl Session {
+    /// Detect unexpected file changes with [AIDE](https://aide.github.io) on a Debian or
+    /// Ubuntu host.
+    pub fn aide(&mut self) -> Aide<'_> {
+        Aide(self)
+    }
+}
+
+/// Provides access to the AIDE file integrity checker.
+pub struct Aide<'a>(&'a mut Session);
+
+impl Aide<'_> {
+    /// Install AIDE.
+    pub async fn ensure_installed(&mut self) -> Result<()> {
+        self.0
+            .require_os_for("AIDE", &[Distro::Debian, Distro::Ubuntu])
+            .await?;
+        self.0.apt().install(&["aide", "aide-common"]).await
+    }
+
+    /// Watch `paths` for changes of content, permissions and ownership, and ignore
+    /// `excluded` paths, in addition to the default rules of the distribution.
+    /// Returns `true` if the rules were changed. Call `init` to rebuild the baseline.
+    pub async fn set_rules(&mut self, paths: &[&str], excluded: &[&str]) -> Result<bool> {
+        let mut rules = String::new();
+        writeln!(rules, "# Managed by roguewave.")?;
+        writeln!(rules, "RoguewaveCheck = p+i+n+u+g+s+m+c+sha256")?;
+        for path in paths {
+            writeln!(rules, "{} RoguewaveCheck", validate_path(path)?)?;
+        }
+        for path in excluded {
+            writeln!(rules, "!{}", validate_path(path)?)?;
+        }
+        self.0.update_file(RULES_PATH, rules).await
+    }
+
+    /// Check if the baseline database exists.
+    pub async fn is_initialized(&mut self) -> Result<bool> {
+        self.0.path_exists(DATABASE_PATH).await
+    }
+
+    /// Build the baseline database from the current state of the files, replacing
+    /// the existing one. Call it after reviewing and accepting reported changes.
+    pub async fn init(&mut self) -> Result<()> {
+        self.0
+            .command(["aideinit", "--yes", "--force"])
+            .hide_all_output()
+            .low_priority()
+            .run()
+            .await?;
+        info!("initialized AIDE database");
+        Ok(())
+    }
+
+    /// Compare the files with the baseline.
+    pub async fn check(&mut self) -> Result<AideReport> {
+        if !self.is_initialized().await? {
+            bail!("AIDE database is not initialized");
+        }
+        let output = self
+            .0
+            .command(["aide", "--config", CONFIG_PATH, "--check"])
+            .hide_all_output()
+            .low_priority()
+            .allow_failure()
+            .run()
+            .await?;
+        if output.exit_code >= FIRST_ERROR_EXIT_CODE {
+            bail!(
+                "aide --check failed with exit code {}: {}",
+                output.exit_code,
+                output.stderr.trim()
+            );
+        }
+        let report = AideReport::parse(&output.stdout);
+        if !report.is_clean() {
+            warn!(
+                "AIDE detected changes: {} added, {} removed, {} changed",
+                report.added.len(),
+                report.removed.len(),
+                report.changed.len()
+            );
+        }
+        Ok(report)
+    }
+
+    /// Run `aide --check` periodically with a systemd timer. `on_calendar` is
+    /// a systemd calendar expression, e.g. `daily` or `*-*-* 04:00:00`.
+    /// Reports end up in the journal of the `roguewave-aide-check` unit.
+    /// Returns `true` if the timer was changed.
+    pub async fn schedule_check(&mut self, on_calendar: &str) -> Result<bool> {
+        self.0
+            .install_periodic_timer(
+                CHECK_UNIT,
+                "AIDE file integrity check",
+                &format!(
+                    "Nice=19\n\
+                     IOSchedulingClass=idle\n\
+                     ExecStart=/usr/bin/aide --config {CONFIG_PATH} --check\n\
+                     # Exit codes below 14 mean that changes were found.\n\
+                     SuccessExitStatus=1 2 3 4 5 6 7\n"
+                ),
+                on_calendar,
+            )
+            .await
+    }
+}
+
+fn validate_path(path: &str) -> Result<&str> {
+    if !path.starts_with('/') || path.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        bail!("invalid path: {path:?}");
+    }
+    Ok(path)
+}
@@ -0,0 +1,110 @@
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+use crate::Session;
+
+const PROFILES_DIR: &str = "/etc/apparmor.d";
+
+/// Mode of a loaded AppArmor profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AppArmorMode {
+    /// Policy violations are blocked.
+    Enforce,
+    /// Policy violations are logged but not blocked.
+    Complain,
+}
+
+impl Session {
+    /// Manage AppArmor profiles.
+    pub fn apparmor(&mut self) -> AppArmor<'_> {
+        AppArmor(self)
+    }
+}
+
+/// Provides access to AppArmor management commands.
+///
+/// Profiles are referred to by file name in `/etc/apparmor.d`
+/// (e.g. `usr.sbin.nginx`), except for `loaded_profile_mode`, which uses the profile name.
+pub struct AppArmor<'a>(&'a mut Session);
+
+impl AppArmor<'_> {
+    /// Install AppArmor utilities.
+    pub async fn ensure_installed(&mut self) -> Result<()> {
+        self.0.apt().install(&["apparmor", "apparmor-utils"]).await
+    }
+
+    /// Check if AppArmor is enabled in the kernel.
+    pub async fn is_enabled(&mut self) -> Result<bool> {
+        let code = self
+            .0
+            .command(["aa-enabled", "--quiet"])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        Ok(code == 0)
+    }
+
+    /// Fetch the mode of a loaded profile by its name. Returns `None` if the profile is not loaded.
+    pub async fn loaded_profile_mode(&mut self, name: &str) -> Result<Option<AppArmorMode>> {
+        let status: Value = self
+            .0
+            .command(["aa-status", "--json"])
+            .hide_command()
+            .hide_stdout()
+            .run()
+            .await?
+            .json()?;
+        let profiles = status
+            .get("profiles")
+            .context("missing profiles in aa-status output")?;
+        match profiles.get(name).and_then(|mode| mode.as_str()) {
+            None => Ok(None),
+            Some("enforce") => Ok(Some(AppArmorMode::Enforce)),
+            Some("complain") => Ok(Some(AppArmorMode::Complain)),
+            Some(mode) => bail!("unknown AppArmor profile mode: {mode:?}"),
+        }
+    }
+
+    /// Put a profile into enforce mode (`aa-enforce`). This also enables a disabled profile.
+    pub async fn enforce(&mut self, profile_file: &str) -> Result<()> {
+        let path = profile_path(profile_file)?;
+        self.0.command(["aa-enforce", &path]).run().await?;
+        Ok(())
+    }
+
+    /// Put a profile into complain mode (`aa-complain`).
+    pub async fn complain(&mut self, profile_file: &str) -> Result<()> {
+        let path = profile_path(profile_file)?;
+        self.0.command(["aa-complain", &path]).run().await?;
+        Ok(())
+    }
+
+    /// Unload and disable a profile (`aa-disable`).
+    pub async fn disable(&mut self, profile_file: &str) -> Result<()> {
+        let path = profile_path(profile_file)?;
+        self.0.command(["aa-disable", &path]).run().await?;
+        Ok(())
+    }
+
+    /// Write a custom profile to `/etc/apparmor.d/{profile_file}` and reload it if it was changed.
+    /// Returns `true` if the profile was changed.
+    pub async fn deploy_profile(&mut self, profile_file: &str, content: &str) -> Result<bool> {
+        let path = profile_path(profile_file)?;
+        let changed = self.0.update_file(&path, content).await?;
+        if changed {
+            self.0
+                .command(["apparmor_parser", "--replace", &path])
+                .run()
+                .await?;
+        }
+        Ok(changed)
+    }
+}
+
+fn profile_path(profile_file: &str) -> Result<String> {
+    if profile_file.is_empty() || profile_file.contains('/') || profile_file.starts_with('.') {
+        bail!("invalid AppArmor profile file name: {profile_file:?}");
+    }
+    Ok(format!("{PROFILES_DIR}/{profile_file}"))
+}
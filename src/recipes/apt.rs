@@ -1,11 +1,24 @@
-use std::time::{Duration, SystemTime};
+use std::{
+    fmt::Write,
+    path::Path,
+    time::{Duration, Instant, SystemTime},
+};
 
 use anyhow::bail;
-use log::info;
+use log::{info, warn};
 
-use crate::Session;
+use crate::{command::shell_quote, Distro, OutputLine, Session};
 
 const AUTO_UPDATE_PERIOD: Duration = Duration::from_secs(3600);
+const LOCK_TIMEOUT: Duration = Duration::from_secs(600);
+const LOCK_RETRY_INITIAL_DELAY: Duration = Duration::from_secs(2);
+const LOCK_RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+const PROXY_CONFIG_PATH: &str = "/etc/apt/apt.conf.d/95roguewave-proxy";
+const LOCK_ERRORS: &[&str] = &[
+    "Could not get lock",
+    "Unable to acquire the dpkg frontend lock",
+    "Unable to lock directory",
+];
 
 impl Session {
     /// Execute apt package management commands.
@@ -14,13 +27,52 @@ impl Session {
     }
 }
 
+/// A package installed on the system.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InstalledPackage {
+    /// Package name.
+    pub name: String,
+    /// Installed version.
+    pub version: String,
+    /// Architecture, e.g. `amd64` or `all`.
+    pub architecture: String,
+}
+
+/// An installed package that has a newer version available.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UpgradablePackage {
+    /// Package name.
+    pub name: String,
+    /// Installed version.
+    pub current_version: String,
+    /// Version that would be installed by an upgrade.
+    pub candidate_version: String,
+    /// Whether the new version comes from a security repository.
+    pub security: bool,
+}
+
+/// Progress of package installation reported by apt-get.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstallProgress {
+    /// Package that is being processed.
+    pub package: String,
+    /// Overall progress of the installation, from 0 to 100.
+    pub percent: f64,
+    /// Description of the current action, e.g. `Unpacking nginx (amd64)`.
+    pub description: String,
+}
+
 /// Provides access to apt package management commands.
+///
+/// If the apt or dpkg lock is held by another process, apt-get commands are retried
+/// for up to 10 minutes.
 pub struct Apt<'a>(&'a mut Session);
 
 impl<'a> Apt<'a> {
     /// Update package list.
     pub async fn update_package_list(&mut self) -> anyhow::Result<()> {
-        self.0.command(["apt-get", "update"]).run().await?;
+        self.require_apt().await?;
+        run_apt_get(self.0, &["apt-get", "update"]).await?;
         self.0.cache().insert(PackageListUpdated);
         Ok(())
     }
@@ -47,8 +99,85 @@ impl<'a> Apt<'a> {
         }
     }
 
+    /// List installed packages.
+    pub async fn installed_packages(&mut self) -> anyhow::Result<Vec<InstalledPackage>> {
+        self.require_apt().await?;
+        let output = self
+            .0
+            .command([
+                "dpkg-query",
+                "--show",
+                "--showformat=${db:Status-Status}\\t${Package}\\t${Version}\\t${Architecture}\\n",
+            ])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?;
+        let mut packages = Vec::new();
+        for line in output.stdout_lines() {
+            let fields: Vec<_> = line.split('\t').collect();
+            if let ["installed", name, version, architecture] = fields[..] {
+                packages.push(InstalledPackage {
+                    name: name.into(),
+                    version: version.into(),
+                    architecture: architecture.into(),
+                });
+            }
+        }
+        Ok(packages)
+    }
+
+    /// List installed packages that can be upgraded.
+    /// Update package list before the check if necessary.
+    pub async fn upgradable_packages(&mut self) -> anyhow::Result<Vec<UpgradablePackage>> {
+        self.require_apt().await?;
+        update_package_list_unless_cached(self.0).await?;
+        let output = self
+            .0
+            .command(["apt", "list", "--upgradable"])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?;
+        let mut packages = Vec::new();
+        // Format: `name/origin1,origin2 candidate arch [upgradable from: current]`
+        for line in output.stdout_lines() {
+            let Some((main, current)) = line.split_once(" [upgradable from: ") else {
+                continue;
+            };
+            let mut words = main.split_whitespace();
+            let (Some(name_and_origins), Some(candidate_version)) = (words.next(), words.next())
+            else {
+                continue;
+            };
+            let (name, origins) = name_and_origins
+                .split_once('/')
+                .unwrap_or((name_and_origins, ""));
+            packages.push(UpgradablePackage {
+                name: name.into(),
+                current_version: current.trim_end_matches(']').into(),
+                candidate_version: candidate_version.into(),
+                security: origins
+                    .split(',')
+                    .any(|origin| origin.ends_with("-security")),
+            });
+        }
+        Ok(packages)
+    }
+
     /// Install specified packages.
     pub async fn install(&mut self, packages: &[&str]) -> anyhow::Result<()> {
+        self.install_with_progress(packages, |_| {}).await
+    }
+
+    /// Install specified packages, calling `on_progress` for each progress update
+    /// reported by apt-get.
+    pub async fn install_with_progress(
+        &mut self,
+        packages: &[&str],
+        mut on_progress: impl FnMut(InstallProgress) + Send,
+    ) -> anyhow::Result<()> {
+        self.require_apt().await?;
         let mut new_packages = Vec::new();
         for package in packages {
             if !self.is_package_installed(package).await? {
@@ -56,29 +185,416 @@ impl<'a> Apt<'a> {
             }
         }
         if !new_packages.is_empty() {
+            let mut command = vec!["apt-get", "-o", "APT::Status-Fd=1", "install", "--yes"];
+            command.extend(new_packages);
+            run_apt_get_inner(self.0, &command, Some(&mut on_progress)).await?;
+        }
+        Ok(())
+    }
+
+    /// Set debconf answers for `package` so that it can be installed non-interactively.
+    /// Each selection is a `(question, type, value)` tuple,
+    /// e.g. `("postfix/main_mailer_type", "select", "Internet Site")`.
+    ///
+    /// Values are not logged, so it's safe to preseed passwords.
+    pub async fn preseed(
+        &mut self,
+        package: &str,
+        selections: &[(&str, &str, &str)],
+    ) -> anyhow::Result<()> {
+        let mut content = String::new();
+        for (question, kind, value) in selections {
+            if [package, question, kind]
+                .iter()
+                .any(|s| s.is_empty() || s.contains(char::is_whitespace))
+                || value.contains('\n')
+            {
+                bail!("invalid debconf selection for {package}: {question:?}");
+            }
+            writeln!(content, "{package} {question} {kind} {value}")?;
+        }
+        self.require_apt().await?;
+        let path = self
+            .0
+            .command(["mktemp"])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?
+            .stdout_trimmed()
+            .to_string();
+        self.0.write_file(&path, content).await?;
+        let result = self
+            .0
+            .command(["debconf-set-selections", &path])
+            .hide_command()
+            .run()
+            .await;
+        self.remove_temp_file(&path).await?;
+        result?;
+        info!(
+            "preseeded {} debconf answers for {package}",
+            selections.len()
+        );
+        Ok(())
+    }
+
+    /// Download a `.deb` package from `url` on the remote host, verify its SHA-256 `checksum`
+    /// and install it with dependencies. Returns `false` if the same version of the package
+    /// is already installed.
+    pub async fn install_deb_from_url(
+        &mut self,
+        url: &str,
+        checksum: &str,
+    ) -> anyhow::Result<bool> {
+        self.install(&["curl"]).await?;
+        let path = self.temp_deb_path().await?;
+        let result = async {
             self.0
-                .command(["apt-get", "install", "--yes"])
-                .args(new_packages)
+                .command(["curl", "--fail", "--silent", "--show-error", "--location"])
+                .args(["--output", &path, url])
                 .run()
                 .await?;
+            let actual = self.0.remote_checksum(&path).await?;
+            if actual.as_deref() != Some(checksum.to_ascii_lowercase().as_str()) {
+                bail!(
+                    "checksum mismatch for {url}: expected {checksum}, got {}",
+                    actual.as_deref().unwrap_or("nothing")
+                );
+            }
+            self.install_deb_file(&path).await
         }
+        .await;
+        self.remove_temp_file(&path).await?;
+        result
+    }
+
+    /// Upload a local `.deb` package and install it with dependencies. Returns `false`
+    /// if the same version of the package is already installed.
+    pub async fn install_local_deb(
+        &mut self,
+        local_path: impl AsRef<Path>,
+    ) -> anyhow::Result<bool> {
+        let path = self.temp_deb_path().await?;
+        let result = async {
+            self.0.upload_artifact(local_path, &path).await?;
+            self.install_deb_file(&path).await
+        }
+        .await;
+        self.remove_temp_file(&path).await?;
+        result
+    }
+
+    async fn install_deb_file(&mut self, path: &str) -> anyhow::Result<bool> {
+        let fields = self
+            .0
+            .command([
+                "dpkg-deb",
+                "--show",
+                "--showformat=${Package} ${Version}",
+                path,
+            ])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?;
+        let Some((package, version)) = fields.stdout_trimmed().split_once(' ') else {
+            bail!("failed to read package information from {path}");
+        };
+        let installed = self
+            .0
+            .command([
+                "dpkg-query",
+                "--show",
+                "--showformat=${db:Status-Status} ${Version}",
+                package,
+            ])
+            .hide_command()
+            .hide_all_output()
+            .allow_failure()
+            .run()
+            .await?;
+        if installed.exit_code == 0 && installed.stdout == format!("installed {version}") {
+            info!("{package} {version} is already installed");
+            return Ok(false);
+        }
+        run_apt_get(self.0, &["apt-get", "install", "--yes", path]).await?;
+        Ok(true)
+    }
+
+    async fn temp_deb_path(&mut self) -> anyhow::Result<String> {
+        self.require_apt().await?;
+        Ok(self
+            .0
+            .command(["mktemp", "--suffix=.deb"])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?
+            .stdout_trimmed()
+            .to_string())
+    }
+
+    async fn remove_temp_file(&mut self, path: &str) -> anyhow::Result<()> {
+        self.0
+            .command(["rm", "-f", "--", path])
+            .hide_command()
+            .run()
+            .await?;
         Ok(())
     }
 
+    /// Add a third-party apt repository signed by the ASCII-armored key at `key_url`.
+    ///
+    /// The key is stored in `/etc/apt/keyrings/{name}.gpg` and the repository is written to
+    /// `/etc/apt/sources.list.d/{name}.list` as `deb [signed-by=...] {repository}`, where
+    /// `repository` is e.g. `https://repo.example.com/apt jammy main`.
+    /// The package list is updated if the repository was added or changed.
+    /// Returns `true` if the repository was added or changed.
+    pub async fn add_repository(
+        &mut self,
+        name: &str,
+        key_url: &str,
+        repository: &str,
+    ) -> anyhow::Result<bool> {
+        if !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-')
+        {
+            bail!("invalid repository name: {name:?}");
+        }
+        let keyring_path = format!("/etc/apt/keyrings/{name}.gpg");
+        let list_path = format!("/etc/apt/sources.list.d/{name}.list");
+        let list = format!("deb [signed-by={keyring_path}] {repository}\n");
+        if self.0.path_exists(&keyring_path).await?
+            && self.0.read_file_if_exists(&list_path).await?.as_deref() == Some(list.as_str())
+        {
+            return Ok(false);
+        }
+        self.install(&["curl", "gnupg"]).await?;
+        self.0
+            .command(["mkdir", "-p", "/etc/apt/keyrings"])
+            .hide_command()
+            .run()
+            .await?;
+        self.0
+            .command([
+                "bash",
+                "-c",
+                &format!(
+                    "set -o pipefail; curl -fsSL {} | gpg --dearmor --yes --output {}",
+                    shell_quote(key_url),
+                    keyring_path
+                ),
+            ])
+            .run()
+            .await?;
+        self.0.update_file(&list_path, list).await?;
+        self.update_package_list().await?;
+        Ok(true)
+    }
+
+    /// Make apt use an HTTP proxy (e.g. `http://proxy.internal:3128`) for all downloads,
+    /// or remove the proxy configuration if `proxy` is `None`.
+    /// Returns `true` if the configuration was changed.
+    pub async fn set_proxy(&mut self, proxy: Option<&str>) -> anyhow::Result<bool> {
+        self.require_apt().await?;
+        let Some(proxy) = proxy else {
+            if !self.0.path_exists(PROXY_CONFIG_PATH).await? {
+                return Ok(false);
+            }
+            self.0
+                .command(["rm", "-f", PROXY_CONFIG_PATH])
+                .hide_command()
+                .run()
+                .await?;
+            info!("removed apt proxy");
+            return Ok(true);
+        };
+        if proxy.is_empty() || proxy.contains(['"', ';', '\n', ' ']) {
+            bail!("invalid proxy URL: {proxy:?}");
+        }
+        let mut config = String::new();
+        writeln!(config, "// Managed by roguewave.")?;
+        writeln!(config, "Acquire::http::Proxy \"{proxy}\";")?;
+        writeln!(config, "Acquire::https::Proxy \"{proxy}\";")?;
+        self.0.update_file(PROXY_CONFIG_PATH, config).await
+    }
+
+    /// Replace the repository URI `original` (e.g. `http://archive.ubuntu.com/ubuntu`)
+    /// with `mirror` in `/etc/apt/sources.list` and all files in `/etc/apt/sources.list.d/`.
+    /// The package list is updated if any file was changed.
+    /// Returns `true` if any file was changed.
+    pub async fn set_mirror(&mut self, original: &str, mirror: &str) -> anyhow::Result<bool> {
+        self.require_apt().await?;
+        let original = original.trim_end_matches('/');
+        let mirror = mirror.trim_end_matches('/');
+        if original.is_empty() || mirror.is_empty() || mirror.contains(char::is_whitespace) {
+            bail!("invalid mirror URI: {mirror:?}");
+        }
+        let output = self
+            .0
+            .command([
+                "find",
+                "/etc/apt/sources.list",
+                "/etc/apt/sources.list.d",
+                "-maxdepth",
+                "1",
+                "-type",
+                "f",
+                "(",
+                "-name",
+                "*.list",
+                "-o",
+                "-name",
+                "*.sources",
+                ")",
+            ])
+            .hide_command()
+            .hide_all_output()
+            .allow_failure()
+            .run()
+            .await?;
+        let mut changed = false;
+        for path in output.stdout_lines() {
+            let Some(content) = self.0.read_file_if_exists(path).await? else {
+                continue;
+            };
+            let new_content = replace_uri(&content, original, mirror);
+            if new_content != content {
+                self.0.update_file(path, new_content).await?;
+                changed = true;
+            }
+        }
+        if changed {
+            info!("switched {original} to {mirror}");
+            self.update_package_list().await?;
+        }
+        Ok(changed)
+    }
+
+    /// Upgrade only packages that have updates in the security repository
+    /// (`{codename}-security`), leaving other upgrades pending.
+    /// Update package list before the upgrade if necessary.
+    /// Returns the packages that were upgraded.
+    pub async fn upgrade_security(&mut self) -> anyhow::Result<Vec<UpgradablePackage>> {
+        let packages: Vec<_> = self
+            .upgradable_packages()
+            .await?
+            .into_iter()
+            .filter(|package| package.security)
+            .collect();
+        if packages.is_empty() {
+            return Ok(packages);
+        }
+        let os = self.0.os_release().await?;
+        let Some(codename) = os.version_codename else {
+            bail!("unknown release codename of {}", os.pretty_name);
+        };
+        let targets: Vec<String> = packages
+            .iter()
+            .map(|package| format!("{}/{codename}-security", package.name))
+            .collect();
+        let mut command = vec![
+            "DEBIAN_FRONTEND=noninteractive",
+            "apt-get",
+            "install",
+            "--only-upgrade",
+            "--yes",
+        ];
+        command.extend(targets.iter().map(String::as_str));
+        run_apt_get(self.0, &command).await?;
+        info!("applied {} security updates", packages.len());
+        Ok(packages)
+    }
+
     /// Upgrade the system. Update package list before the upgrade if necessary.
     pub async fn upgrade_system(&mut self) -> anyhow::Result<()> {
+        self.require_apt().await?;
         update_package_list_unless_cached(self.0).await?;
-        self.0
-            .command([
+        run_apt_get(
+            self.0,
+            &[
                 "DEBIAN_FRONTEND=noninteractive",
                 "apt-get",
                 "dist-upgrade",
                 "--yes",
-            ])
-            .run()
-            .await?;
+            ],
+        )
+        .await?;
         Ok(())
     }
+
+    async fn require_apt(&mut self) -> anyhow::Result<()> {
+        self.0
+            .require_os_for("apt", &[Distro::Debian, Distro::Ubuntu])
+            .await
+    }
+}
+
+/// Run an apt-get command, retrying while the dpkg or apt lock is held by another process
+/// (e.g. cloud-init or unattended-upgrades).
+pub(crate) async fn run_apt_get(session: &Session, command: &[&str]) -> anyhow::Result<()> {
+    run_apt_get_inner(session, command, None).await
+}
+
+/// Run apt-get, retrying while the lock is held. If `on_progress` is set, `command` must
+/// contain `-o APT::Status-Fd=1` and progress lines are parsed from stdout.
+async fn run_apt_get_inner(
+    session: &Session,
+    command: &[&str],
+    mut on_progress: Option<&mut (dyn FnMut(InstallProgress) + Send)>,
+) -> anyhow::Result<()> {
+    let started = Instant::now();
+    let mut delay = LOCK_RETRY_INITIAL_DELAY;
+    loop {
+        let apt_get = session.command(command).allow_failure();
+        let output = match on_progress.as_deref_mut() {
+            Some(on_progress) => {
+                apt_get
+                    .run_streaming(|line| {
+                        if let OutputLine::Stdout(line) = line {
+                            if let Some(progress) = parse_progress(&line) {
+                                on_progress(progress);
+                            }
+                        }
+                    })
+                    .await?
+            }
+            None => apt_get.run().await?,
+        };
+        if output.exit_code == 0 {
+            return Ok(());
+        }
+        let lock_held = LOCK_ERRORS
+            .iter()
+            .any(|pattern| output.stderr.contains(pattern));
+        if !lock_held {
+            bail!("apt-get failed with exit code {}", output.exit_code);
+        }
+        if started.elapsed() >= LOCK_TIMEOUT {
+            bail!("timed out waiting for the apt lock");
+        }
+        warn!(
+            "apt lock is held by another process, retrying in {} s",
+            delay.as_secs()
+        );
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(LOCK_RETRY_MAX_DELAY);
+    }
+}
+
+/// Parse a `pmstatus:<package>:<percent>:<description>` line written to `APT::Status-Fd`.
+fn parse_progress(line: &str) -> Option<InstallProgress> {
+    let mut parts = line.strip_prefix("pmstatus:")?.splitn(3, ':');
+    let package = parts.next()?;
+    let percent = parts.next()?.parse().ok()?;
+    let description = parts.next()?;
+    Some(InstallProgress {
+        package: package.into(),
+        percent,
+        description: description.into(),
+    })
 }
 
 async fn update_package_list_unless_cached(session: &mut Session) -> anyhow::Result<()> {
@@ -100,12 +616,28 @@ async fn update_package_list_unless_cached(session: &mut Session) -> anyhow::Res
 }
 
 async fn last_update_time(session: &mut Session) -> Option<SystemTime> {
-    let metadata = session
-        .fs()
-        .metadata("/var/lib/apt/periodic/update-success-stamp")
+    session
+        .modified_time("/var/lib/apt/periodic/update-success-stamp")
         .await
-        .ok()?;
-    Some(metadata.modified()?.as_system_time())
+        .ok()
+}
+
+/// Replace `from` with `to` where `from` is a complete URI or a prefix of a path.
+fn replace_uri(text: &str, from: &str, to: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(index) = rest.find(from) {
+        let after = &rest[index + from.len()..];
+        let boundary = after
+            .chars()
+            .next()
+            .map_or(true, |c| c == '/' || c.is_whitespace());
+        out.push_str(&rest[..index]);
+        out.push_str(if boundary { to } else { from });
+        rest = after;
+    }
+    out.push_str(rest);
+    out
 }
 
 struct PackageListUpdated;
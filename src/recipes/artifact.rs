@@ -0,0 +1,94 @@
+use std::{path::Path, time::Instant};
+
+use anyhow::{bail, Context, Result};
+use log::{debug, info};
+
+use crate::{local::LocalCommand, timings::OperationKind, Session};
+
+impl Session {
+    /// Upload a local file to `remote_path`, verifying its SHA-256 checksum before
+    /// moving it into place. Returns `false` if the remote file already has the same checksum.
+    ///
+    /// The file is first written next to `remote_path` with a temporary name.
+    /// If the checksum of the uploaded copy doesn't match, it's deleted and an error
+    /// is returned, so a corrupted transfer never replaces the existing file.
+    ///
+    /// Requires `sha256sum` to be available locally and remotely.
+    pub async fn upload_artifact(
+        &mut self,
+        local_path: impl AsRef<Path>,
+        remote_path: &str,
+    ) -> Result<bool> {
+        let local_path = local_path.as_ref();
+        let local_path_str = local_path.to_str().context("non-utf8 path")?;
+        let output = LocalCommand::new(["sha256sum", "--", local_path_str])
+            .hide_command()
+            .hide_all_output()
+            .log_prefix(&self.log_prefix)
+            .run()
+            .await?;
+        let expected = parse_checksum(&output.stdout)?;
+
+        if self.remote_checksum(remote_path).await?.as_deref() == Some(expected.as_str()) {
+            debug!("{remote_path:?} is up to date");
+            return Ok(false);
+        }
+
+        let temp_path = format!("{remote_path}.roguewave-upload");
+        let data =
+            std::fs::read(local_path).with_context(|| format!("failed to read {local_path:?}"))?;
+        let size = data.len() as u64;
+        let permit = self.throttle().await;
+        let started = Instant::now();
+        self.write_file(&temp_path, data).await?;
+        drop(permit);
+        self.stats.record_upload(size);
+        self.record_timing(
+            OperationKind::Upload,
+            format!("{local_path_str:?} -> {remote_path:?}"),
+            started.elapsed(),
+        );
+
+        let actual = self.remote_checksum(&temp_path).await?;
+        if actual.as_deref() != Some(expected.as_str()) {
+            self.command(["rm", "-f", "--", &temp_path])
+                .hide_command()
+                .run()
+                .await?;
+            bail!(
+                "checksum mismatch after uploading {local_path:?}: expected {expected}, got {}",
+                actual.as_deref().unwrap_or("nothing")
+            );
+        }
+        self.command(["mv", "-f", "--", &temp_path, remote_path])
+            .hide_command()
+            .run()
+            .await?;
+        info!("uploaded {local_path:?} to {remote_path:?} (sha256 {expected})");
+        Ok(true)
+    }
+
+    pub(crate) async fn remote_checksum(&mut self, path: &str) -> Result<Option<String>> {
+        if !self.path_exists(path).await? {
+            return Ok(None);
+        }
+        let output = self
+            .command(["sha256sum", "--", path])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?;
+        parse_checksum(&output.stdout).map(Some)
+    }
+}
+
+fn parse_checksum(output: &str) -> Result<String> {
+    let checksum = output
+        .split_whitespace()
+        .next()
+        .context("empty sha256sum output")?;
+    if checksum.len() != 64 || !checksum.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("invalid sha256sum output: {output:?}");
+    }
+    Ok(checksum.to_ascii_lowercase())
+}
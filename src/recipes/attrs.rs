@@ -0,0 +1,152 @@
+use anyhow::{Context, Result};
+use log::info;
+
+use crate::Session;
+
+/// An inode flag managed by `chattr`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileAttribute {
+    /// `i`: the file can't be modified, deleted or renamed, even by root.
+    Immutable,
+    /// `a`: the file can only be opened for appending.
+    AppendOnly,
+    /// `d`: the file is skipped by `dump`.
+    NoDump,
+    /// `A`: access time is not updated.
+    NoAtime,
+}
+
+impl FileAttribute {
+    fn flag(self) -> char {
+        match self {
+            FileAttribute::Immutable => 'i',
+            FileAttribute::AppendOnly => 'a',
+            FileAttribute::NoDump => 'd',
+            FileAttribute::NoAtime => 'A',
+        }
+    }
+}
+
+/// Setuid, setgid and sticky bits of a file mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SpecialBits {
+    /// Executables run as the file owner.
+    pub setuid: bool,
+    /// Executables run as the file group. New files in a directory inherit its group.
+    pub setgid: bool,
+    /// Only owners can delete or rename files in a directory.
+    pub sticky: bool,
+}
+
+impl SpecialBits {
+    /// Extract the bits from an octal mode (e.g. `1777` or `2755`).
+    pub fn from_mode(mode: u32) -> Self {
+        Self {
+            setuid: mode & 0o4000 != 0,
+            setgid: mode & 0o2000 != 0,
+            sticky: mode & 0o1000 != 0,
+        }
+    }
+
+    fn chmod_spec(&self) -> String {
+        let sign = |enabled: bool| if enabled { '+' } else { '-' };
+        format!(
+            "u{}s,g{}s,o{}t",
+            sign(self.setuid),
+            sign(self.setgid),
+            sign(self.sticky)
+        )
+    }
+}
+
+impl Session {
+    /// Read `chattr` flags of a remote file or directory (`lsattr -d`).
+    ///
+    /// Flags that don't have a `FileAttribute` variant are ignored.
+    pub async fn file_attributes(&mut self, path: &str) -> Result<Vec<FileAttribute>> {
+        let output = self
+            .command(["lsattr", "-d", "--", path])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?;
+        let flags = output
+            .stdout
+            .split_whitespace()
+            .next()
+            .context("unexpected lsattr output")?;
+        Ok([
+            FileAttribute::Immutable,
+            FileAttribute::AppendOnly,
+            FileAttribute::NoDump,
+            FileAttribute::NoAtime,
+        ]
+        .into_iter()
+        .filter(|attribute| flags.contains(attribute.flag()))
+        .collect())
+    }
+
+    /// Set or clear a `chattr` flag. Returns `true` if the flag was changed.
+    pub async fn set_file_attribute(
+        &mut self,
+        path: &str,
+        attribute: FileAttribute,
+        enabled: bool,
+    ) -> Result<bool> {
+        if self.file_attributes(path).await?.contains(&attribute) == enabled {
+            return Ok(false);
+        }
+        let sign = if enabled { '+' } else { '-' };
+        self.command(["chattr", &format!("{sign}{}", attribute.flag()), "--", path])
+            .run()
+            .await?;
+        info!(
+            "{} {attribute:?} flag of {path:?}",
+            if enabled { "set" } else { "cleared" }
+        );
+        Ok(true)
+    }
+
+    /// Check if a file is immutable (`chattr +i`).
+    pub async fn is_immutable(&mut self, path: &str) -> Result<bool> {
+        Ok(self
+            .file_attributes(path)
+            .await?
+            .contains(&FileAttribute::Immutable))
+    }
+
+    /// Make a file immutable (`chattr +i`) or writable again, e.g. to protect a critical
+    /// config from accidental edits. Returns `true` if the flag was changed.
+    ///
+    /// Immutable files can't be changed by `update_file` either, so clear the flag first.
+    pub async fn set_immutable(&mut self, path: &str, immutable: bool) -> Result<bool> {
+        self.set_file_attribute(path, FileAttribute::Immutable, immutable)
+            .await
+    }
+
+    /// Read setuid, setgid and sticky bits of a remote file or directory.
+    pub async fn special_bits(&mut self, path: &str) -> Result<SpecialBits> {
+        let output = self
+            .command(["stat", "--format=%a", "--", path])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?;
+        let mode = u32::from_str_radix(output.stdout.trim(), 8)
+            .with_context(|| format!("unexpected stat output: {:?}", output.stdout))?;
+        Ok(SpecialBits::from_mode(mode))
+    }
+
+    /// Set setuid, setgid and sticky bits, leaving permission bits intact.
+    /// Returns `true` if the mode was changed.
+    pub async fn set_special_bits(&mut self, path: &str, bits: SpecialBits) -> Result<bool> {
+        if self.special_bits(path).await? == bits {
+            return Ok(false);
+        }
+        self.command(["chmod", &bits.chmod_spec(), "--", path])
+            .run()
+            .await?;
+        info!("updated special mode bits of {path:?}");
+        Ok(true)
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    fmt::Write,
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{command::shell_quote, CommandOutput, Session};
+
+impl Session {
+    /// Prepare a batch of independent commands that will be executed in a single
+    /// remote shell invocation.
+    ///
+    /// This saves a round trip per command, which matters on high-latency links when running
+    /// many small checks. Commands are executed sequentially; a failing command doesn't stop
+    /// the batch. Requires `sh`, `mktemp` and `cat` on the remote host.
+    pub fn batch(&self) -> Batch<'_> {
+        Batch {
+            session: self,
+            commands: Vec::new(),
+        }
+    }
+}
+
+/// A list of commands to be executed together. Use `Session::batch` to create it.
+pub struct Batch<'a> {
+    session: &'a Session,
+    commands: Vec<Vec<String>>,
+}
+
+impl Batch<'_> {
+    /// Add a command to the batch. Returns the index of its output in the result of `run`.
+    pub fn add<S: AsRef<str>, I: IntoIterator<Item = S>>(&mut self, command: I) -> usize {
+        self.commands
+            .push(command.into_iter().map(|s| s.as_ref().into()).collect());
+        self.commands.len() - 1
+    }
+
+    /// Execute all commands and return their outputs in the order they were added.
+    ///
+    /// Non-zero exit codes of individual commands are not treated as errors.
+    pub async fn run(self) -> Result<Vec<CommandOutput>> {
+        if self.commands.is_empty() {
+            return Ok(Vec::new());
+        }
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+        let token = format!("roguewave-batch-{}-{nanos}", process::id());
+        let mut script = String::from("t=$(mktemp -d) || exit 1\n");
+        for (index, command) in self.commands.iter().enumerate() {
+            if command.is_empty() {
+                bail!("cannot run empty command");
+            }
+            let command = command
+                .iter()
+                .map(|arg| shell_quote(arg))
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(script, "printf '%s\\n' '{token}:begin:{index}'")?;
+            writeln!(script, "{command} >\"$t/o\" 2>\"$t/e\" </dev/null; c=$?")?;
+            writeln!(script, "cat \"$t/o\"; printf '\\n%s\\n' '{token}:stderr'")?;
+            writeln!(
+                script,
+                "cat \"$t/e\"; printf '\\n%s:%d\\n' '{token}:end' \"$c\""
+            )?;
+        }
+        writeln!(script, "rm -rf \"$t\"")?;
+
+        let output = self
+            .session
+            .command(["sh", "-c"])
+            .redacted_arg(
+                script,
+                format!(
+                    "<batch of {} commands: {:?}>",
+                    self.commands.len(),
+                    self.commands
+                ),
+            )
+            .hide_all_output()
+            .run()
+            .await?;
+
+        let mut outputs = Vec::new();
+        let mut rest = output.stdout.as_str();
+        for index in 0..self.commands.len() {
+            let begin = format!("{token}:begin:{index}\n");
+            let stderr_marker = format!("\n{token}:stderr\n");
+            let end_marker = format!("\n{token}:end:");
+            let error = || format!("malformed batch output for command {index}");
+            rest = rest.strip_prefix(&begin).with_context(error)?;
+            let (stdout, after) = rest.split_once(&stderr_marker).with_context(error)?;
+            let (stderr, after) = after.split_once(&end_marker).with_context(error)?;
+            let (code, after) = after.split_once('\n').with_context(error)?;
+            outputs.push(CommandOutput {
+                exit_code: code.parse().with_context(error)?,
+                stdout: stdout.into(),
+                stderr: stderr.into(),
+                stdout_overflow: None,
+                stderr_overflow: None,
+            });
+            rest = after;
+        }
+        Ok(outputs)
+    }
+}
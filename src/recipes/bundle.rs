@@ -0,0 +1,214 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use log::info;
+
+use crate::{local::LocalCommand, recipes::apt::run_apt_get, Distro, Session};
+
+const REMOTE_DIR: &str = "/var/cache/roguewave/bundle";
+const DEBS_DIR: &str = "debs";
+const BINARIES_DIR: &str = "bin";
+const IMAGES_DIR: &str = "images";
+
+/// A local directory of artifacts for provisioning hosts that have no internet access.
+///
+/// The bundle contains `.deb` packages (`debs/`), executables (`bin/`) and docker image
+/// archives (`images/`). It's prepared on a machine with network access and installed
+/// with [`Session::install_bundle`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bundle {
+    dir: PathBuf,
+}
+
+impl Bundle {
+    /// Open a bundle in `dir`, creating the directory layout if needed.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        for subdir in [DEBS_DIR, BINARIES_DIR, IMAGES_DIR] {
+            fs::create_dir_all(dir.join(subdir))
+                .with_context(|| format!("failed to create {:?}", dir.join(subdir)))?;
+        }
+        Ok(Self { dir })
+    }
+
+    /// Path to the bundle directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Copy a local `.deb` package into the bundle.
+    pub fn add_deb(&self, path: impl AsRef<Path>) -> Result<()> {
+        self.add_file(DEBS_DIR, path.as_ref())
+    }
+
+    /// Copy a local executable into the bundle. It will be installed to `/usr/local/bin`.
+    pub fn add_binary(&self, path: impl AsRef<Path>) -> Result<()> {
+        self.add_file(BINARIES_DIR, path.as_ref())
+    }
+
+    /// Export a local docker image (`docker save`) into the bundle.
+    pub async fn add_docker_image(&self, image: &str) -> Result<()> {
+        let file_name: String = image
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let path = self.dir.join(IMAGES_DIR).join(format!("{file_name}.tar"));
+        let path = path.to_str().context("non-utf8 path")?;
+        LocalCommand::new(["docker", "save", "--output", path, image])
+            .run()
+            .await?;
+        Ok(())
+    }
+
+    /// Files of the specified kind in the bundle, sorted by name.
+    fn files(&self, subdir: &str) -> Result<Vec<PathBuf>> {
+        let dir = self.dir.join(subdir);
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&dir).with_context(|| format!("failed to read {dir:?}"))? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                files.push(entry.path());
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    fn add_file(&self, subdir: &str, path: &Path) -> Result<()> {
+        let name = path
+            .file_name()
+            .with_context(|| format!("invalid file path: {path:?}"))?;
+        let target = self.dir.join(subdir).join(name);
+        fs::copy(path, &target).with_context(|| format!("failed to copy {path:?}"))?;
+        Ok(())
+    }
+}
+
+impl Session {
+    /// Download `packages` and all their dependencies that are not installed on this host
+    /// into `bundle`. This host must have network access and run the same distribution
+    /// release as the target hosts, which should have a similar set of installed packages.
+    pub async fn download_debs_into_bundle(
+        &mut self,
+        bundle: &Bundle,
+        packages: &[&str],
+    ) -> Result<()> {
+        let dir = self
+            .command(["mktemp", "-d"])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?
+            .stdout_trimmed()
+            .to_string();
+        let result = async {
+            // apt requires the `partial` subdirectory to exist.
+            self.command(["mkdir", &format!("{dir}/partial")])
+                .hide_command()
+                .run()
+                .await?;
+            self.apt().update_package_list().await?;
+            let mut command = vec![
+                "apt-get".to_string(),
+                "install".into(),
+                "--yes".into(),
+                "--download-only".into(),
+                "-o".into(),
+                format!("Dir::Cache::Archives={dir}"),
+            ];
+            command.extend(packages.iter().map(|package| package.to_string()));
+            let command: Vec<&str> = command.iter().map(String::as_str).collect();
+            run_apt_get(self, &command).await?;
+            let output = self
+                .command(["find", &dir, "-maxdepth", "1", "-name", "*.deb"])
+                .hide_command()
+                .hide_all_output()
+                .run()
+                .await?;
+            for remote_path in output.stdout_lines() {
+                let name = remote_path.rsplit('/').next().unwrap_or(remote_path);
+                let data = self.read_file(remote_path).await?;
+                let local_path = bundle.dir.join(DEBS_DIR).join(name);
+                fs::write(&local_path, data)
+                    .with_context(|| format!("failed to write {local_path:?}"))?;
+                info!("added {name} to the bundle");
+            }
+            Ok(())
+        }
+        .await;
+        self.command(["rm", "-rf", "--", &dir])
+            .hide_command()
+            .run()
+            .await?;
+        result
+    }
+
+    /// Upload the contents of `bundle` and install it without using the network:
+    /// `.deb` packages are installed with apt (failing if any dependency is missing from
+    /// the bundle), executables are copied to `/usr/local/bin` and docker images are loaded.
+    ///
+    /// Files are kept in `/var/cache/roguewave/bundle` and unchanged files are not uploaded
+    /// again.
+    pub async fn install_bundle(&mut self, bundle: &Bundle) -> Result<()> {
+        let debs = self.upload_bundle_files(bundle, DEBS_DIR).await?;
+        if !debs.is_empty() {
+            self.require_os_for(".deb packages", &[Distro::Debian, Distro::Ubuntu])
+                .await?;
+            let mut command = vec!["apt-get", "install", "--yes", "--no-download"];
+            command.extend(debs.iter().map(String::as_str));
+            run_apt_get(self, &command).await?;
+        }
+        for path in self.upload_bundle_files(bundle, BINARIES_DIR).await? {
+            let name = path.rsplit('/').next().unwrap_or(&path);
+            self.command([
+                "install",
+                "-m",
+                "755",
+                &path,
+                &format!("/usr/local/bin/{name}"),
+            ])
+            .run()
+            .await?;
+        }
+        for path in self.upload_bundle_files(bundle, IMAGES_DIR).await? {
+            self.command(["docker", "load", "--input", &path])
+                .run()
+                .await?;
+        }
+        info!("installed bundle from {:?}", bundle.dir);
+        Ok(())
+    }
+
+    /// Upload files of one kind and return their remote paths.
+    async fn upload_bundle_files(&mut self, bundle: &Bundle, subdir: &str) -> Result<Vec<String>> {
+        let files = bundle.files(subdir)?;
+        if files.is_empty() {
+            return Ok(Vec::new());
+        }
+        let remote_dir = format!("{REMOTE_DIR}/{subdir}");
+        self.command(["mkdir", "-p", &remote_dir])
+            .hide_command()
+            .run()
+            .await?;
+        let mut remote_paths = Vec::new();
+        for file in files {
+            let name = file
+                .file_name()
+                .and_then(|name| name.to_str())
+                .with_context(|| format!("invalid file name: {file:?}"))?;
+            let remote_path = format!("{remote_dir}/{name}");
+            self.upload_artifact(&file, &remote_path).await?;
+            remote_paths.push(remote_path);
+        }
+        Ok(remote_paths)
+    }
+}
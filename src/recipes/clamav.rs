@@ -0,0 +1,206 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+
+use crate::{Distro, Session};
+
+const DATABASE_DIR: &str = "/var/lib/clamav";
+const SCAN_UNIT: &str = "roguewave-clamscan";
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A file flagged by `clamscan`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClamDetection {
+    /// Path of the file.
+    pub path: String,
+    /// Name of the matched signature, e.g. `Win.Test.EICAR_HDB-1`.
+    pub signature: String,
+}
+
+/// Results of a `clamscan` run.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ClamScanSummary {
+    /// Infected files.
+    pub detections: Vec<ClamDetection>,
+    /// Number of scanned files.
+    pub scanned_files: u64,
+    /// Number of infected files.
+    pub infected_files: u64,
+    /// Number of signatures in the database.
+    pub known_viruses: u64,
+    /// ClamAV engine version.
+    pub engine_version: String,
+}
+
+impl ClamScanSummary {
+    /// Parse `clamscan --infected` output: `{path}: {signature} FOUND` lines followed by
+    /// the `SCAN SUMMARY` section.
+    pub fn parse(output: &str) -> Result<Self> {
+        let mut summary = Self::default();
+        for line in output.lines() {
+            if let Some(line) = line.strip_suffix(" FOUND") {
+                let (path, signature) = line
+                    .rsplit_once(": ")
+                    .with_context(|| format!("unexpected clamscan output line: {line:?}"))?;
+                summary.detections.push(ClamDetection {
+                    path: path.into(),
+                    signature: signature.into(),
+                });
+                continue;
+            }
+            let Some((key, value)) = line.split_once(": ") else {
+                continue;
+            };
+            let parse = |value: &str| {
+                value
+                    .trim()
+                    .parse::<u64>()
+                    .with_context(|| format!("unexpected value of {key:?}: {value:?}"))
+            };
+            match key {
+                "Scanned files" => summary.scanned_files = parse(value)?,
+                "Infected files" => summary.infected_files = parse(value)?,
+                "Known viruses" => summary.known_viruses = parse(value)?,
+                "Engine version" => summary.engine_version = value.trim().into(),
+                _ => {}
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Check if no infected files were found.
+    pub fn is_clean(&self) -> bool {
+        self.infected_files == 0
+    }
+}
+
+impl Session {
+    /// Manage the ClamAV antivirus on a Debian or Ubuntu host.
+    pub fn clamav(&mut self) -> ClamAv<'_> {
+        ClamAv(self)
+    }
+}
+
+/// Provides access to ClamAV.
+pub struct ClamAv<'a>(&'a mut Session);
+
+impl ClamAv<'_> {
+    /// Install ClamAV and enable the `freshclam` service that keeps signatures updated.
+    pub async fn ensure_installed(&mut self) -> Result<()> {
+        self.0
+            .require_os_for("ClamAV", &[Distro::Debian, Distro::Ubuntu])
+            .await?;
+        self.0
+            .apt()
+            .install(&["clamav", "clamav-freshclam"])
+            .await?;
+        self.0
+            .command(["systemctl", "enable", "--now", "clamav-freshclam"])
+            .run()
+            .await?;
+        Ok(())
+    }
+
+    /// Check if the signature database has been downloaded.
+    pub async fn has_signatures(&mut self) -> Result<bool> {
+        for file in ["main.cvd", "main.cld"] {
+            if self.0.path_exists(format!("{DATABASE_DIR}/{file}")).await? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Wait until `freshclam` downloads the signature database after installation.
+    pub async fn wait_for_signatures(&mut self, timeout: Duration) -> Result<()> {
+        let started = Instant::now();
+        let mut logged_wait = false;
+        while !self.has_signatures().await? {
+            if started.elapsed() >= timeout {
+                bail!("timed out waiting for ClamAV signatures");
+            }
+            if !logged_wait {
+                info!("waiting for freshclam to download signatures");
+                logged_wait = true;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        Ok(())
+    }
+
+    /// Scan `paths` recursively with `clamscan` at low priority.
+    pub async fn scan(&mut self, paths: &[&str]) -> Result<ClamScanSummary> {
+        if paths.is_empty() {
+            bail!("no paths to scan");
+        }
+        let output = self
+            .0
+            .command(["clamscan", "--recursive", "--infected", "--"])
+            .args(
+                paths
+                    .iter()
+                    .map(|path| validate_path(path))
+                    .collect::<Result<Vec<_>>>()?,
+            )
+            .hide_all_output()
+            .low_priority()
+            .allow_failure()
+            .run()
+            .await?;
+        // Exit code 1 means that infected files were found.
+        if output.exit_code > 1 {
+            bail!(
+                "clamscan failed with exit code {}: {}",
+                output.exit_code,
+                output.stderr.trim()
+            );
+        }
+        let summary = ClamScanSummary::parse(&output.stdout)?;
+        for detection in &summary.detections {
+            warn!(
+                "ClamAV detected {} in {:?}",
+                detection.signature, detection.path
+            );
+        }
+        Ok(summary)
+    }
+
+    /// Scan `paths` periodically with a systemd timer. `on_calendar` is a systemd
+    /// calendar expression, e.g. `weekly` or `Sun *-*-* 03:00:00`.
+    /// The `roguewave-clamscan` unit fails if infected files are found, and the report
+    /// ends up in its journal. Returns `true` if the timer was changed.
+    pub async fn schedule_scan(&mut self, paths: &[&str], on_calendar: &str) -> Result<bool> {
+        if paths.is_empty() {
+            bail!("no paths to scan");
+        }
+        let paths = paths
+            .iter()
+            .map(|path| validate_path(path))
+            .collect::<Result<Vec<_>>>()?;
+        self.0
+            .install_periodic_timer(
+                SCAN_UNIT,
+                "ClamAV scan",
+                &format!(
+                    "Nice=19\n\
+                     IOSchedulingClass=idle\n\
+                     ExecStart=/usr/bin/clamscan --recursive --infected -- {}\n",
+                    paths.join(" ")
+                ),
+                on_calendar,
+            )
+            .await
+    }
+}
+
+fn validate_path(path: &str) -> Result<&str> {
+    if !path.starts_with('/')
+        || path
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || c == '%' || c == '$')
+    {
+        bail!("invalid path: {path:?}");
+    }
+    Ok(path)
+}
@@ -0,0 +1,98 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+use log::warn;
+
+use crate::Session;
+
+/// Difference between the remote clock and the local clock, measured by `Session::clock_skew`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockSkew {
+    /// Remote time minus local time, in seconds. Positive if the remote clock is ahead.
+    pub offset_secs: f64,
+    /// Round-trip time of the measurement. The offset is accurate to about half of it.
+    pub round_trip: Duration,
+}
+
+impl ClockSkew {
+    /// Check if the clocks differ by more than `max`, even after allowing for
+    /// the measurement error.
+    pub fn exceeds(&self, max: Duration) -> bool {
+        self.offset_secs.abs() - self.round_trip.as_secs_f64() / 2.0 > max.as_secs_f64()
+    }
+}
+
+/// What `Session::check_clock_skew` does if the skew is too large.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SkewAction {
+    /// Log a warning.
+    Warn,
+    /// Return an error.
+    Fail,
+}
+
+impl Session {
+    /// Measure the difference between the remote clock and the local clock.
+    ///
+    /// The remote time is compared with the local time in the middle of the round trip,
+    /// so network latency doesn't affect the result as long as it's symmetric.
+    pub async fn clock_skew(&self) -> Result<ClockSkew> {
+        let local_start = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .context("local clock is before the Unix epoch")?;
+        let started = Instant::now();
+        let output = self
+            .command(["date", "+%s.%N"])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?;
+        let round_trip = started.elapsed();
+        let remote_secs = parse_timestamp(output.stdout_trimmed())?;
+        let local_secs = (local_start + round_trip / 2).as_secs_f64();
+        Ok(ClockSkew {
+            offset_secs: remote_secs - local_secs,
+            round_trip,
+        })
+    }
+
+    /// Measure the clock skew and warn or fail depending on `action` if it exceeds `max`.
+    ///
+    /// A skewed clock breaks TLS certificate validation, Kerberos authentication and
+    /// time-based cleanup such as pruning of old backups, often without clear errors.
+    pub async fn check_clock_skew(&self, max: Duration, action: SkewAction) -> Result<ClockSkew> {
+        let skew = self.clock_skew().await?;
+        if skew.exceeds(max) {
+            let message = format!(
+                "remote clock is {:.1} s {} the local clock (maximum allowed skew is {:?})",
+                skew.offset_secs.abs(),
+                if skew.offset_secs > 0.0 {
+                    "ahead of"
+                } else {
+                    "behind"
+                },
+                max
+            );
+            match action {
+                SkewAction::Warn => warn!("{message}"),
+                SkewAction::Fail => bail!("{message}"),
+            }
+        }
+        Ok(skew)
+    }
+}
+
+/// Parse the output of `date +%s.%N`. Some `date` implementations (e.g. BusyBox)
+/// don't support `%N` and print it literally, so the fraction is optional.
+fn parse_timestamp(output: &str) -> Result<f64> {
+    let (secs, fraction) = output.split_once('.').unwrap_or((output, ""));
+    let secs = secs
+        .parse::<u64>()
+        .with_context(|| format!("unexpected date output: {output:?}"))?;
+    let fraction = if fraction.chars().all(|c| c.is_ascii_digit()) && !fraction.is_empty() {
+        format!("0.{fraction}").parse::<f64>()?
+    } else {
+        0.0
+    };
+    Ok(secs as f64 + fraction)
+}
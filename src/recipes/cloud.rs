@@ -0,0 +1,268 @@
+use std::fmt;
+
+use anyhow::{Context, Result};
+use log::debug;
+use serde_json::Value;
+
+use crate::Session;
+
+const METADATA_TIMEOUT_SECS: &str = "2";
+
+/// A cloud provider detected through its instance metadata service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CloudProvider {
+    /// Amazon EC2.
+    Aws,
+    /// Google Compute Engine.
+    Gcp,
+    /// Microsoft Azure.
+    Azure,
+    /// Hetzner Cloud.
+    Hetzner,
+}
+
+impl fmt::Display for CloudProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CloudProvider::Aws => write!(f, "AWS"),
+            CloudProvider::Gcp => write!(f, "GCP"),
+            CloudProvider::Azure => write!(f, "Azure"),
+            CloudProvider::Hetzner => write!(f, "Hetzner"),
+        }
+    }
+}
+
+/// Information about a cloud instance from the instance metadata service.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CloudMetadata {
+    /// Cloud provider.
+    pub provider: CloudProvider,
+    /// Provider-specific instance ID.
+    pub instance_id: String,
+    /// Instance type, e.g. `t3.micro` (not reported by Hetzner).
+    pub instance_type: Option<String>,
+    /// Region, e.g. `eu-central-1`.
+    pub region: Option<String>,
+    /// Availability zone, e.g. `eu-central-1a`.
+    pub zone: Option<String>,
+    /// Private IPv4 address of the primary network interface.
+    pub private_ipv4: Option<String>,
+    /// Public IPv4 address.
+    pub public_ipv4: Option<String>,
+}
+
+impl Session {
+    /// Query the instance metadata service from the remote host. EC2, GCE, Azure and
+    /// Hetzner Cloud are detected automatically. Returns `None` if no metadata service
+    /// responds (e.g. on bare metal). The result is cached for the session.
+    ///
+    /// Requires `curl` on the remote host.
+    pub async fn cloud_metadata(&mut self) -> Result<Option<CloudMetadata>> {
+        if let Some(cached) = self.cache().get::<Option<CloudMetadata>>() {
+            return Ok(cached.clone());
+        }
+        let metadata = match self.hetzner_metadata().await? {
+            Some(metadata) => Some(metadata),
+            None => match self.gcp_metadata().await? {
+                Some(metadata) => Some(metadata),
+                None => match self.azure_metadata().await? {
+                    Some(metadata) => Some(metadata),
+                    None => self.aws_metadata().await?,
+                },
+            },
+        };
+        match &metadata {
+            Some(metadata) => debug!(
+                "detected {} instance {}",
+                metadata.provider, metadata.instance_id
+            ),
+            None => debug!("no cloud metadata service found"),
+        }
+        self.cache().insert(metadata.clone());
+        Ok(metadata)
+    }
+
+    async fn hetzner_metadata(&self) -> Result<Option<CloudMetadata>> {
+        let Some(metadata) = self
+            .fetch_metadata("http://169.254.169.254/hetzner/v1/metadata", &[], false)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let field = |name: &str| {
+            metadata.lines().find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                (key.trim() == name).then(|| value.trim().trim_matches('"').to_string())
+            })
+        };
+        let Some(instance_id) = field("instance-id") else {
+            return Ok(None);
+        };
+        let private_ipv4 = self
+            .fetch_metadata(
+                "http://169.254.169.254/hetzner/v1/metadata/private-networks",
+                &[],
+                false,
+            )
+            .await?
+            .and_then(|networks| {
+                networks.lines().find_map(|line| {
+                    let value = line.trim().trim_start_matches("- ").strip_prefix("ip:")?;
+                    Some(value.trim().to_string())
+                })
+            });
+        Ok(Some(CloudMetadata {
+            provider: CloudProvider::Hetzner,
+            instance_id,
+            instance_type: None,
+            region: field("region"),
+            zone: field("availability-zone"),
+            private_ipv4,
+            public_ipv4: field("public-ipv4"),
+        }))
+    }
+
+    async fn gcp_metadata(&self) -> Result<Option<CloudMetadata>> {
+        let Some(metadata) = self
+            .fetch_metadata(
+                "http://metadata.google.internal/computeMetadata/v1/instance/?recursive=true",
+                &["Metadata-Flavor: Google"],
+                false,
+            )
+            .await?
+        else {
+            return Ok(None);
+        };
+        let metadata: Value = serde_json::from_str(&metadata).context("invalid GCE metadata")?;
+        let last_segment = |value: &Value| {
+            value
+                .as_str()
+                .and_then(|v| v.rsplit('/').next())
+                .map(String::from)
+        };
+        let zone = last_segment(&metadata["zone"]);
+        let region = zone
+            .as_deref()
+            .and_then(|zone| zone.rsplit_once('-'))
+            .map(|(region, _)| region.to_string());
+        let interface = &metadata["networkInterfaces"][0];
+        Ok(Some(CloudMetadata {
+            provider: CloudProvider::Gcp,
+            instance_id: json_string(&metadata["id"]).context("missing GCE instance id")?,
+            instance_type: last_segment(&metadata["machineType"]),
+            region,
+            zone,
+            private_ipv4: json_string(&interface["ip"]),
+            public_ipv4: json_string(&interface["accessConfigs"][0]["externalIp"]),
+        }))
+    }
+
+    async fn azure_metadata(&self) -> Result<Option<CloudMetadata>> {
+        let Some(metadata) = self
+            .fetch_metadata(
+                "http://169.254.169.254/metadata/instance?api-version=2021-02-01",
+                &["Metadata: true"],
+                false,
+            )
+            .await?
+        else {
+            return Ok(None);
+        };
+        let metadata: Value = serde_json::from_str(&metadata).context("invalid Azure metadata")?;
+        let compute = &metadata["compute"];
+        let address = &metadata["network"]["interface"][0]["ipv4"]["ipAddress"][0];
+        Ok(Some(CloudMetadata {
+            provider: CloudProvider::Azure,
+            instance_id: json_string(&compute["vmId"]).context("missing Azure VM id")?,
+            instance_type: json_string(&compute["vmSize"]),
+            region: json_string(&compute["location"]),
+            zone: json_string(&compute["zone"]),
+            private_ipv4: json_string(&address["privateIpAddress"]),
+            public_ipv4: json_string(&address["publicIpAddress"]),
+        }))
+    }
+
+    async fn aws_metadata(&self) -> Result<Option<CloudMetadata>> {
+        // IMDSv2 requires a session token.
+        let Some(token) = self
+            .fetch_metadata(
+                "http://169.254.169.254/latest/api/token",
+                &["X-aws-ec2-metadata-token-ttl-seconds: 60"],
+                true,
+            )
+            .await?
+        else {
+            return Ok(None);
+        };
+        let header = format!("X-aws-ec2-metadata-token: {}", token.trim());
+        let Some(document) = self
+            .fetch_metadata(
+                "http://169.254.169.254/latest/dynamic/instance-identity/document",
+                &[&header],
+                false,
+            )
+            .await?
+        else {
+            return Ok(None);
+        };
+        let document: Value =
+            serde_json::from_str(&document).context("invalid EC2 identity document")?;
+        let public_ipv4 = self
+            .fetch_metadata(
+                "http://169.254.169.254/latest/meta-data/public-ipv4",
+                &[&header],
+                false,
+            )
+            .await?
+            .map(|ip| ip.trim().to_string());
+        Ok(Some(CloudMetadata {
+            provider: CloudProvider::Aws,
+            instance_id: json_string(&document["instanceId"]).context("missing EC2 instance id")?,
+            instance_type: json_string(&document["instanceType"]),
+            region: json_string(&document["region"]),
+            zone: json_string(&document["availabilityZone"]),
+            private_ipv4: json_string(&document["privateIp"]),
+            public_ipv4,
+        }))
+    }
+
+    /// Request a metadata URL with curl. Returns `None` if the request fails.
+    async fn fetch_metadata(
+        &self,
+        url: &str,
+        headers: &[&str],
+        put: bool,
+    ) -> Result<Option<String>> {
+        let mut command = self.command([
+            "curl",
+            "--silent",
+            "--fail",
+            "--noproxy",
+            "*",
+            "--max-time",
+            METADATA_TIMEOUT_SECS,
+        ]);
+        if put {
+            command = command.args(["--request", "PUT"]);
+        }
+        for header in headers {
+            command = command.args(["--header", header]);
+        }
+        let output = command
+            .arg(url)
+            .hide_command()
+            .hide_all_output()
+            .allow_failure()
+            .run()
+            .await?;
+        Ok((output.exit_code == 0).then_some(output.stdout))
+    }
+}
+
+fn json_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) if !value.is_empty() => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
@@ -0,0 +1,201 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use log::{debug, info, warn};
+
+use crate::Session;
+
+/// Overall state of cloud-init.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CloudInitState {
+    /// cloud-init hasn't started yet.
+    NotRun,
+    /// cloud-init is still running.
+    Running,
+    /// cloud-init has finished successfully.
+    Done,
+    /// cloud-init has finished with errors.
+    Error,
+    /// cloud-init is disabled on this host.
+    Disabled,
+    /// A state not known to this crate.
+    Other(String),
+}
+
+impl CloudInitState {
+    fn parse(value: &str) -> Self {
+        match value {
+            "not run" | "not started" => Self::NotRun,
+            "running" => Self::Running,
+            "done" => Self::Done,
+            "error" => Self::Error,
+            "disabled" => Self::Disabled,
+            _ => Self::Other(value.into()),
+        }
+    }
+
+    /// Whether cloud-init won't do anything else until the next boot.
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Done | Self::Error | Self::Disabled)
+    }
+}
+
+/// Output of `cloud-init status --long`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CloudInitStatus {
+    /// Overall state.
+    pub state: CloudInitState,
+    /// More specific state reported by newer versions, e.g. `degraded done`.
+    pub extended_status: Option<String>,
+    /// Time of the last status change.
+    pub last_update: Option<String>,
+    /// Details about the current stage or the datasource.
+    pub detail: Option<String>,
+    /// Reported errors.
+    pub errors: Vec<String>,
+}
+
+impl CloudInitStatus {
+    /// Parse output of `cloud-init status --long`.
+    pub fn parse(output: &str) -> Result<Self> {
+        let mut state = None;
+        let mut extended_status = None;
+        let mut last_update = None;
+        let mut detail: Option<String> = None;
+        let mut errors = Vec::new();
+        let mut current_key = "";
+        for line in output.lines() {
+            let continuation = line.starts_with([' ', '\t']) || !line.contains(':');
+            if let (false, Some((key, value))) = (continuation, line.split_once(':')) {
+                current_key = key.trim();
+                let value = value.trim();
+                match current_key {
+                    "status" => state = Some(CloudInitState::parse(value)),
+                    "extended_status" => extended_status = Some(value.to_string()),
+                    "last_update" | "time" => last_update = Some(value.to_string()),
+                    "detail" if !value.is_empty() => detail = Some(value.to_string()),
+                    "errors" => errors.extend(parse_inline_list(value)),
+                    _ => {}
+                }
+                continue;
+            }
+            let value = line.trim();
+            if value.is_empty() {
+                continue;
+            }
+            match current_key {
+                "detail" => match &mut detail {
+                    Some(detail) => {
+                        detail.push('\n');
+                        detail.push_str(value);
+                    }
+                    None => detail = Some(value.to_string()),
+                },
+                "errors" => errors.push(value.trim_start_matches("- ").to_string()),
+                _ => {}
+            }
+        }
+        let Some(state) = state else {
+            bail!("missing status in cloud-init output: {output:?}");
+        };
+        Ok(Self {
+            state,
+            extended_status,
+            last_update,
+            detail,
+            errors,
+        })
+    }
+}
+
+fn parse_inline_list(value: &str) -> Vec<String> {
+    let Some(items) = value
+        .strip_prefix('[')
+        .and_then(|value| value.strip_suffix(']'))
+    else {
+        return Vec::new();
+    };
+    items
+        .split(',')
+        .map(|item| item.trim().trim_matches(['\'', '"']).to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+impl Session {
+    /// Inspect cloud-init, which performs first-boot setup of cloud VMs.
+    pub fn cloud_init(&mut self) -> CloudInit<'_> {
+        CloudInit(self)
+    }
+}
+
+/// Provides access to cloud-init status.
+pub struct CloudInit<'a>(&'a mut Session);
+
+impl CloudInit<'_> {
+    /// Check if cloud-init is installed.
+    pub async fn is_installed(&mut self) -> Result<bool> {
+        let code = self
+            .0
+            .command(["cloud-init", "--version"])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        Ok(code == 0)
+    }
+
+    /// Fetch the current status (`cloud-init status --long`).
+    pub async fn status(&mut self) -> Result<CloudInitStatus> {
+        // Non-zero exit codes are used to report errors, but the output is still valid.
+        let output = self
+            .0
+            .command(["cloud-init", "status", "--long"])
+            .hide_command()
+            .hide_all_output()
+            .allow_failure()
+            .run()
+            .await?;
+        CloudInitStatus::parse(&output.stdout)
+    }
+
+    /// Wait until cloud-init has finished, for up to `timeout`. Returns immediately
+    /// if cloud-init is not installed. Fails if cloud-init has finished with errors or
+    /// hasn't finished in time.
+    pub async fn wait_finished(&mut self, timeout: Duration) -> Result<()> {
+        if !self.is_installed().await? {
+            debug!("cloud-init is not installed");
+            return Ok(());
+        }
+        info!("waiting for cloud-init to finish");
+        let code = self
+            .0
+            .command([
+                "timeout",
+                &timeout.as_secs().max(1).to_string(),
+                "cloud-init",
+                "status",
+                "--wait",
+            ])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        if code == 124 {
+            bail!("cloud-init hasn't finished in {} s", timeout.as_secs());
+        }
+        let status = self.status().await?;
+        match status.state {
+            CloudInitState::Done | CloudInitState::Disabled => {
+                if !status.errors.is_empty() {
+                    warn!("cloud-init reported errors: {:?}", status.errors);
+                }
+                Ok(())
+            }
+            CloudInitState::Error => {
+                bail!("cloud-init has failed: {:?}", status.errors)
+            }
+            state => bail!("unexpected cloud-init state after waiting: {state:?}"),
+        }
+    }
+}
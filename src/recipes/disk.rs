@@ -0,0 +1,172 @@
+use anyhow::{bail, Context, Result};
+use log::info;
+
+use crate::Session;
+
+/// A block device with a filesystem (or other) signature, as reported by `blkid`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct BlockDevice {
+    /// Device path, e.g. `/dev/sdb1`.
+    pub device: String,
+    /// Filesystem UUID.
+    pub uuid: Option<String>,
+    /// Filesystem label.
+    pub label: Option<String>,
+    /// Filesystem type, e.g. `ext4`.
+    pub fs_type: Option<String>,
+    /// Partition UUID.
+    pub part_uuid: Option<String>,
+    /// Partition label.
+    pub part_label: Option<String>,
+}
+
+impl BlockDevice {
+    /// Parse output of `blkid -o export`.
+    pub fn parse_export(output: &str) -> Result<Vec<Self>> {
+        let mut devices = Vec::new();
+        let mut current: Option<Self> = None;
+        for line in output.lines().map(str::trim) {
+            if line.is_empty() {
+                devices.extend(current.take());
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .with_context(|| format!("invalid blkid output line: {line:?}"))?;
+            if key == "DEVNAME" {
+                devices.extend(current.take());
+                current = Some(Self {
+                    device: value.into(),
+                    ..Self::default()
+                });
+                continue;
+            }
+            let device = current
+                .as_mut()
+                .with_context(|| format!("unexpected blkid output line: {line:?}"))?;
+            let value = Some(value.to_string());
+            match key {
+                "UUID" => device.uuid = value,
+                "LABEL" => device.label = value,
+                "TYPE" => device.fs_type = value,
+                "PARTUUID" => device.part_uuid = value,
+                "PARTLABEL" => device.part_label = value,
+                _ => {}
+            }
+        }
+        devices.extend(current);
+        Ok(devices)
+    }
+}
+
+impl Session {
+    /// List block devices that have a filesystem (or other) signature.
+    pub async fn block_devices(&self) -> Result<Vec<BlockDevice>> {
+        let output = self
+            .command(["blkid", "-o", "export"])
+            .hide_command()
+            .hide_all_output()
+            .allow_failure()
+            .run()
+            .await?;
+        // Exit code 2 means no devices were found.
+        match output.exit_code {
+            0 => BlockDevice::parse_export(&output.stdout),
+            2 => Ok(Vec::new()),
+            code => bail!(
+                "blkid failed with exit code {code}: {}",
+                output.stderr.trim()
+            ),
+        }
+    }
+
+    /// Get information about a single block device. Returns `None` if the device
+    /// has no recognized signature.
+    pub async fn block_device(&self, device: &str) -> Result<Option<BlockDevice>> {
+        let output = self
+            .command(["blkid", "-p", "-o", "export", "--", device])
+            .hide_command()
+            .hide_all_output()
+            .allow_failure()
+            .run()
+            .await?;
+        match output.exit_code {
+            0 => {
+                let mut devices = BlockDevice::parse_export(&output.stdout)?;
+                if devices.is_empty() {
+                    // Probing mode doesn't always print DEVNAME.
+                    devices =
+                        BlockDevice::parse_export(&format!("DEVNAME={device}\n{}", output.stdout))?;
+                }
+                Ok(devices.into_iter().next())
+            }
+            2 => Ok(None),
+            code => bail!(
+                "blkid failed with exit code {code}: {}",
+                output.stderr.trim()
+            ),
+        }
+    }
+
+    /// Find the device path of a filesystem by its UUID.
+    pub async fn find_device_by_uuid(&self, uuid: &str) -> Result<Option<String>> {
+        Ok(self
+            .block_devices()
+            .await?
+            .into_iter()
+            .find(|device| device.uuid.as_deref() == Some(uuid))
+            .map(|device| device.device))
+    }
+
+    /// Find the device path of a filesystem by its label.
+    pub async fn find_device_by_label(&self, label: &str) -> Result<Option<String>> {
+        Ok(self
+            .block_devices()
+            .await?
+            .into_iter()
+            .find(|device| device.label.as_deref() == Some(label))
+            .map(|device| device.device))
+    }
+
+    /// Create a filesystem of type `fs_type` on `device`.
+    ///
+    /// If the device already contains a filesystem or another signature, this fails
+    /// unless `force` is set, in which case existing signatures are wiped first.
+    /// Returns information about the new filesystem.
+    pub async fn mkfs(
+        &self,
+        device: &str,
+        fs_type: &str,
+        label: Option<&str>,
+        force: bool,
+    ) -> Result<BlockDevice> {
+        if fs_type.is_empty() || !fs_type.chars().all(|c| c.is_ascii_alphanumeric()) {
+            bail!("invalid filesystem type: {fs_type:?}");
+        }
+        if let Some(existing) = self.block_device(device).await? {
+            if !force {
+                bail!(
+                    "{device} already contains a {} signature, refusing to create filesystem \
+                    without force",
+                    existing.fs_type.as_deref().unwrap_or("unknown")
+                );
+            }
+            self.command(["wipefs", "--all", "--", device])
+                .run()
+                .await?;
+        }
+        let mut command = self.command([format!("mkfs.{fs_type}")]);
+        if let Some(label) = label {
+            let flag = match fs_type {
+                "vfat" | "fat" | "msdos" => "-n",
+                _ => "-L",
+            };
+            command = command.args([flag, label]);
+        }
+        command.arg(device).run().await?;
+        info!("created {fs_type} filesystem on {device}");
+        self.block_device(device)
+            .await?
+            .with_context(|| format!("no filesystem found on {device} after mkfs"))
+    }
+}
@@ -0,0 +1,171 @@
+use anyhow::{bail, Result};
+use log::info;
+
+use crate::{Distro, Session};
+
+const CONFIG_PATH: &str = "/etc/dnf/dnf.conf";
+pub(crate) const DNF_DISTROS: &[Distro] = &[
+    Distro::Fedora,
+    Distro::Rhel,
+    Distro::CentOs,
+    Distro::Rocky,
+    Distro::AlmaLinux,
+];
+
+/// A pending security update reported by `dnf updateinfo`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DnfSecurityUpdate {
+    /// Package name.
+    pub name: String,
+    /// Version and release that fixes the issues, e.g. `3.0.7-25.el9_3`.
+    pub version: String,
+    /// IDs of the fixed CVEs.
+    pub cves: Vec<String>,
+}
+
+impl Session {
+    /// Configure the dnf package manager (Fedora and RHEL-based distributions).
+    pub fn dnf(&mut self) -> Dnf<'_> {
+        Dnf(self)
+    }
+}
+
+/// Provides access to dnf configuration and security updates.
+pub struct Dnf<'a>(&'a mut Session);
+
+impl Dnf<'_> {
+    /// Make dnf use an HTTP proxy (e.g. `http://proxy.internal:3128`) for all repositories,
+    /// or remove the proxy setting if `proxy` is `None`.
+    /// Returns `true` if the configuration was changed.
+    pub async fn set_proxy(&mut self, proxy: Option<&str>) -> Result<bool> {
+        self.require_dnf().await?;
+        if let Some(proxy) = proxy {
+            if proxy.is_empty() || proxy.contains(char::is_whitespace) {
+                bail!("invalid proxy URL: {proxy:?}");
+            }
+        }
+        let current = self
+            .0
+            .read_file_if_exists(CONFIG_PATH)
+            .await?
+            .unwrap_or_else(|| "[main]\n".into());
+        let config = set_main_option(&current, "proxy", proxy);
+        if config == current {
+            return Ok(false);
+        }
+        self.0.update_file(CONFIG_PATH, config).await?;
+        match proxy {
+            Some(proxy) => info!("set dnf proxy to {proxy}"),
+            None => info!("removed dnf proxy"),
+        }
+        Ok(true)
+    }
+
+    /// List installed packages that have security updates, with the CVEs they fix.
+    pub async fn security_updates(&mut self) -> Result<Vec<DnfSecurityUpdate>> {
+        self.require_dnf().await?;
+        let output = self
+            .0
+            .command([
+                "dnf",
+                "updateinfo",
+                "list",
+                "--security",
+                "--with-cve",
+                "--quiet",
+            ])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?;
+        let mut updates: Vec<DnfSecurityUpdate> = Vec::new();
+        // Format: `CVE-2023-1234 Important/Sec. openssl-libs-1:3.0.7-25.el9_3.x86_64`
+        for line in output.stdout_lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [cve, _severity, package] = fields[..] else {
+                continue;
+            };
+            let Some((name, version)) = split_nevra(package) else {
+                continue;
+            };
+            match updates
+                .iter_mut()
+                .find(|update| update.name == name && update.version == version)
+            {
+                Some(update) => {
+                    if !update.cves.iter().any(|c| c == cve) {
+                        update.cves.push(cve.into());
+                    }
+                }
+                None => updates.push(DnfSecurityUpdate {
+                    name: name.into(),
+                    version: version.into(),
+                    cves: vec![cve.into()],
+                }),
+            }
+        }
+        Ok(updates)
+    }
+
+    /// Apply security updates only (`dnf upgrade --security`).
+    pub async fn upgrade_security(&mut self) -> Result<()> {
+        self.require_dnf().await?;
+        self.0
+            .command(["dnf", "upgrade", "--security", "--assumeyes"])
+            .run()
+            .await?;
+        Ok(())
+    }
+
+    async fn require_dnf(&mut self) -> Result<()> {
+        self.0.require_os_for("dnf", DNF_DISTROS).await
+    }
+}
+
+/// Split `name-[epoch:]version-release.arch` into the name and `version-release`.
+fn split_nevra(package: &str) -> Option<(&str, &str)> {
+    let (package, _arch) = package.rsplit_once('.')?;
+    let (rest, release) = package.rsplit_once('-')?;
+    let (name, version) = rest.rsplit_once('-')?;
+    let version_start = version.len() + release.len() + 1;
+    let version = &package[package.len() - version_start..];
+    let version = version.split_once(':').map_or(version, |(_, v)| v);
+    Some((name, version))
+}
+
+/// Set or remove `key` in the `[main]` section of an INI file.
+fn set_main_option(config: &str, key: &str, value: Option<&str>) -> String {
+    let mut lines = Vec::new();
+    let mut section = "";
+    let mut inserted = false;
+    for line in config.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            section = trimmed;
+            lines.push(line.to_string());
+            if section == "[main]" && !inserted {
+                if let Some(value) = value {
+                    lines.push(format!("{key}={value}"));
+                }
+                inserted = true;
+            }
+            continue;
+        }
+        let is_key = trimmed
+            .split_once('=')
+            .is_some_and(|(name, _)| name.trim() == key);
+        if section == "[main]" && is_key {
+            continue;
+        }
+        lines.push(line.to_string());
+    }
+    if !inserted {
+        if let Some(value) = value {
+            lines.push("[main]".into());
+            lines.push(format!("{key}={value}"));
+        }
+    }
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
@@ -0,0 +1,230 @@
+use std::net::IpAddr;
+
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+
+use crate::Session;
+
+const HOSTS_PATH: &str = "/etc/hosts";
+const RESOLVED_DROP_IN_PATH: &str = "/etc/systemd/resolved.conf.d/roguewave.conf";
+const RESOLV_CONF_PATH: &str = "/etc/resolv.conf";
+
+impl Session {
+    /// Manage `/etc/hosts` and DNS resolver configuration.
+    pub fn dns(&mut self) -> Dns<'_> {
+        Dns(self)
+    }
+}
+
+/// Provides access to name resolution configuration.
+pub struct Dns<'a>(&'a mut Session);
+
+impl Dns<'_> {
+    /// Make `hostname` resolve to `ip` in `/etc/hosts`. Other entries for `hostname` are removed.
+    /// Returns `true` if the file was changed.
+    pub async fn set_host(&mut self, hostname: &str, ip: IpAddr) -> Result<bool> {
+        validate_hostname(hostname)?;
+        let hosts = self.read_hosts().await?;
+        if hosts.lines().any(|line| {
+            let mut fields = entry_fields(line);
+            fields.next() == Some(&ip.to_string()) && fields.any(|name| name == hostname)
+        }) && hosts_entries(&hosts, hostname) == 1
+        {
+            return Ok(false);
+        }
+        let mut new_hosts = remove_host_from_hosts(&hosts, hostname);
+        new_hosts.push_str(&format!("{ip}\t{hostname}\n"));
+        self.0.update_file(HOSTS_PATH, new_hosts).await
+    }
+
+    /// Remove all entries for `hostname` from `/etc/hosts`.
+    /// Returns `true` if the file was changed.
+    pub async fn remove_host(&mut self, hostname: &str) -> Result<bool> {
+        validate_hostname(hostname)?;
+        let hosts = self.read_hosts().await?;
+        if hosts_entries(&hosts, hostname) == 0 {
+            return Ok(false);
+        }
+        let new_hosts = remove_host_from_hosts(&hosts, hostname);
+        self.0.update_file(HOSTS_PATH, new_hosts).await
+    }
+
+    /// Resolve `hostname` on the remote host using the system resolver (`getent ahosts`).
+    /// Returns an empty list if the name can't be resolved.
+    pub async fn resolve(&mut self, hostname: &str) -> Result<Vec<IpAddr>> {
+        let output = self
+            .0
+            .command(["getent", "ahosts", hostname])
+            .hide_command()
+            .hide_stdout()
+            .allow_failure()
+            .run()
+            .await?;
+        match output.exit_code {
+            0 => {}
+            2 => return Ok(Vec::new()),
+            code => bail!("getent failed with exit code {code}"),
+        }
+        let mut addresses = Vec::new();
+        for line in output.stdout_lines() {
+            let Some(address) = line.split_whitespace().next() else {
+                continue;
+            };
+            let address = address.parse().context("invalid getent output")?;
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+        Ok(addresses)
+    }
+
+    /// Configure DNS servers and search domains for systemd-resolved using a drop-in config file.
+    ///
+    /// After the change, `check_hostname` is resolved to verify that name resolution still works.
+    /// If it fails, the previous configuration is restored.
+    /// Returns `true` if the configuration was changed.
+    pub async fn configure_resolved(
+        &mut self,
+        servers: &[IpAddr],
+        search_domains: &[&str],
+        check_hostname: &str,
+    ) -> Result<bool> {
+        let mut config = "[Resolve]\n".to_string();
+        config.push_str(&format!("DNS={}\n", join(servers)));
+        config.push_str(&format!("Domains={}\n", search_domains.join(" ")));
+        self.0
+            .command(["mkdir", "-p", "/etc/systemd/resolved.conf.d"])
+            .hide_command()
+            .run()
+            .await?;
+        self.apply_config(
+            RESOLVED_DROP_IN_PATH,
+            &config,
+            &["systemctl", "restart", "systemd-resolved"],
+            check_hostname,
+        )
+        .await
+    }
+
+    /// Write DNS servers and search domains to `/etc/resolv.conf` directly.
+    /// Use this on hosts that don't use systemd-resolved or another resolv.conf manager.
+    ///
+    /// After the change, `check_hostname` is resolved to verify that name resolution still works.
+    /// If it fails, the previous configuration is restored.
+    /// Returns `true` if the configuration was changed.
+    pub async fn configure_resolv_conf(
+        &mut self,
+        servers: &[IpAddr],
+        search_domains: &[&str],
+        check_hostname: &str,
+    ) -> Result<bool> {
+        let mut config = String::new();
+        if !search_domains.is_empty() {
+            config.push_str(&format!("search {}\n", search_domains.join(" ")));
+        }
+        for server in servers {
+            config.push_str(&format!("nameserver {server}\n"));
+        }
+        self.apply_config(RESOLV_CONF_PATH, &config, &[], check_hostname)
+            .await
+    }
+
+    async fn apply_config(
+        &mut self,
+        path: &str,
+        config: &str,
+        reload_command: &[&str],
+        check_hostname: &str,
+    ) -> Result<bool> {
+        let previous = self.0.read_file_if_exists(path).await?;
+        if previous.as_deref() == Some(config) {
+            return Ok(false);
+        }
+        self.0.update_file(path, config).await?;
+        if !reload_command.is_empty() {
+            self.0.command(reload_command).run().await?;
+        }
+        if !self.resolve(check_hostname).await?.is_empty() {
+            return Ok(true);
+        }
+        warn!("name resolution is broken after changing {path}, restoring previous config");
+        match previous {
+            Some(previous) => {
+                self.0.update_file(path, previous).await?;
+            }
+            None => {
+                self.0.remove_file(path).await?;
+            }
+        }
+        if !reload_command.is_empty() {
+            self.0.command(reload_command).run().await?;
+        }
+        info!("restored previous config in {path}");
+        bail!("failed to resolve {check_hostname:?} after changing {path}");
+    }
+
+    async fn read_hosts(&mut self) -> Result<String> {
+        self.0
+            .read_file_if_exists(HOSTS_PATH)
+            .await
+            .map(Option::unwrap_or_default)
+    }
+}
+
+fn validate_hostname(hostname: &str) -> Result<()> {
+    if hostname.is_empty()
+        || !hostname
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_')
+    {
+        bail!("invalid hostname: {hostname:?}");
+    }
+    Ok(())
+}
+
+fn join(addresses: &[IpAddr]) -> String {
+    addresses
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Fields of an `/etc/hosts` line without the comment.
+fn entry_fields(line: &str) -> std::str::SplitWhitespace<'_> {
+    line.split('#')
+        .next()
+        .unwrap_or_default()
+        .split_whitespace()
+}
+
+/// Number of lines in `/etc/hosts` that contain `hostname`.
+fn hosts_entries(hosts: &str, hostname: &str) -> usize {
+    hosts
+        .lines()
+        .filter(|line| entry_fields(line).skip(1).any(|name| name == hostname))
+        .count()
+}
+
+/// Remove `hostname` from all lines. Lines that don't have other names left are removed.
+fn remove_host_from_hosts(hosts: &str, hostname: &str) -> String {
+    let mut output = String::new();
+    for line in hosts.lines() {
+        let mut fields = entry_fields(line);
+        let address = fields.next();
+        let names: Vec<_> = fields.collect();
+        match address {
+            Some(address) if names.contains(&hostname) => {
+                let other_names: Vec<_> = names.into_iter().filter(|n| *n != hostname).collect();
+                if !other_names.is_empty() {
+                    output.push_str(&format!("{address}\t{}\n", other_names.join(" ")));
+                }
+            }
+            _ => {
+                output.push_str(line);
+                output.push('\n');
+            }
+        }
+    }
+    output
+}
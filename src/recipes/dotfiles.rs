@@ -0,0 +1,125 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use anyhow::{bail, Result};
+
+use crate::{command::shell_quote, Session};
+
+impl Session {
+    /// Manage shell dotfiles of the specified user. If no user is specified, the current user
+    /// is used.
+    ///
+    /// Managed content is kept in marker blocks, so the rest of each file is left intact.
+    pub fn dotfiles(&mut self, user: Option<&str>) -> Dotfiles<'_> {
+        Dotfiles {
+            session: self,
+            user: user.map(Into::into),
+        }
+    }
+
+    /// Write a file to `/etc/skel` so that it's provisioned into home directories of
+    /// newly created users. Returns `true` if the file was changed.
+    pub async fn set_skeleton_file(&mut self, name: &str, content: &str) -> Result<bool> {
+        validate_file_name(name)?;
+        self.update_file(format!("/etc/skel/{name}"), content).await
+    }
+}
+
+/// Provides access to dotfiles of a user.
+pub struct Dotfiles<'a> {
+    session: &'a mut Session,
+    user: Option<String>,
+}
+
+impl Dotfiles<'_> {
+    /// Set the content of the block `name` in `file` (relative to the home directory,
+    /// e.g. `.bashrc`). Returns `true` if the file was changed.
+    pub async fn set_fragment(&mut self, file: &str, name: &str, content: &str) -> Result<bool> {
+        validate_file_name(file)?;
+        validate_fragment_name(name)?;
+        self.update_block(file, name, content).await
+    }
+
+    /// Remove the block `name` from `file`. Returns `true` if the file was changed.
+    pub async fn remove_fragment(&mut self, file: &str, name: &str) -> Result<bool> {
+        validate_file_name(file)?;
+        validate_fragment_name(name)?;
+        self.update_block(file, name, "").await
+    }
+
+    /// Define shell aliases in the rc file of the user's shell (`.zshrc` for zsh,
+    /// `.bashrc` otherwise). Replaces aliases previously set by this method.
+    /// Returns `true` if the file was changed.
+    pub async fn set_aliases(&mut self, aliases: &BTreeMap<String, String>) -> Result<bool> {
+        let mut block = String::new();
+        for (name, command) in aliases {
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+            {
+                bail!("invalid alias name: {name:?}");
+            }
+            writeln!(block, "alias {name}={}", shell_quote(command))?;
+        }
+        let file = self.rc_file().await?;
+        self.update_block(file, "aliases", &block).await
+    }
+
+    /// Prepend directories to `PATH` in `.profile`. Replaces directories previously
+    /// added by this method. Returns `true` if the file was changed.
+    pub async fn set_path_additions(&mut self, dirs: &[&str]) -> Result<bool> {
+        let mut block = String::new();
+        for dir in dirs.iter().rev() {
+            if dir.is_empty() || dir.contains(':') {
+                bail!("invalid PATH directory: {dir:?}");
+            }
+            writeln!(block, "export PATH={}:\"$PATH\"", shell_quote(dir))?;
+        }
+        self.update_block(".profile", "path", &block).await
+    }
+
+    async fn rc_file(&mut self) -> Result<&'static str> {
+        let shell = self.session.shell(self.user.as_deref()).await?;
+        Ok(if shell.ends_with("/zsh") {
+            ".zshrc"
+        } else {
+            ".bashrc"
+        })
+    }
+
+    async fn update_block(&mut self, file: &str, name: &str, block: &str) -> Result<bool> {
+        let user = self.user.as_deref();
+        let path = format!("{}/{file}", self.session.home_dir(user).await?);
+        let existed = self.session.path_exists(&path).await?;
+        let changed = self
+            .session
+            .update_managed_block(&path, &format!("roguewave {name}"), block)
+            .await?;
+        if let (Some(user), false, true) = (user, existed, changed) {
+            self.session
+                .command(["chown", &format!("{user}:"), "--", &path])
+                .hide_command()
+                .run()
+                .await?;
+        }
+        Ok(changed)
+    }
+}
+
+fn validate_file_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.starts_with('/') || name.split('/').any(|part| part == "..") {
+        bail!("invalid dotfile name: {name:?}");
+    }
+    Ok(())
+}
+
+fn validate_fragment_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        bail!("invalid fragment name: {name:?}");
+    }
+    Ok(())
+}
@@ -1,8 +1,8 @@
 use std::collections::BTreeMap;
 
-use anyhow::Context;
+use anyhow::{bail, Context, Result};
 
-use crate::Session;
+use crate::{command::shell_quote, Session};
 
 impl Session {
     /// Read environment variables for the specified user. If no user if specified, the current user
@@ -15,21 +15,18 @@ impl Session {
 
         if !cache_has_user {
             let output = self
-                .command(["env"])
+                .command(["env", "-0"])
                 .user(user)
                 .hide_command()
                 .hide_stdout()
                 .run()
                 .await?;
             let mut env = BTreeMap::new();
-            for line in output.stdout.split('\n') {
-                if line.is_empty() {
+            for entry in output.stdout.split('\0') {
+                if entry.is_empty() {
                     continue;
                 }
-                let line = line.trim_end_matches('\n');
-                let mut iter = line.splitn(2, '=');
-                let name = iter.next().unwrap();
-                let value = iter.next().context("missing '=' in env output")?;
+                let (name, value) = entry.split_once('=').context("missing '=' in env output")?;
                 env.insert(name.to_string(), value.to_string());
             }
             let cache = self
@@ -89,6 +86,88 @@ impl Session {
         }
         Ok(())
     }
+
+    /// Persistently set an environment variable for login shells of the specified user
+    /// (or the current user) by exporting it from `~/.profile`.
+    /// Returns `true` if the file was changed.
+    pub async fn set_persistent_env(
+        &mut self,
+        user: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> Result<bool> {
+        validate_env_name(key)?;
+        let block = format!("export {key}={}", shell_quote(value));
+        self.update_profile_env(user, key, &block).await
+    }
+
+    /// Remove an environment variable previously set with `set_persistent_env`.
+    /// Returns `true` if the file was changed.
+    pub async fn remove_persistent_env(&mut self, user: Option<&str>, key: &str) -> Result<bool> {
+        validate_env_name(key)?;
+        self.update_profile_env(user, key, "").await
+    }
+
+    async fn update_profile_env(
+        &mut self,
+        user: Option<&str>,
+        key: &str,
+        block: &str,
+    ) -> Result<bool> {
+        let path = format!("{}/.profile", self.home_dir(user).await?);
+        let existed = self.path_exists(&path).await?;
+        let changed = self
+            .update_managed_block(&path, &format!("roguewave env {key}"), block)
+            .await?;
+        if let (Some(user), false, true) = (user, existed, changed) {
+            self.command(["chown", &format!("{user}:"), "--", &path])
+                .hide_command()
+                .run()
+                .await?;
+        }
+        if changed {
+            self.cache().remove::<EnvCache>();
+        }
+        Ok(changed)
+    }
+
+    /// Persistently set a system-wide environment variable in `/etc/environment`.
+    /// Returns `true` if the file was changed.
+    pub async fn set_system_env(&mut self, key: &str, value: &str) -> Result<bool> {
+        validate_env_name(key)?;
+        if value.contains(['"', '\n']) {
+            bail!("unsupported value for {key} in /etc/environment");
+        }
+        self.update_system_env(key, &format!("{key}=\"{value}\""))
+            .await
+    }
+
+    /// Remove a system-wide environment variable previously set with `set_system_env`.
+    /// Returns `true` if the file was changed.
+    pub async fn remove_system_env(&mut self, key: &str) -> Result<bool> {
+        validate_env_name(key)?;
+        self.update_system_env(key, "").await
+    }
+
+    async fn update_system_env(&mut self, key: &str, block: &str) -> Result<bool> {
+        let changed = self
+            .update_managed_block("/etc/environment", &format!("roguewave env {key}"), block)
+            .await?;
+        if changed {
+            self.cache().remove::<EnvCache>();
+        }
+        Ok(changed)
+    }
+}
+
+pub(crate) fn validate_env_name(key: &str) -> Result<()> {
+    if key.is_empty()
+        || key.starts_with(|c: char| c.is_ascii_digit())
+        || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        bail!("invalid environment variable name: {key:?}");
+    }
+    Ok(())
 }
 
 #[derive(Default)]
@@ -0,0 +1,150 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use anyhow::{bail, Result};
+use log::info;
+
+use crate::{recipes::env::validate_env_name, Session};
+
+impl Session {
+    /// Manage a `KEY=VALUE` environment file (e.g. `/etc/app/app.env`).
+    ///
+    /// Variables are written in sorted order. Values are never logged.
+    /// New files are created with `0600` permissions.
+    pub fn env_file(&mut self, path: impl Into<String>) -> EnvFile<'_> {
+        EnvFile {
+            session: self,
+            path: path.into(),
+            mode: 0o600,
+        }
+    }
+}
+
+/// Provides access to an environment file on the remote host.
+pub struct EnvFile<'a> {
+    session: &'a mut Session,
+    path: String,
+    mode: u32,
+}
+
+impl EnvFile<'_> {
+    /// Set permissions of the file (default is `0o600`).
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Read variables from the file. Returns an empty map if the file doesn't exist.
+    pub async fn read(&mut self) -> Result<BTreeMap<String, String>> {
+        let Some(content) = self.session.read_file_if_exists(&self.path).await? else {
+            return Ok(BTreeMap::new());
+        };
+        let mut vars = BTreeMap::new();
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                bail!("invalid line in {:?}: missing '='", self.path);
+            };
+            vars.insert(key.trim().to_string(), unquote(value));
+        }
+        Ok(vars)
+    }
+
+    /// Set a variable. Returns `true` if the file was changed.
+    pub async fn set(&mut self, key: &str, value: &str) -> Result<bool> {
+        let mut vars = self.read().await?;
+        vars.insert(key.into(), value.into());
+        let changed = self.render(&vars).await?;
+        if changed {
+            info!("set {key} in {:?}", self.path);
+        }
+        Ok(changed)
+    }
+
+    /// Remove a variable. Returns `true` if the file was changed.
+    pub async fn remove(&mut self, key: &str) -> Result<bool> {
+        let mut vars = self.read().await?;
+        if vars.remove(key).is_none() {
+            return Ok(false);
+        }
+        let changed = self.render(&vars).await?;
+        if changed {
+            info!("removed {key} from {:?}", self.path);
+        }
+        Ok(changed)
+    }
+
+    /// Replace the file contents with `vars`. Returns `true` if the file was changed.
+    pub async fn render(&mut self, vars: &BTreeMap<String, String>) -> Result<bool> {
+        let mut out = String::new();
+        writeln!(out, "# Managed by roguewave.")?;
+        for (key, value) in vars {
+            validate_env_name(key)?;
+            writeln!(out, "{key}={}", quote(value))?;
+        }
+        let mode = format!("{:o}", self.mode);
+        if !self.session.path_exists(&self.path).await? {
+            self.session
+                .command(["install", "-m", &mode, "/dev/null", &self.path])
+                .hide_command()
+                .run()
+                .await?;
+        }
+        let changed = self.session.update_file(&self.path, out).await?;
+        self.session
+            .command(["chmod", &mode, "--", &self.path])
+            .hide_command()
+            .run()
+            .await?;
+        Ok(changed)
+    }
+}
+
+fn quote(value: &str) -> String {
+    if !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-.,:/@%+".contains(c))
+    {
+        return value.into();
+    }
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' | '\\' | '$' | '`' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    let Some(inner) = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    else {
+        return value.into();
+    };
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some(c) => out.push(c),
+                None => out.push('\\'),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
@@ -0,0 +1,67 @@
+use anyhow::{bail, Result};
+use log::debug;
+
+use crate::Session;
+
+impl Session {
+    /// Manage `/etc` history with etckeeper.
+    pub fn etckeeper(&mut self) -> Etckeeper<'_> {
+        Etckeeper(self)
+    }
+}
+
+/// Provides access to etckeeper commands.
+///
+/// A typical usage is to call `commit` at the start of a run (to record manual changes made
+/// since the last run) and at the end of a run (to record changes made by the run).
+pub struct Etckeeper<'a>(&'a mut Session);
+
+impl Etckeeper<'_> {
+    /// Install etckeeper and initialize the repository in `/etc` if necessary.
+    pub async fn ensure_installed(&mut self) -> Result<()> {
+        self.0.apt().install(&["etckeeper"]).await?;
+        if !self.0.path_exists("/etc/.etckeeper").await? {
+            self.0.command(["etckeeper", "init"]).run().await?;
+            self.commit("initial commit").await?;
+        }
+        Ok(())
+    }
+
+    /// Check if `/etc` has uncommitted changes.
+    pub async fn has_changes(&mut self) -> Result<bool> {
+        let code = self
+            .0
+            .command(["etckeeper", "unclean"])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        match code {
+            0 => Ok(true),
+            1 => Ok(false),
+            _ => bail!("unexpected exit code"),
+        }
+    }
+
+    /// Commit changes in `/etc` with the specified message.
+    /// The current step (see `Session::start_step`) and the destination of the session
+    /// are appended to the message.
+    /// Returns `false` if there were no changes to commit.
+    pub async fn commit(&mut self, message: &str) -> Result<bool> {
+        if !self.has_changes().await? {
+            debug!("no changes in /etc to commit");
+            return Ok(false);
+        }
+        let mut message = format!("{message}\n");
+        if let Some(step) = &self.0.step {
+            message.push_str(&format!("\nStep: {step}"));
+        }
+        message.push_str(&format!("\nHost: {}", self.0.destination));
+        self.0
+            .command(["etckeeper", "commit", &message])
+            .hide_stdout()
+            .run()
+            .await?;
+        Ok(true)
+    }
+}
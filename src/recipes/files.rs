@@ -0,0 +1,202 @@
+use std::{
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+use log::{debug, info};
+use openssh_sftp_client::{error::SftpErrorKind, Error};
+
+use tokio::io::AsyncReadExt;
+
+use crate::Session;
+
+impl Session {
+    /// Read a remote file.
+    ///
+    /// Uses SFTP if it's available, otherwise `cat`.
+    pub async fn read_file(&mut self, path: impl AsRef<Path>) -> Result<Vec<u8>> {
+        let path = path.as_ref();
+        if self.has_sftp() {
+            return Ok(self.fs().read(path).await?.to_vec());
+        }
+        let mut child = self
+            .command(["cat", "--", path_str(path)?])
+            .hide_command()
+            .hide_stdout()
+            .spawn()
+            .await?;
+        let mut data = Vec::new();
+        child.stdout().read_to_end(&mut data).await?;
+        child
+            .wait()
+            .await
+            .with_context(|| format!("failed to read {path:?}"))?;
+        Ok(data)
+    }
+
+    /// Write `content` to a remote file, replacing it if it exists.
+    ///
+    /// Uses SFTP if it's available, otherwise `cat`.
+    pub async fn write_file(
+        &mut self,
+        path: impl AsRef<Path>,
+        content: impl AsRef<[u8]>,
+    ) -> Result<()> {
+        let path = path.as_ref();
+        if self.has_sftp() {
+            self.fs().write(path, content).await?;
+            return Ok(());
+        }
+        self.command(["sh", "-c", "cat > \"$1\"", "sh", path_str(path)?])
+            .hide_command()
+            .stdin(content.as_ref())
+            .run()
+            .await
+            .with_context(|| format!("failed to write {path:?}"))?;
+        Ok(())
+    }
+
+    /// Remove a remote file.
+    ///
+    /// Uses SFTP if it's available, otherwise `rm`.
+    pub async fn remove_file(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if self.has_sftp() {
+            self.fs().remove_file(path).await?;
+            return Ok(());
+        }
+        self.command(["rm", "--", path_str(path)?])
+            .hide_command()
+            .run()
+            .await
+            .with_context(|| format!("failed to remove {path:?}"))?;
+        Ok(())
+    }
+
+    /// Get the last modification time of a remote file.
+    ///
+    /// Uses SFTP if it's available, otherwise `stat`.
+    pub async fn modified_time(&mut self, path: impl AsRef<Path>) -> Result<SystemTime> {
+        let path = path.as_ref();
+        if self.has_sftp() {
+            let metadata = self.fs().metadata(path).await?;
+            return Ok(metadata
+                .modified()
+                .context("missing modification time")?
+                .as_system_time());
+        }
+        let output = self
+            .command(["stat", "--format=%Y", "--", path_str(path)?])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?;
+        let secs = output
+            .stdout_trimmed()
+            .parse()
+            .with_context(|| format!("unexpected stat output: {:?}", output.stdout))?;
+        Ok(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    /// Read a remote file as a string. Returns `None` if the file doesn't exist.
+    pub async fn read_file_if_exists(&mut self, path: impl AsRef<Path>) -> Result<Option<String>> {
+        let path = path.as_ref();
+        if !self.has_sftp() {
+            if !self.path_exists(path).await? {
+                return Ok(None);
+            }
+            return Ok(Some(String::from_utf8(self.read_file(path).await?)?));
+        }
+        match self.fs().read(path).await {
+            Ok(data) => Ok(Some(String::from_utf8(data.to_vec())?)),
+            Err(Error::SftpError(SftpErrorKind::NoSuchFile, _)) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Write `content` to a remote file unless the file already has the same content.
+    /// Returns `true` if the file was created or changed.
+    pub async fn update_file(
+        &mut self,
+        path: impl AsRef<Path>,
+        content: impl AsRef<[u8]>,
+    ) -> Result<bool> {
+        let path = path.as_ref();
+        let content = content.as_ref();
+        let current = if self.has_sftp() {
+            match self.fs().read(path).await {
+                Ok(current) => Some(current.to_vec()),
+                Err(Error::SftpError(SftpErrorKind::NoSuchFile, _)) => None,
+                Err(err) => return Err(err.into()),
+            }
+        } else if self.path_exists(path).await? {
+            Some(self.read_file(path).await?)
+        } else {
+            None
+        };
+        if current.as_deref() == Some(content) {
+            debug!("{path:?} is up to date");
+            return Ok(false);
+        }
+        self.write_file(path, content).await?;
+        self.stats.record_file_changed();
+        info!("updated {path:?}");
+        Ok(true)
+    }
+
+    /// Replace the block between `# BEGIN {marker}` and `# END {marker}` lines in a remote
+    /// file with `block`, keeping the rest of the file intact. The block is appended
+    /// if it's not present yet, and removed if `block` is empty. The file is created if it
+    /// doesn't exist. Returns `true` if the file was changed.
+    pub async fn update_managed_block(
+        &mut self,
+        path: impl AsRef<Path>,
+        marker: &str,
+        block: &str,
+    ) -> Result<bool> {
+        let path = path.as_ref();
+        let begin = format!("# BEGIN {marker}");
+        let end = format!("# END {marker}");
+        let current = self.read_file_if_exists(path).await?.unwrap_or_default();
+        let mut lines = Vec::new();
+        let mut inside = false;
+        let mut found = false;
+        for line in current.lines() {
+            if line == begin {
+                inside = true;
+                found = true;
+                if !block.is_empty() {
+                    lines.push(begin.clone());
+                    lines.extend(block.lines().map(String::from));
+                    lines.push(end.clone());
+                }
+            } else if line == end && inside {
+                inside = false;
+            } else if !inside {
+                lines.push(line.to_string());
+            }
+        }
+        if inside {
+            bail!("{path:?}: missing {end:?} line");
+        }
+        if !found && !block.is_empty() {
+            lines.push(begin);
+            lines.extend(block.lines().map(String::from));
+            lines.push(end);
+        }
+        let mut content = lines.join("\n");
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        if content == current {
+            debug!("{path:?} is up to date");
+            return Ok(false);
+        }
+        self.update_file(path, content).await
+    }
+}
+
+pub(crate) fn path_str(path: &Path) -> Result<&str> {
+    path.to_str().context("non-utf8 path")
+}
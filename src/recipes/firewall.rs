@@ -0,0 +1,225 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use log::{info, warn};
+
+use crate::{ScheduledJob, Session};
+
+const BACKUP_DIR: &str = "/var/lib/roguewave";
+
+/// IP version of an iptables ruleset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IptablesFamily {
+    /// IPv4 (`iptables`).
+    V4,
+    /// IPv6 (`ip6tables`).
+    V6,
+}
+
+impl IptablesFamily {
+    fn save_command(self) -> &'static str {
+        match self {
+            Self::V4 => "iptables-save",
+            Self::V6 => "ip6tables-save",
+        }
+    }
+
+    fn restore_command(self) -> &'static str {
+        match self {
+            Self::V4 => "iptables-restore",
+            Self::V6 => "ip6tables-restore",
+        }
+    }
+
+    fn suffix(self) -> &'static str {
+        match self {
+            Self::V4 => "v4",
+            Self::V6 => "v6",
+        }
+    }
+}
+
+impl Session {
+    /// Manage iptables firewall rules.
+    pub fn firewall(&mut self) -> Firewall<'_> {
+        Firewall(self)
+    }
+}
+
+/// Provides access to iptables firewall rules.
+pub struct Firewall<'a>(&'a mut Session);
+
+impl Firewall<'_> {
+    /// Fetch the current ruleset in `iptables-save` format.
+    pub async fn rules(&mut self, family: IptablesFamily) -> Result<String> {
+        saved_rules(self.0, family).await
+    }
+
+    /// Replace the ruleset with `rules` (in `iptables-save` format), restoring the previous
+    /// ruleset automatically after `confirm_timeout` unless the change is confirmed.
+    ///
+    /// Existing SSH connections usually survive a bad ruleset, so the change should be confirmed
+    /// over a new connection to prove that the host is still reachable:
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use roguewave::{IptablesFamily, Session};
+    /// # async fn f(session: &mut Session, rules: &str) -> anyhow::Result<()> {
+    /// let change = session
+    ///     .firewall()
+    ///     .apply_with_revert(IptablesFamily::V4, rules, Duration::from_secs(60))
+    ///     .await?;
+    /// let new_session = Session::connect("example.com").await?;
+    /// change.confirm(&new_session).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// The revert is scheduled with [`Session::schedule_once`], so it happens even if
+    /// the controller loses the connection.
+    pub async fn apply_with_revert(
+        &mut self,
+        family: IptablesFamily,
+        rules: &str,
+        confirm_timeout: Duration,
+    ) -> Result<PendingFirewallChange> {
+        let backup_path = format!("{BACKUP_DIR}/iptables-backup.{}", family.suffix());
+        self.0
+            .command(["mkdir", "-p", BACKUP_DIR])
+            .hide_command()
+            .run()
+            .await?;
+        let current = self.rules(family).await?;
+        self.0.update_file(&backup_path, current).await?;
+
+        let job = self
+            .0
+            .schedule_once(
+                &format!("firewall-revert-{}", family.suffix()),
+                confirm_timeout,
+                [family.restore_command(), &backup_path],
+            )
+            .await?;
+
+        let temp_path = self
+            .0
+            .command(["mktemp"])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?
+            .stdout_trimmed()
+            .to_string();
+        self.0.write_file(&temp_path, rules).await?;
+        let result = self
+            .0
+            .command([family.restore_command(), &temp_path])
+            .run()
+            .await;
+        self.0
+            .command(["rm", "-f", "--", &temp_path])
+            .hide_command()
+            .run()
+            .await?;
+        if let Err(err) = result {
+            job.cancel(self.0).await?;
+            return Err(err.context("failed to apply firewall rules"));
+        }
+        let applied = normalize_rules(&self.rules(family).await?);
+        warn!(
+            "new firewall rules will be reverted in {} s unless confirmed",
+            confirm_timeout.as_secs()
+        );
+        Ok(PendingFirewallChange {
+            family,
+            job,
+            backup_path,
+            applied,
+        })
+    }
+}
+
+/// Firewall rules that will be reverted unless confirmed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[must_use = "firewall rules are reverted unless the change is confirmed"]
+pub struct PendingFirewallChange {
+    family: IptablesFamily,
+    job: ScheduledJob,
+    backup_path: String,
+    /// Rules active after applying the change, without comments and counters.
+    applied: String,
+}
+
+impl PendingFirewallChange {
+    /// Cancel the scheduled revert. `session` should be a new connection to the host.
+    ///
+    /// Returns an error if the active rules differ from the applied ones,
+    /// e.g. because the revert has already run.
+    pub async fn confirm(self, session: &Session) -> Result<()> {
+        // The units are stopped without checking their state first, so that the revert
+        // can't start between the check and the stop. Stopping the service also interrupts
+        // a revert that has just started.
+        let unit = self.job.unit();
+        session
+            .command([
+                "systemctl",
+                "stop",
+                &format!("{unit}.timer"),
+                &format!("{unit}.service"),
+            ])
+            .hide_command()
+            .hide_all_output()
+            .allow_failure()
+            .run()
+            .await?;
+        let active = normalize_rules(&saved_rules(session, self.family).await?);
+        if active != self.applied {
+            bail!("firewall change was already reverted or the rules were modified");
+        }
+        session
+            .command(["rm", "-f", "--", &self.backup_path])
+            .hide_command()
+            .run()
+            .await?;
+        info!("confirmed firewall change");
+        Ok(())
+    }
+
+    /// Restore the previous rules immediately.
+    pub async fn revert(self, session: &Session) -> Result<()> {
+        self.job.cancel(session).await?;
+        session
+            .command([self.family.restore_command(), &self.backup_path])
+            .run()
+            .await?;
+        info!("reverted firewall change");
+        Ok(())
+    }
+}
+
+async fn saved_rules(session: &Session, family: IptablesFamily) -> Result<String> {
+    let output = session
+        .command([family.save_command()])
+        .hide_command()
+        .hide_all_output()
+        .run()
+        .await?;
+    Ok(output.stdout)
+}
+
+/// Remove comments (which contain timestamps) and packet counters from `iptables-save` output.
+fn normalize_rules(rules: &str) -> String {
+    let mut out = String::new();
+    for line in rules.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+        let line = match line.rsplit_once(" [") {
+            Some((chain, counters)) if line.starts_with(':') && counters.ends_with(']') => chain,
+            _ => line,
+        };
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
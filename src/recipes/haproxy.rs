@@ -0,0 +1,140 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+
+use crate::{command::shell_quote, Session, SessionFuture};
+
+const DEFAULT_SOCKET: &str = "/run/haproxy/admin.sock";
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Administrative state of an HAProxy server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ServerState {
+    /// The server receives traffic.
+    Ready,
+    /// The server doesn't receive new connections, existing connections are kept.
+    Drain,
+    /// The server is in maintenance mode and doesn't receive any traffic.
+    Maint,
+}
+
+impl Session {
+    /// Manage HAProxy through its admin socket (`/run/haproxy/admin.sock` by default).
+    ///
+    /// Requires `socat` on the remote host and a `stats socket ... level admin` line
+    /// in the HAProxy config.
+    pub fn haproxy(&mut self) -> Haproxy<'_> {
+        Haproxy {
+            session: self,
+            socket: DEFAULT_SOCKET.into(),
+        }
+    }
+}
+
+/// Provides access to the HAProxy runtime API.
+pub struct Haproxy<'a> {
+    session: &'a mut Session,
+    socket: String,
+}
+
+impl Haproxy<'_> {
+    /// Use another admin socket path.
+    pub fn socket(mut self, path: &str) -> Self {
+        self.socket = path.into();
+        self
+    }
+
+    /// Send a command to the admin socket and return the response.
+    pub async fn admin_command(&mut self, command: &str) -> Result<String> {
+        let output = self
+            .session
+            .command([
+                "sh",
+                "-c",
+                &format!(
+                    "echo {} | socat stdio {}",
+                    shell_quote(command),
+                    shell_quote(&self.socket)
+                ),
+            ])
+            .hide_command()
+            .hide_stdout()
+            .run()
+            .await?;
+        Ok(output.stdout)
+    }
+
+    /// Change the administrative state of `backend/server`.
+    pub async fn set_server_state(
+        &mut self,
+        backend: &str,
+        server: &str,
+        state: ServerState,
+    ) -> Result<()> {
+        let state_name = match state {
+            ServerState::Ready => "ready",
+            ServerState::Drain => "drain",
+            ServerState::Maint => "maint",
+        };
+        let response = self
+            .admin_command(&format!("set server {backend}/{server} state {state_name}"))
+            .await?;
+        if !response.trim().is_empty() {
+            bail!("haproxy: {}", response.trim());
+        }
+        info!("haproxy: {backend}/{server} is now in {state_name} state");
+        Ok(())
+    }
+
+    /// Fetch the number of current sessions of `backend/server` (from `show stat`).
+    pub async fn current_sessions(&mut self, backend: &str, server: &str) -> Result<u64> {
+        let stat = self.admin_command("show stat").await?;
+        // CSV columns: pxname,svname,qcur,qmax,scur,...
+        for line in stat.lines() {
+            let fields: Vec<_> = line.split(',').collect();
+            if fields.len() > 4 && fields[0] == backend && fields[1] == server {
+                return fields[4]
+                    .parse()
+                    .context("failed to parse haproxy session count");
+            }
+        }
+        bail!("server {backend}/{server} not found in haproxy stats");
+    }
+
+    /// Take `backend/server` out of rotation, run `deploy` on `backend_session`,
+    /// and put the server back into rotation.
+    ///
+    /// The server is put into drain state and current sessions are allowed to finish
+    /// for up to `drain_timeout`. If sessions don't finish in time, the deployment proceeds
+    /// anyway. If `deploy` fails, the server is left in drain state and the error is returned.
+    pub async fn drain_and_run<T>(
+        &mut self,
+        backend: &str,
+        server: &str,
+        drain_timeout: Duration,
+        backend_session: &mut Session,
+        deploy: impl for<'s> FnOnce(&'s mut Session) -> SessionFuture<'s, T>,
+    ) -> Result<T> {
+        self.set_server_state(backend, server, ServerState::Drain)
+            .await?;
+        let started = Instant::now();
+        loop {
+            let sessions = self.current_sessions(backend, server).await?;
+            if sessions == 0 {
+                break;
+            }
+            if started.elapsed() >= drain_timeout {
+                warn!("haproxy: {backend}/{server} still has {sessions} sessions, proceeding");
+                break;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        let value = deploy(backend_session).await.with_context(|| {
+            format!("deployment failed, {backend}/{server} is left in drain state")
+        })?;
+        self.set_server_state(backend, server, ServerState::Ready)
+            .await?;
+        Ok(value)
+    }
+}
@@ -0,0 +1,400 @@
+use std::{
+    fmt::Write,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use log::info;
+
+use crate::{Distro, Session};
+
+const JAVA_HOME_CONFIG_PATH: &str = "/etc/environment.d/50-java.conf";
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Where to install a JDK from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JdkSource {
+    /// OpenJDK packages from the distribution repositories.
+    Distro,
+    /// Eclipse Temurin packages from the Adoptium apt repository.
+    TemurinRepository,
+    /// Eclipse Temurin tarball from the Adoptium API, extracted to `/opt/java/temurin-{version}`.
+    TemurinTarball,
+}
+
+/// A Java application running from a jar file as a systemd service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JavaService {
+    /// Service name. The unit is written to `/etc/systemd/system/{name}.service`.
+    pub name: String,
+    /// Remote path of the jar file. Defaults to `/opt/{name}/{name}.jar`.
+    pub jar_path: String,
+    /// JDK directory returned by `Java::install_jdk`.
+    pub java_home: String,
+    /// User the service runs as. Created as a system user if it doesn't exist.
+    /// Defaults to the service name.
+    pub user: String,
+    /// Initial and maximum heap size (e.g. `512m`), passed as `-Xms` and `-Xmx`.
+    pub heap_size: Option<String>,
+    /// Hard memory limit of the service (e.g. `1G`), enforced by systemd's `MemoryMax`.
+    pub memory_max: Option<String>,
+    /// Additional JVM options, e.g. `-XX:+UseG1GC`.
+    pub jvm_options: Vec<String>,
+    /// Arguments passed to the application.
+    pub args: Vec<String>,
+    /// Environment variables of the service.
+    pub environment: Vec<(String, String)>,
+}
+
+impl JavaService {
+    /// Create a service description with default settings.
+    pub fn new(name: impl Into<String>, java_home: impl Into<String>) -> Self {
+        let name = name.into();
+        Self {
+            jar_path: format!("/opt/{name}/{name}.jar"),
+            java_home: java_home.into(),
+            user: name.clone(),
+            heap_size: None,
+            memory_max: None,
+            jvm_options: Vec::new(),
+            args: Vec::new(),
+            environment: Vec::new(),
+            name,
+        }
+    }
+
+    /// Render the systemd unit.
+    pub fn render(&self) -> Result<String> {
+        validate_name(&self.name)?;
+        let mut exec = vec![format!("{}/bin/java", self.java_home)];
+        if let Some(size) = &self.heap_size {
+            if size.is_empty() || !size.chars().all(|c| c.is_ascii_alphanumeric()) {
+                bail!("invalid heap size: {size:?}");
+            }
+            exec.push(format!("-Xms{size}"));
+            exec.push(format!("-Xmx{size}"));
+        }
+        exec.extend(self.jvm_options.iter().cloned());
+        exec.push("-jar".into());
+        exec.push(self.jar_path.clone());
+        exec.extend(self.args.iter().cloned());
+        let working_directory = Path::new(&self.jar_path)
+            .parent()
+            .and_then(|p| p.to_str())
+            .filter(|p| !p.is_empty())
+            .context("jar path must be absolute")?;
+
+        let mut out = String::new();
+        writeln!(out, "# Managed by roguewave.")?;
+        writeln!(out, "[Unit]")?;
+        writeln!(out, "Description={}", self.name)?;
+        writeln!(out, "After=network-online.target")?;
+        writeln!(out, "Wants=network-online.target")?;
+        writeln!(out)?;
+        writeln!(out, "[Service]")?;
+        writeln!(out, "User={}", self.user)?;
+        writeln!(out, "WorkingDirectory={working_directory}")?;
+        writeln!(
+            out,
+            "Environment={}",
+            systemd_quote(&format!("JAVA_HOME={}", self.java_home))
+        )?;
+        for (key, value) in &self.environment {
+            writeln!(
+                out,
+                "Environment={}",
+                systemd_quote(&format!("{key}={value}"))
+            )?;
+        }
+        let exec: Vec<String> = exec.iter().map(|arg| systemd_quote(arg)).collect();
+        writeln!(out, "ExecStart={}", exec.join(" "))?;
+        // The JVM exits with 143 after receiving SIGTERM.
+        writeln!(out, "SuccessExitStatus=143")?;
+        writeln!(out, "Restart=on-failure")?;
+        writeln!(out, "RestartSec=5")?;
+        if let Some(memory_max) = &self.memory_max {
+            writeln!(out, "MemoryMax={memory_max}")?;
+        }
+        writeln!(out)?;
+        writeln!(out, "[Install]")?;
+        writeln!(out, "WantedBy=multi-user.target")?;
+        Ok(out)
+    }
+}
+
+impl Session {
+    /// Install JDKs and run Java applications.
+    pub fn java(&mut self) -> Java<'_> {
+        Java(self)
+    }
+}
+
+/// Provides access to JDK installation and Java services.
+pub struct Java<'a>(&'a mut Session);
+
+impl Java<'_> {
+    /// Install JDK `version` (e.g. `21`) from `source` unless it's already installed.
+    /// Returns the JDK directory that can be used as `JAVA_HOME`.
+    pub async fn install_jdk(&mut self, source: JdkSource, version: u32) -> Result<String> {
+        match source {
+            JdkSource::Distro => {
+                self.0
+                    .require_os_for("OpenJDK installation", &[Distro::Ubuntu, Distro::Debian])
+                    .await?;
+                let package = format!("openjdk-{version}-jdk-headless");
+                self.0.apt().install(&[&package]).await?;
+                // `bin/java` is provided by the JRE package the JDK depends on.
+                self.package_java_home(&format!("openjdk-{version}-jre-headless"))
+                    .await
+            }
+            JdkSource::TemurinRepository => {
+                let package = format!("temurin-{version}-jdk");
+                if !self.0.apt().is_package_installed(&package).await? {
+                    let os = self.0.os_release().await?;
+                    let codename = match (&os.distro, os.version_codename.as_deref()) {
+                        (Distro::Ubuntu | Distro::Debian, Some(codename)) => codename.to_string(),
+                        _ => bail!(
+                            "unsupported OS for Temurin installation: {}",
+                            os.pretty_name
+                        ),
+                    };
+                    self.0
+                        .apt()
+                        .add_repository(
+                            "adoptium",
+                            "https://packages.adoptium.net/artifactory/api/gpg/key/public",
+                            &format!(
+                                "https://packages.adoptium.net/artifactory/deb {codename} main"
+                            ),
+                        )
+                        .await?;
+                    self.0.apt().install(&[&package]).await?;
+                }
+                self.package_java_home(&package).await
+            }
+            JdkSource::TemurinTarball => self.install_temurin_tarball(version).await,
+        }
+    }
+
+    async fn package_java_home(&mut self, package: &str) -> Result<String> {
+        let output = self
+            .0
+            .command(["dpkg-query", "--listfiles", package])
+            .hide_command()
+            .hide_stdout()
+            .run()
+            .await?;
+        output
+            .stdout
+            .lines()
+            .find_map(|line| line.trim().strip_suffix("/bin/java"))
+            .map(Into::into)
+            .with_context(|| format!("java binary not found in package {package:?}"))
+    }
+
+    async fn install_temurin_tarball(&mut self, version: u32) -> Result<String> {
+        let java_home = format!("/opt/java/temurin-{version}");
+        if self.0.path_exists(format!("{java_home}/bin/java")).await? {
+            return Ok(java_home);
+        }
+        let machine = self
+            .0
+            .command(["uname", "-m"])
+            .hide_command()
+            .hide_stdout()
+            .run()
+            .await?
+            .stdout;
+        let arch = match machine.trim() {
+            "x86_64" => "x64",
+            "aarch64" => "aarch64",
+            other => bail!("unsupported architecture for Temurin: {other:?}"),
+        };
+        self.0.apt().install(&["curl"]).await?;
+        let url = format!(
+            "https://api.adoptium.net/v3/binary/latest/{version}/ga/linux/{arch}/jdk/hotspot/normal/eclipse"
+        );
+        // Extract to a temporary directory first so that an interrupted download doesn't
+        // leave a half-installed JDK behind.
+        let temp_dir = format!("{java_home}.roguewave-tmp");
+        self.0
+            .command([
+                "bash",
+                "-c",
+                r#"set -o pipefail; rm -rf -- "$2" && mkdir -p -- "$2" && curl -fsSL "$1" | tar -xz --strip-components=1 -C "$2" && mv -- "$2" "$3""#,
+                "--",
+                &url,
+                &temp_dir,
+                &java_home,
+            ])
+            .run()
+            .await?;
+        info!("installed Temurin {version} to {java_home}");
+        Ok(java_home)
+    }
+
+    /// Set `JAVA_HOME` in `/etc/environment.d`, which applies to user sessions started
+    /// by systemd. Returns `true` if the config was changed.
+    pub async fn set_java_home(&mut self, java_home: &str) -> Result<bool> {
+        if java_home.contains(['"', '\n']) {
+            bail!("invalid JAVA_HOME: {java_home:?}");
+        }
+        self.0
+            .command(["mkdir", "-p", "/etc/environment.d"])
+            .hide_command()
+            .run()
+            .await?;
+        self.0
+            .update_file(
+                JAVA_HOME_CONFIG_PATH,
+                format!("# Managed by roguewave.\nJAVA_HOME=\"{java_home}\"\n"),
+            )
+            .await
+    }
+
+    /// Upload `local_jar` to `service.jar_path`, install the systemd unit and make sure
+    /// the service is enabled and running. The service is restarted if the jar or the
+    /// unit was changed. Returns `true` if anything was changed.
+    pub async fn deploy(
+        &mut self,
+        service: &JavaService,
+        local_jar: impl AsRef<Path>,
+    ) -> Result<bool> {
+        let unit = service.render()?;
+        if !self.0.user_exists(&service.user).await? {
+            self.0
+                .command([
+                    "useradd",
+                    "--system",
+                    "--no-create-home",
+                    "--shell",
+                    "/usr/sbin/nologin",
+                    &service.user,
+                ])
+                .run()
+                .await?;
+            info!("created user {:?}", service.user);
+        }
+        let dir = Path::new(&service.jar_path)
+            .parent()
+            .and_then(|p| p.to_str())
+            .context("jar path must be absolute")?;
+        self.0
+            .command(["mkdir", "-p", "--", dir])
+            .hide_command()
+            .run()
+            .await?;
+        let jar_changed = self.0.upload_artifact(local_jar, &service.jar_path).await?;
+        let unit_changed = self
+            .0
+            .update_file(
+                format!("/etc/systemd/system/{}.service", service.name),
+                unit,
+            )
+            .await?;
+        if unit_changed {
+            self.0
+                .command(["systemctl", "daemon-reload"])
+                .hide_command()
+                .run()
+                .await?;
+        }
+        self.0
+            .command(["systemctl", "enable", &service.name])
+            .run()
+            .await?;
+        let action = if jar_changed || unit_changed {
+            "restart"
+        } else {
+            "start"
+        };
+        self.0
+            .command(["systemctl", action, &service.name])
+            .run()
+            .await?;
+        Ok(jar_changed || unit_changed)
+    }
+
+    /// Check if the service is running.
+    pub async fn is_active(&mut self, name: &str) -> Result<bool> {
+        validate_name(name)?;
+        let code = self
+            .0
+            .command(["systemctl", "is-active", "--quiet", name])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        Ok(code == 0)
+    }
+
+    /// Wait until the service is running and `url` (e.g. `http://localhost:8080/health`)
+    /// responds with a successful status, requested with `curl` on the remote host.
+    /// Fails if the service stops or `timeout` is exceeded.
+    pub async fn wait_healthy(&mut self, name: &str, url: &str, timeout: Duration) -> Result<()> {
+        let started = Instant::now();
+        let mut logged_wait = false;
+        loop {
+            if !self.is_active(name).await? {
+                let code = self
+                    .0
+                    .command(["systemctl", "is-failed", "--quiet", name])
+                    .hide_command()
+                    .hide_all_output()
+                    .exit_code()
+                    .await?;
+                if code == 0 {
+                    bail!("service {name:?} failed");
+                }
+            } else {
+                let code = self
+                    .0
+                    .command(["curl", "--silent", "--fail", "--output", "/dev/null", url])
+                    .hide_command()
+                    .hide_all_output()
+                    .exit_code()
+                    .await?;
+                if code == 0 {
+                    return Ok(());
+                }
+            }
+            if started.elapsed() >= timeout {
+                bail!("timed out waiting for {name:?} to become healthy");
+            }
+            if !logged_wait {
+                info!("waiting for {name:?} to become healthy");
+                logged_wait = true;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-')
+    {
+        bail!("invalid service name: {name:?}");
+    }
+    Ok(())
+}
+
+/// Quote a word for `ExecStart=` and `Environment=` lines.
+fn systemd_quote(value: &str) -> String {
+    if !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=,+@".contains(c))
+    {
+        return value.into();
+    }
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+    format!("\"{escaped}\"")
+}
@@ -0,0 +1,218 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use log::info;
+
+use crate::{command::shell_quote, Session};
+
+const JOBS_DIR: &str = "/var/lib/roguewave/jobs";
+
+/// Script that runs a job and records its PID, output and exit code.
+/// Arguments: job directory, then the command.
+const RUNNER_SCRIPT: &str = r#"dir="$1"; shift
+echo $$ > "$dir/pid"
+"$@" > "$dir/output" 2>&1 < /dev/null
+echo $? > "$dir/exit_code.tmp" && mv "$dir/exit_code.tmp" "$dir/exit_code""#;
+
+/// State of a job started with [`Jobs::start`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JobState {
+    /// The command is still running.
+    Running,
+    /// The command has exited.
+    Finished {
+        /// Exit code of the command.
+        exit_code: i32,
+    },
+    /// The command is not running, but didn't record an exit code
+    /// (e.g. it was killed or the host was rebooted).
+    Lost,
+}
+
+/// Status and output of a job.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JobStatus {
+    /// Job ID.
+    pub id: String,
+    /// Current state.
+    pub state: JobState,
+    /// Combined stdout and stderr of the command produced so far.
+    pub output: String,
+}
+
+impl Session {
+    /// Manage long-running jobs that keep running on the remote host after the session
+    /// is closed. A job can be started in one run and attached to from another one.
+    pub fn jobs(&mut self) -> Jobs<'_> {
+        Jobs(self)
+    }
+}
+
+/// Provides access to the job registry of the remote host.
+///
+/// Jobs are stored in `/var/lib/roguewave/jobs/{id}`.
+pub struct Jobs<'a>(&'a mut Session);
+
+impl Jobs<'_> {
+    /// Start `command` in the background as job `id` and return immediately.
+    /// Fails if a job with the same ID is still running; a finished job with the same ID
+    /// is replaced.
+    pub async fn start<S: AsRef<str>, I: IntoIterator<Item = S>>(
+        &mut self,
+        id: &str,
+        command: I,
+    ) -> Result<()> {
+        let dir = job_dir(id)?;
+        if self.0.path_exists(&dir).await? && self.state(id).await? == JobState::Running {
+            bail!("job {id:?} is already running");
+        }
+        let command: Vec<String> = command
+            .into_iter()
+            .map(|arg| shell_quote(arg.as_ref()))
+            .collect();
+        if command.is_empty() {
+            bail!("cannot run empty command");
+        }
+        self.0
+            .command([
+                "bash",
+                "-c",
+                &format!(
+                    "rm -rf {dir} && mkdir -p {dir} && \
+                    nohup setsid sh -c {} sh {dir} {} > /dev/null 2>&1 < /dev/null &",
+                    shell_quote(RUNNER_SCRIPT),
+                    command.join(" "),
+                ),
+            ])
+            .run()
+            .await?;
+        info!("started job {id:?}");
+        Ok(())
+    }
+
+    /// Fetch the state and output of job `id`. Fails if the job doesn't exist.
+    pub async fn attach(&mut self, id: &str) -> Result<JobStatus> {
+        let state = self.state(id).await?;
+        let output = self
+            .0
+            .read_file_if_exists(format!("{}/output", job_dir(id)?))
+            .await?
+            .unwrap_or_default();
+        Ok(JobStatus {
+            id: id.into(),
+            state,
+            output,
+        })
+    }
+
+    /// Wait until job `id` is no longer running, checking every `poll_interval`,
+    /// and return its final status.
+    pub async fn wait(&mut self, id: &str, poll_interval: Duration) -> Result<JobStatus> {
+        while self.state(id).await? == JobState::Running {
+            tokio::time::sleep(poll_interval).await;
+        }
+        self.attach(id).await
+    }
+
+    /// IDs of all jobs on the host.
+    pub async fn list(&mut self) -> Result<Vec<String>> {
+        let output = self
+            .0
+            .command([
+                "find",
+                JOBS_DIR,
+                "-mindepth",
+                "1",
+                "-maxdepth",
+                "1",
+                "-type",
+                "d",
+                "-printf",
+                "%f\\n",
+            ])
+            .hide_command()
+            .hide_all_output()
+            .allow_failure()
+            .run()
+            .await?;
+        let mut ids: Vec<String> = output.stdout_lines().map(String::from).collect();
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Kill job `id` if it's running.
+    pub async fn kill(&mut self, id: &str) -> Result<()> {
+        if self.state(id).await? != JobState::Running {
+            return Ok(());
+        }
+        let dir = job_dir(id)?;
+        // The job runs in its own session, so the whole process group is killed.
+        self.0
+            .command(["bash", "-c", &format!("kill -- -$(cat {dir}/pid)")])
+            .run()
+            .await?;
+        info!("killed job {id:?}");
+        Ok(())
+    }
+
+    /// Delete job `id` and its output. Fails if the job is still running.
+    pub async fn remove(&mut self, id: &str) -> Result<()> {
+        let dir = job_dir(id)?;
+        if !self.0.path_exists(&dir).await? {
+            return Ok(());
+        }
+        if self.state(id).await? == JobState::Running {
+            bail!("job {id:?} is still running");
+        }
+        self.0
+            .command(["rm", "-rf", "--", &dir])
+            .hide_command()
+            .run()
+            .await?;
+        Ok(())
+    }
+
+    async fn state(&mut self, id: &str) -> Result<JobState> {
+        let dir = job_dir(id)?;
+        if !self.0.path_exists(&dir).await? {
+            bail!("job {id:?} not found");
+        }
+        if let Some(code) = self
+            .0
+            .read_file_if_exists(format!("{dir}/exit_code"))
+            .await?
+        {
+            return Ok(JobState::Finished {
+                exit_code: code.trim().parse()?,
+            });
+        }
+        let Some(pid) = self.0.read_file_if_exists(format!("{dir}/pid")).await? else {
+            // The runner hasn't written its PID yet.
+            return Ok(JobState::Running);
+        };
+        let code = self
+            .0
+            .command(["kill", "-0", pid.trim()])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        Ok(if code == 0 {
+            JobState::Running
+        } else {
+            JobState::Lost
+        })
+    }
+}
+
+fn job_dir(id: &str) -> Result<String> {
+    if id.is_empty()
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+        || id.starts_with('.')
+    {
+        bail!("invalid job ID: {id:?}");
+    }
+    Ok(format!("{JOBS_DIR}/{id}"))
+}
@@ -0,0 +1,219 @@
+use std::{
+    env, fs, process,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Result};
+use log::{info, warn};
+use tokio::runtime::Handle;
+
+use crate::Session;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+impl Session {
+    /// Prepare a named lock on the remote host.
+    ///
+    /// Locks prevent concurrent execution of the same script (e.g. two deployments
+    /// started by different people). The lock is implemented as a directory
+    /// `/tmp/roguewave-lock-{name}` which is created atomically. The lock records
+    /// the local host, PID and time of acquisition of its owner.
+    pub fn lock(&mut self, name: &str) -> RemoteLock<'_> {
+        RemoteLock {
+            session: self,
+            name: name.into(),
+            stale_after: None,
+        }
+    }
+}
+
+/// A named lock on the remote host.
+pub struct RemoteLock<'a> {
+    session: &'a mut Session,
+    name: String,
+    stale_after: Option<Duration>,
+}
+
+impl RemoteLock<'_> {
+    /// Break the lock if it has been held for longer than `age`, e.g. because the run
+    /// that acquired it crashed. By default, the lock is never broken.
+    ///
+    /// The age is measured with the remote clock, so `age` should be well above
+    /// the longest expected run.
+    pub fn stale_after(mut self, age: Duration) -> Self {
+        self.stale_after = Some(age);
+        self
+    }
+
+    /// Acquire the lock, waiting for up to `timeout` if it's held by someone else.
+    pub async fn acquire(mut self, timeout: Duration) -> Result<RemoteLockGuard> {
+        if !self
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-')
+        {
+            bail!("invalid lock name: {:?}", self.name);
+        }
+        let path = format!("/tmp/roguewave-lock-{}", self.name);
+        let owner_path = format!("{path}/owner");
+        let started = Instant::now();
+        let mut logged_wait = false;
+        loop {
+            let code = self
+                .session
+                .command(["mkdir", &path])
+                .hide_command()
+                .hide_all_output()
+                .exit_code()
+                .await?;
+            if code == 0 {
+                let host = fs::read_to_string("/etc/hostname")
+                    .map(|host| host.trim().to_string())
+                    .or_else(|_| env::var("HOSTNAME"))
+                    .unwrap_or_else(|_| "unknown host".into());
+                let owner = format!(
+                    "pid {} on {host} (local user {:?}) since {}\n",
+                    process::id(),
+                    env::var("USER").unwrap_or_default(),
+                    SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+                );
+                if let Err(err) = self.session.write_file(&owner_path, owner).await {
+                    // Don't leave behind a lock that nobody holds.
+                    self.remove(&path).await?;
+                    return Err(err.context(format!("failed to write owner of lock {path:?}")));
+                }
+                info!("acquired lock {:?}", self.name);
+                return Ok(RemoteLockGuard {
+                    session: Some(self.session.inner.clone()),
+                    name: self.name,
+                    path,
+                });
+            }
+            if !self.session.path_exists(&path).await? {
+                bail!("failed to create lock directory {path:?}");
+            }
+            let owner_data = self
+                .session
+                .read_file(&owner_path)
+                .await
+                .map(|data| String::from_utf8_lossy(&data).trim().to_string())
+                .ok();
+            let owner = owner_data.as_deref().unwrap_or("unknown owner").to_string();
+            if let Some(stale_after) = self.stale_after {
+                if self
+                    .break_if_stale(&path, stale_after, owner_data.as_deref().unwrap_or(""))
+                    .await?
+                {
+                    warn!("broke stale lock {:?} held by {owner}", self.name);
+                    continue;
+                }
+            }
+            if started.elapsed() >= timeout {
+                bail!("timed out waiting for lock {:?} held by {owner}", self.name);
+            }
+            if !logged_wait {
+                info!("waiting for lock {:?} held by {owner}", self.name);
+                logged_wait = true;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Remove the lock if it's older than `stale_after` and is still held by `owner`.
+    /// Returns `true` if the lock was removed.
+    ///
+    /// The check and the removal are done in one remote script, and the lock directory
+    /// is renamed to a unique name before removing it, so that concurrent waiters can't
+    /// remove a lock acquired by someone else in the meantime.
+    async fn break_if_stale(
+        &mut self,
+        path: &str,
+        stale_after: Duration,
+        owner: &str,
+    ) -> Result<bool> {
+        let code = self
+            .session
+            .command([
+                "sh",
+                "-c",
+                r#"modified=$(stat --format=%Y -- "$1") || exit 1
+[ $(( $(date +%s) - modified )) -ge "$2" ] || exit 1
+[ "$(cat -- "$1/owner" 2>/dev/null)" = "$3" ] || exit 1
+stale=$(mktemp --dry-run -- "$1.stale.XXXXXXXX") || exit 1
+mv --no-target-directory -- "$1" "$stale" 2>/dev/null || exit 1
+rm --recursive --force -- "$stale""#,
+                "sh",
+                path,
+                &stale_after.as_secs().to_string(),
+                owner,
+            ])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        Ok(code == 0)
+    }
+
+    async fn remove(&mut self, path: &str) -> Result<()> {
+        self.session
+            .command(["rm", "--recursive", "--force", path])
+            .hide_command()
+            .run()
+            .await?;
+        Ok(())
+    }
+}
+
+/// A held remote lock.
+///
+/// Call `release` to release the lock. If the guard is dropped without calling `release`,
+/// the lock is released in a background task on a best-effort basis.
+#[derive(Debug)]
+pub struct RemoteLockGuard {
+    session: Option<Arc<openssh::Session>>,
+    name: String,
+    path: String,
+}
+
+impl RemoteLockGuard {
+    /// Release the lock.
+    pub async fn release(mut self) -> Result<()> {
+        let Some(session) = self.session.take() else {
+            return Ok(());
+        };
+        let status = session
+            .command("rm")
+            .args(["--recursive", "--force", &self.path])
+            .status()
+            .await?;
+        if !status.success() {
+            bail!("failed to release lock {:?}", self.name);
+        }
+        info!("released lock {:?}", self.name);
+        Ok(())
+    }
+}
+
+impl Drop for RemoteLockGuard {
+    fn drop(&mut self) {
+        let Some(session) = self.session.take() else {
+            return;
+        };
+        let Ok(handle) = Handle::try_current() else {
+            warn!("lock {:?} was not released", self.name);
+            return;
+        };
+        let path = self.path.clone();
+        handle.spawn(async move {
+            let result = session
+                .command("rm")
+                .args(["--recursive", "--force", &path])
+                .status()
+                .await;
+            if let Err(err) = result {
+                warn!("failed to release lock at {path:?}: {err}");
+            }
+        });
+    }
+}
@@ -0,0 +1,119 @@
+use std::{future::Future, pin::Pin, sync::Arc};
+
+use anyhow::{Context, Result};
+use log::{error, info, warn};
+use tokio::runtime::Handle;
+
+use crate::{on_interrupt, runner::RunState, ForkParts, Session};
+
+/// A boxed future borrowing a session, returned by closures passed to session helpers.
+pub type SessionFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+type DisableFn = dyn for<'s> Fn(&'s mut Session) -> SessionFuture<'s, ()> + Send + Sync;
+
+impl Session {
+    /// Run `body` while the service is in maintenance mode.
+    ///
+    /// `enable` is called before `body` and `disable` is called after `body`, regardless of
+    /// whether `body` succeeded. If `enable` fails, `disable` is called to restore the service
+    /// and `body` is not executed.
+    ///
+    /// ```no_run
+    /// # use roguewave::Session;
+    /// # async fn f(session: &mut Session) -> anyhow::Result<()> {
+    /// session
+    ///     .with_maintenance(
+    ///         |s| Box::pin(async move {
+    ///             s.command(["touch", "/var/www/maintenance.flag"]).run().await?;
+    ///             Ok(())
+    ///         }),
+    ///         |s| Box::pin(async move {
+    ///             s.command(["rm", "-f", "/var/www/maintenance.flag"]).run().await?;
+    ///             Ok(())
+    ///         }),
+    ///         |s| Box::pin(async move {
+    ///             s.command(["systemctl", "restart", "app"]).run().await?;
+    ///             Ok(())
+    ///         }),
+    ///     )
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// If the returned future is dropped before completion (e.g. the task is cancelled),
+    /// `disable` is executed in a background task with a session forked from this session.
+    /// Inside `roguewave::run`, an interruption with Ctrl-C waits for it to finish.
+    /// Awaiting the returned future to completion is still the preferred way
+    /// to restore the service, because errors of the background task are only logged.
+    pub async fn with_maintenance<T>(
+        &mut self,
+        enable: impl for<'s> FnOnce(&'s mut Session) -> SessionFuture<'s, ()>,
+        disable: impl for<'s> Fn(&'s mut Session) -> SessionFuture<'s, ()> + Send + Sync + 'static,
+        body: impl for<'s> FnOnce(&'s mut Session) -> SessionFuture<'s, T>,
+    ) -> Result<T> {
+        let disable: Arc<DisableFn> = Arc::new(disable);
+        let mut guard = MaintenanceGuard {
+            restore: Some((self.fork_parts(), disable.clone())),
+        };
+        info!("enabling maintenance mode");
+        if let Err(err) = enable(self).await {
+            if let Err(disable_err) = (*disable)(self).await {
+                error!("failed to disable maintenance mode: {disable_err:?}");
+            }
+            guard.restore = None;
+            return Err(err.context("failed to enable maintenance mode"));
+        }
+        let result = body(self).await;
+        info!("disabling maintenance mode");
+        let disable_result = (*disable)(self).await;
+        guard.restore = None;
+        match result {
+            Ok(value) => {
+                disable_result.context("failed to disable maintenance mode")?;
+                Ok(value)
+            }
+            Err(err) => {
+                if let Err(disable_err) = disable_result {
+                    error!("failed to disable maintenance mode: {disable_err:?}");
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Disables maintenance mode if `with_maintenance` is cancelled.
+struct MaintenanceGuard {
+    restore: Option<(ForkParts, Arc<DisableFn>)>,
+}
+
+impl Drop for MaintenanceGuard {
+    fn drop(&mut self) {
+        let Some((parts, disable)) = self.restore.take() else {
+            return;
+        };
+        let Ok(handle) = Handle::try_current() else {
+            warn!("maintenance mode may still be enabled: operation was cancelled");
+            return;
+        };
+        warn!("operation was cancelled, disabling maintenance mode in the background");
+        let task = handle.spawn(async move {
+            let result = async {
+                let mut session = parts.connect().await?;
+                (*disable)(&mut session).await
+            }
+            .await;
+            match &result {
+                Ok(()) => info!("maintenance mode disabled"),
+                Err(err) => error!("failed to disable maintenance mode: {err:?}"),
+            }
+            result
+        });
+        if RunState::current().is_some() {
+            on_interrupt("disable maintenance mode", move || {
+                Box::pin(async move { task.await? })
+            });
+        }
+    }
+}
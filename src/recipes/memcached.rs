@@ -0,0 +1,110 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use anyhow::{bail, Result};
+
+use crate::Session;
+
+const CONFIG_PATH: &str = "/etc/memcached.conf";
+
+/// Settings written to `/etc/memcached.conf`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemcachedConfig {
+    /// Memory limit in megabytes.
+    pub memory_mb: u32,
+    /// TCP port.
+    pub port: u16,
+    /// Addresses to listen on.
+    pub listen: Vec<String>,
+    /// Maximum number of simultaneous connections.
+    pub max_connections: u32,
+}
+
+impl Default for MemcachedConfig {
+    fn default() -> Self {
+        Self {
+            memory_mb: 64,
+            port: 11211,
+            listen: vec!["127.0.0.1".into()],
+            max_connections: 1024,
+        }
+    }
+}
+
+impl MemcachedConfig {
+    /// Render the config file.
+    pub fn render(&self) -> Result<String> {
+        let mut out = String::new();
+        writeln!(out, "# Managed by roguewave.")?;
+        writeln!(out, "-d")?;
+        writeln!(out, "logfile /var/log/memcached.log")?;
+        writeln!(out, "-m {}", self.memory_mb)?;
+        writeln!(out, "-p {}", self.port)?;
+        writeln!(out, "-u memcache")?;
+        writeln!(out, "-l {}", self.listen.join(","))?;
+        writeln!(out, "-c {}", self.max_connections)?;
+        writeln!(out, "-P /var/run/memcached/memcached.pid")?;
+        Ok(out)
+    }
+}
+
+impl Session {
+    /// Manage memcached.
+    pub fn memcached(&mut self) -> Memcached<'_> {
+        Memcached(self)
+    }
+}
+
+/// Provides access to memcached management.
+pub struct Memcached<'a>(&'a mut Session);
+
+impl Memcached<'_> {
+    /// Install memcached.
+    pub async fn ensure_installed(&mut self) -> Result<()> {
+        self.0.apt().install(&["memcached"]).await
+    }
+
+    /// Write `/etc/memcached.conf` and restart the service if the config was changed.
+    /// Returns `true` if the config was changed.
+    pub async fn configure(&mut self, config: &MemcachedConfig) -> Result<bool> {
+        let changed = self.0.update_file(CONFIG_PATH, config.render()?).await?;
+        if changed {
+            self.0
+                .command(["systemctl", "restart", "memcached"])
+                .run()
+                .await?;
+        }
+        Ok(changed)
+    }
+
+    /// Send the `stats` command to memcached on `127.0.0.1:{port}` and return the parsed
+    /// statistics. Fails if memcached doesn't respond.
+    pub async fn stats(&mut self, port: u16) -> Result<BTreeMap<String, String>> {
+        let output = self
+            .0
+            .command([
+                "timeout",
+                "5",
+                "bash",
+                "-c",
+                &format!(
+                    "exec 3<>/dev/tcp/127.0.0.1/{port}; printf 'stats\\r\\nquit\\r\\n' >&3; cat <&3"
+                ),
+            ])
+            .hide_command()
+            .hide_stdout()
+            .run()
+            .await?;
+        let mut stats = BTreeMap::new();
+        for line in output.stdout_lines() {
+            if let Some(stat) = line.trim_end().strip_prefix("STAT ") {
+                if let Some((name, value)) = stat.split_once(' ') {
+                    stats.insert(name.to_string(), value.to_string());
+                }
+            }
+        }
+        if stats.is_empty() {
+            bail!("memcached didn't return any stats");
+        }
+        Ok(stats)
+    }
+}
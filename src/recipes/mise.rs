@@ -0,0 +1,154 @@
+use anyhow::{bail, Result};
+use log::info;
+use serde_json::Value;
+
+use crate::{Command, Session};
+
+const SYSTEM_BINARY: &str = "/usr/local/bin/mise";
+const SYSTEM_DATA_DIR: &str = "/usr/local/share/mise";
+const SYSTEM_CONFIG_DIR: &str = "/etc/mise";
+
+impl Session {
+    /// Manage language runtimes (Python, Node.js, Ruby, Go, ...) with
+    /// [mise](https://mise.jdx.dev), for the specified user or system-wide if `user` is `None`.
+    ///
+    /// System-wide runtimes are stored in `/usr/local/share/mise` and the global tool
+    /// versions in `/etc/mise`.
+    pub fn mise(&mut self, user: Option<&str>) -> Mise<'_> {
+        Mise {
+            session: self,
+            user: user.map(Into::into),
+        }
+    }
+}
+
+/// Provides access to the mise runtime version manager.
+pub struct Mise<'a> {
+    session: &'a mut Session,
+    user: Option<String>,
+}
+
+impl Mise<'_> {
+    /// Install mise unless it's already installed.
+    pub async fn ensure_installed(&mut self) -> Result<()> {
+        let binary = self.binary().await?;
+        if self.session.path_exists(&binary).await? {
+            return Ok(());
+        }
+        self.session.apt().install(&["curl"]).await?;
+        self.session
+            .command([
+                "bash",
+                "-c",
+                r#"set -o pipefail; curl -fsSL https://mise.run | MISE_INSTALL_PATH="$1" sh"#,
+                "--",
+                &binary,
+            ])
+            .user(self.user.as_deref())
+            .run()
+            .await?;
+        info!("installed mise to {binary}");
+        Ok(())
+    }
+
+    /// Install `version` of `tool` (e.g. `("python", "3.12")`) unless it's already installed.
+    /// Returns `true` if it was installed.
+    pub async fn install(&mut self, tool: &str, version: &str) -> Result<bool> {
+        let spec = tool_spec(tool, version)?;
+        let code = self
+            .command(["where", &spec])
+            .await?
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        if code == 0 {
+            return Ok(false);
+        }
+        self.command(["install", &spec]).await?.run().await?;
+        info!("installed {spec}");
+        Ok(true)
+    }
+
+    /// Make `version` of `tool` the default one, installing it if needed.
+    pub async fn use_global(&mut self, tool: &str, version: &str) -> Result<()> {
+        let spec = tool_spec(tool, version)?;
+        self.command(["use", "--global", &spec])
+            .await?
+            .run()
+            .await?;
+        Ok(())
+    }
+
+    /// Installed versions of `tool`.
+    pub async fn installed_versions(&mut self, tool: &str) -> Result<Vec<String>> {
+        tool_spec(tool, "latest")?;
+        let output = self
+            .command(["ls", "--installed", "--json", tool])
+            .await?
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?;
+        let versions: Vec<Value> = output.json()?;
+        Ok(versions
+            .iter()
+            .filter_map(|version| version["version"].as_str())
+            .map(String::from)
+            .collect())
+    }
+
+    /// Prepare a command that runs with `version` of `tool` in `PATH` (`mise exec`).
+    pub async fn exec<S: AsRef<str>, I: IntoIterator<Item = S>>(
+        &mut self,
+        tool: &str,
+        version: &str,
+        command: I,
+    ) -> Result<Command<'_>> {
+        let spec = tool_spec(tool, version)?;
+        Ok(self.command(["exec", &spec, "--"]).await?.args(command))
+    }
+
+    async fn binary(&mut self) -> Result<String> {
+        match &self.user {
+            Some(user) => Ok(format!(
+                "{}/.local/bin/mise",
+                self.session.home_dir(Some(user)).await?
+            )),
+            None => Ok(SYSTEM_BINARY.into()),
+        }
+    }
+
+    async fn command<S: AsRef<str>, I: IntoIterator<Item = S>>(
+        &mut self,
+        args: I,
+    ) -> Result<Command<'_>> {
+        let binary = self.binary().await?;
+        let command = match &self.user {
+            Some(user) => self.session.command([binary]).args(args).user(Some(user)),
+            None => self
+                .session
+                .command([
+                    "env".to_string(),
+                    format!("MISE_DATA_DIR={SYSTEM_DATA_DIR}"),
+                    format!("MISE_GLOBAL_CONFIG_FILE={SYSTEM_CONFIG_DIR}/config.toml"),
+                    binary,
+                ])
+                .args(args),
+        };
+        Ok(command)
+    }
+}
+
+fn tool_spec(tool: &str, version: &str) -> Result<String> {
+    let valid = |value: &str| {
+        !value.is_empty()
+            && value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "._-:/+".contains(c))
+    };
+    if !valid(tool) || !valid(version) {
+        bail!("invalid tool version: {tool}@{version}");
+    }
+    Ok(format!("{tool}@{version}"))
+}
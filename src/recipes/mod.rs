@@ -1,5 +1,61 @@
+pub mod acl;
+pub mod aide;
+pub mod apparmor;
 pub mod apt;
+pub mod artifact;
+pub mod attrs;
+pub mod batch;
+pub mod bundle;
+pub mod clamav;
+pub mod clock;
+pub mod cloud;
+pub mod cloud_init;
+pub mod disk;
+pub mod dnf;
+pub mod dns;
+pub mod dotfiles;
 pub mod env;
+pub mod env_file;
+pub mod etckeeper;
+pub mod files;
+pub mod firewall;
+pub mod haproxy;
+pub mod java;
+pub mod jobs;
+pub mod lock;
+pub mod maintenance;
+pub mod memcached;
+pub mod mise;
+pub mod mongodb;
+pub mod monitoring;
+pub mod msmtp;
+pub mod net;
+pub mod netplan;
+pub mod nfs;
+pub mod nvidia;
+pub mod openrc;
+pub mod os;
+pub mod patching;
+pub mod pm2;
+pub mod postfix;
 pub mod postgres;
+pub mod powershell;
+pub mod quota;
 pub mod rsync;
+pub mod samba;
+pub mod schedule;
+pub mod script;
+pub mod search_engine;
+pub mod selinux;
+pub mod sftp_user;
+pub mod sops;
+pub mod sqlite;
+pub mod tail;
+pub mod tailscale;
+pub mod transfer;
+pub mod transient_unit;
 pub mod user;
+pub mod user_systemd;
+pub mod version;
+pub mod window;
+pub mod zypper;
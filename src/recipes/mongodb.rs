@@ -0,0 +1,205 @@
+use std::fmt::Write;
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+
+use crate::{Distro, Session};
+
+const CONFIG_PATH: &str = "/etc/mongod.conf";
+
+/// Settings written to `/etc/mongod.conf`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MongoDbConfig {
+    /// Addresses to listen on.
+    pub bind_ip: Vec<String>,
+    /// Port to listen on.
+    pub port: u16,
+    /// Require authentication.
+    pub authorization: bool,
+}
+
+impl Default for MongoDbConfig {
+    fn default() -> Self {
+        Self {
+            bind_ip: vec!["127.0.0.1".into()],
+            port: 27017,
+            authorization: true,
+        }
+    }
+}
+
+impl MongoDbConfig {
+    /// Render the config file.
+    pub fn render(&self) -> Result<String> {
+        let mut out = String::new();
+        writeln!(out, "# Managed by roguewave.")?;
+        writeln!(out, "storage:")?;
+        writeln!(out, "  dbPath: /var/lib/mongodb")?;
+        writeln!(out, "systemLog:")?;
+        writeln!(out, "  destination: file")?;
+        writeln!(out, "  logAppend: true")?;
+        writeln!(out, "  path: /var/log/mongodb/mongod.log")?;
+        writeln!(out, "net:")?;
+        writeln!(out, "  port: {}", self.port)?;
+        writeln!(out, "  bindIp: {}", self.bind_ip.join(","))?;
+        writeln!(out, "processManagement:")?;
+        writeln!(out, "  timeZoneInfo: /usr/share/zoneinfo")?;
+        if self.authorization {
+            writeln!(out, "security:")?;
+            writeln!(out, "  authorization: enabled")?;
+        }
+        Ok(out)
+    }
+}
+
+impl Session {
+    /// Execute MongoDB commands.
+    pub fn mongodb(&mut self) -> MongoDb<'_> {
+        MongoDb {
+            session: self,
+            credentials: None,
+        }
+    }
+}
+
+/// Provides access to MongoDB commands.
+pub struct MongoDb<'a> {
+    session: &'a mut Session,
+    credentials: Option<(String, String)>,
+}
+
+impl MongoDb<'_> {
+    /// Authenticate `mongosh` commands as `user` in the `admin` database.
+    /// The password is not logged.
+    pub fn with_credentials(mut self, user: &str, password: &str) -> Self {
+        self.credentials = Some((user.into(), password.into()));
+        self
+    }
+
+    /// Install MongoDB `version` (e.g. `7.0`) from the official repository on a Debian or
+    /// Ubuntu host, and start the service.
+    pub async fn install(&mut self, version: &str) -> Result<()> {
+        if !self
+            .session
+            .apt()
+            .is_package_installed("mongodb-org")
+            .await?
+        {
+            let os = self.session.os_release().await?;
+            let repository = match (&os.distro, os.version_codename.as_deref()) {
+                (Distro::Ubuntu, Some(codename)) => format!(
+                    "https://repo.mongodb.org/apt/ubuntu {codename}/mongodb-org/{version} multiverse"
+                ),
+                (Distro::Debian, Some(codename)) => format!(
+                    "https://repo.mongodb.org/apt/debian {codename}/mongodb-org/{version} main"
+                ),
+                _ => bail!("unsupported OS for MongoDB installation: {}", os.pretty_name),
+            };
+            self.session
+                .apt()
+                .add_repository(
+                    &format!("mongodb-org-{version}"),
+                    &format!("https://www.mongodb.org/static/pgp/server-{version}.asc"),
+                    &repository,
+                )
+                .await?;
+            self.session.apt().install(&["mongodb-org"]).await?;
+        }
+        self.session
+            .command(["systemctl", "enable", "--now", "mongod"])
+            .run()
+            .await?;
+        Ok(())
+    }
+
+    /// Write `/etc/mongod.conf` and restart the service if the config was changed.
+    /// Returns `true` if the config was changed.
+    ///
+    /// Create an admin user with `create_admin_user` before enabling authorization.
+    pub async fn configure(&mut self, config: &MongoDbConfig) -> Result<bool> {
+        let changed = self
+            .session
+            .update_file(CONFIG_PATH, config.render()?)
+            .await?;
+        if changed {
+            self.session
+                .command(["systemctl", "restart", "mongod"])
+                .run()
+                .await?;
+        }
+        Ok(changed)
+    }
+
+    /// Evaluate a `mongosh` script in `database` and parse the result as JSON.
+    pub async fn eval(&mut self, database: &str, script: &str) -> Result<Value> {
+        self.eval_redacted(database, script, script).await
+    }
+
+    async fn eval_redacted(
+        &mut self,
+        database: &str,
+        script: &str,
+        display_script: &str,
+    ) -> Result<Value> {
+        let mut command = self
+            .session
+            .command(["mongosh", "--quiet", "--json=relaxed", database, "--eval"])
+            .redacted_arg(script, display_script)
+            .hide_stdout();
+        if let Some((user, password)) = &self.credentials {
+            command = command
+                .args(["--authenticationDatabase", "admin", "--username", user])
+                .arg("--password")
+                .redacted_arg(password, "<redacted>");
+        }
+        let output = command.run().await?;
+        if output.stdout_trimmed().is_empty() {
+            return Ok(Value::Null);
+        }
+        output.json()
+    }
+
+    /// Check if `user` exists in `database`.
+    pub async fn user_exists(&mut self, database: &str, user: &str) -> Result<bool> {
+        let script = format!("db.getUser({})", json!(user));
+        Ok(!self.eval(database, &script).await?.is_null())
+    }
+
+    /// Create a user in `database` with the specified roles in the same database.
+    /// The password is not logged.
+    ///
+    /// Note: if the user already exists, its password and roles will not be changed.
+    pub async fn create_user(
+        &mut self,
+        database: &str,
+        user: &str,
+        password: &str,
+        roles: &[&str],
+    ) -> Result<()> {
+        if self.user_exists(database, user).await? {
+            return Ok(());
+        }
+        let roles: Vec<_> = roles
+            .iter()
+            .map(|role| json!({ "role": role, "db": database }))
+            .collect();
+        let script = |password: &str| {
+            format!(
+                "db.createUser({})",
+                json!({ "user": user, "pwd": password, "roles": roles })
+            )
+        };
+        self.eval_redacted(database, &script(password), &script("<redacted>"))
+            .await
+            .with_context(|| format!("failed to create MongoDB user {user:?}"))?;
+        Ok(())
+    }
+
+    /// Create a user with the `root` role in the `admin` database.
+    ///
+    /// On a fresh installation, the first user can be created without credentials
+    /// even if authorization is enabled.
+    pub async fn create_admin_user(&mut self, user: &str, password: &str) -> Result<()> {
+        self.create_user("admin", user, password, &["root"]).await
+    }
+}
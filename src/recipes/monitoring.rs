@@ -0,0 +1,193 @@
+use std::{
+    fmt::Write,
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use log::info;
+
+use crate::Session;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const DATADOG_CONFIG_PATH: &str = "/etc/datadog-agent/datadog.yaml";
+
+/// A monitoring agent that can be installed with `Session::install_monitoring_agent`.
+pub trait MonitoringAgent {
+    /// Name of the systemd service of the agent.
+    fn service(&self) -> &str;
+
+    /// Set up the vendor repository and install the agent unless it's already installed.
+    fn install(&self, session: &mut Session) -> impl Future<Output = Result<()>> + Send;
+
+    /// Write the agent config. Returns `true` if it was changed.
+    fn configure(&self, session: &mut Session) -> impl Future<Output = Result<bool>> + Send;
+
+    /// Check if the running agent reports itself as healthy.
+    fn is_healthy(&self, session: &mut Session) -> impl Future<Output = Result<bool>> + Send;
+}
+
+impl Session {
+    /// Install and configure a monitoring agent, make sure its service is enabled and
+    /// running (restarting it if the config was changed), and wait until it reports
+    /// itself as healthy.
+    pub async fn install_monitoring_agent(
+        &mut self,
+        agent: &impl MonitoringAgent,
+        timeout: Duration,
+    ) -> Result<()> {
+        agent.install(self).await?;
+        let changed = agent.configure(self).await?;
+        let service = agent.service();
+        self.command(["systemctl", "enable", "--now", service])
+            .run()
+            .await?;
+        if changed {
+            self.command(["systemctl", "restart", service])
+                .run()
+                .await?;
+        }
+        let started = Instant::now();
+        let mut logged_wait = false;
+        while !agent.is_healthy(self).await? {
+            if started.elapsed() >= timeout {
+                bail!("timed out waiting for {service} to become healthy");
+            }
+            if !logged_wait {
+                info!("waiting for {service} to become healthy");
+                logged_wait = true;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        Ok(())
+    }
+}
+
+/// The [Datadog Agent](https://docs.datadoghq.com/agent/), installed from the official
+/// apt repository.
+///
+/// The API key is written to `/etc/datadog-agent/datadog.yaml`, which is created with mode 640
+/// and owned by `dd-agent` before the key is written, and is never logged.
+#[derive(Clone)]
+pub struct Datadog {
+    api_key: String,
+    /// Datadog site, e.g. `datadoghq.com` (the default) or `datadoghq.eu`.
+    pub site: String,
+    /// Hostname reported to Datadog. Detected by the agent if not set.
+    pub hostname: Option<String>,
+    /// Host tags, e.g. `env:prod`.
+    pub tags: Vec<String>,
+}
+
+impl Datadog {
+    /// Configure the agent with default settings.
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            site: "datadoghq.com".into(),
+            hostname: None,
+            tags: Vec::new(),
+        }
+    }
+
+    fn render(&self) -> Result<String> {
+        let valid = |value: &str| {
+            !value.is_empty()
+                && value
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "._-:/".contains(c))
+        };
+        if !self.api_key.chars().all(|c| c.is_ascii_alphanumeric()) {
+            bail!("invalid Datadog API key");
+        }
+        if !valid(&self.site) {
+            bail!("invalid Datadog site: {:?}", self.site);
+        }
+        let mut out = String::new();
+        writeln!(out, "# Managed by roguewave.")?;
+        writeln!(out, "api_key: {}", self.api_key)?;
+        writeln!(out, "site: {}", self.site)?;
+        if let Some(hostname) = &self.hostname {
+            if !valid(hostname) {
+                bail!("invalid hostname: {hostname:?}");
+            }
+            writeln!(out, "hostname: {hostname}")?;
+        }
+        if !self.tags.is_empty() {
+            writeln!(out, "tags:")?;
+            for tag in &self.tags {
+                if !valid(tag) {
+                    bail!("invalid tag: {tag:?}");
+                }
+                writeln!(out, "  - {tag}")?;
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl MonitoringAgent for Datadog {
+    fn service(&self) -> &str {
+        "datadog-agent"
+    }
+
+    async fn install(&self, session: &mut Session) -> Result<()> {
+        if session.apt().is_package_installed("datadog-agent").await? {
+            return Ok(());
+        }
+        session
+            .apt()
+            .add_repository(
+                "datadog",
+                "https://keys.datadoghq.com/DATADOG_APT_KEY_CURRENT.public",
+                "https://apt.datadoghq.com/ stable 7",
+            )
+            .await?;
+        session.apt().install(&["datadog-agent"]).await
+    }
+
+    async fn configure(&self, session: &mut Session) -> Result<bool> {
+        // Restrict access before writing the API key to the file.
+        if session.path_exists(DATADOG_CONFIG_PATH).await? {
+            session
+                .command(["chown", "dd-agent:dd-agent", DATADOG_CONFIG_PATH])
+                .hide_command()
+                .run()
+                .await?;
+            session
+                .command(["chmod", "640", DATADOG_CONFIG_PATH])
+                .hide_command()
+                .run()
+                .await?;
+        } else {
+            session
+                .command([
+                    "install",
+                    "-m",
+                    "640",
+                    "-o",
+                    "dd-agent",
+                    "-g",
+                    "dd-agent",
+                    "/dev/null",
+                    DATADOG_CONFIG_PATH,
+                ])
+                .hide_command()
+                .run()
+                .await?;
+        }
+        session
+            .update_file(DATADOG_CONFIG_PATH, self.render()?)
+            .await
+    }
+
+    async fn is_healthy(&self, session: &mut Session) -> Result<bool> {
+        let code = session
+            .command(["datadog-agent", "health"])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        Ok(code == 0)
+    }
+}
@@ -0,0 +1,135 @@
+use std::fmt::Write;
+
+use anyhow::{bail, Result};
+use log::info;
+
+use crate::Session;
+
+const CONFIG_PATH: &str = "/etc/msmtprc";
+
+/// SMTP provider settings written to `/etc/msmtprc`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsmtpConfig {
+    /// SMTP server host name.
+    pub host: String,
+    /// SMTP server port.
+    pub port: u16,
+    /// Envelope sender address.
+    pub from: String,
+    /// Username and password for SMTP authentication.
+    pub credentials: Option<(String, String)>,
+    /// Use TLS. If `port` is 465, TLS is started immediately, otherwise STARTTLS is used.
+    pub tls: bool,
+}
+
+impl MsmtpConfig {
+    /// Render the config file.
+    pub fn render(&self) -> Result<String> {
+        for value in [&self.host, &self.from] {
+            if value.is_empty() || value.contains(char::is_whitespace) {
+                bail!("invalid msmtp setting: {value:?}");
+            }
+        }
+        let on_off = |value: bool| if value { "on" } else { "off" };
+        let mut out = String::new();
+        writeln!(out, "# Managed by roguewave.")?;
+        writeln!(out, "defaults")?;
+        writeln!(out, "tls {}", on_off(self.tls))?;
+        writeln!(out, "tls_starttls {}", on_off(self.tls && self.port != 465))?;
+        writeln!(out, "tls_trust_file /etc/ssl/certs/ca-certificates.crt")?;
+        writeln!(out, "syslog LOG_MAIL")?;
+        writeln!(out)?;
+        writeln!(out, "account default")?;
+        writeln!(out, "host {}", self.host)?;
+        writeln!(out, "port {}", self.port)?;
+        writeln!(out, "from {}", self.from)?;
+        match &self.credentials {
+            Some((user, password)) => {
+                if user.contains(char::is_whitespace) || password.contains(['\n', '\r']) {
+                    bail!("invalid SMTP credentials");
+                }
+                writeln!(out, "auth on")?;
+                writeln!(out, "user {user}")?;
+                writeln!(out, "password {password}")?;
+            }
+            None => writeln!(out, "auth off")?,
+        }
+        Ok(out)
+    }
+}
+
+impl Session {
+    /// Manage msmtp, a lightweight SMTP client acting as `sendmail`.
+    pub fn msmtp(&mut self) -> Msmtp<'_> {
+        Msmtp(self)
+    }
+}
+
+/// Provides access to msmtp management.
+pub struct Msmtp<'a>(&'a mut Session);
+
+impl Msmtp<'_> {
+    /// Install msmtp and make it provide `/usr/sbin/sendmail`.
+    pub async fn ensure_installed(&mut self) -> Result<()> {
+        self.0.apt().install(&["msmtp", "msmtp-mta"]).await
+    }
+
+    /// Write `/etc/msmtprc`. The file is readable only by root and the `msmtp` group
+    /// (which the msmtp binary runs as), and its content is not logged.
+    /// Returns `true` if the config was changed.
+    pub async fn configure(&mut self, config: &MsmtpConfig) -> Result<bool> {
+        let content = config.render()?;
+        if !self.0.path_exists(CONFIG_PATH).await? {
+            self.0
+                .command(["install", "-m", "640", "/dev/null", CONFIG_PATH])
+                .hide_command()
+                .run()
+                .await?;
+        }
+        let changed = self.0.update_file(CONFIG_PATH, content).await?;
+        let group_exists = self
+            .0
+            .command(["getent", "group", "msmtp"])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?
+            == 0;
+        let (owner, mode) = if group_exists {
+            ("root:msmtp", "640")
+        } else {
+            ("root:root", "600")
+        };
+        self.0
+            .command(["chown", owner, CONFIG_PATH])
+            .hide_command()
+            .run()
+            .await?;
+        self.0
+            .command(["chmod", mode, CONFIG_PATH])
+            .hide_command()
+            .run()
+            .await?;
+        Ok(changed)
+    }
+
+    /// Send a test message to `recipient`. msmtp delivers synchronously, so this fails
+    /// if the SMTP server rejects the message.
+    pub async fn send_test_mail(&mut self, recipient: &str) -> Result<()> {
+        if recipient.is_empty() || recipient.contains(char::is_whitespace) {
+            bail!("invalid recipient: {recipient:?}");
+        }
+        self.0
+            .command([
+                "bash",
+                "-c",
+                r#"printf 'To: %s\nSubject: Test message from %s\n\nThis is a test message sent by roguewave.\n' "$1" "$(hostname --fqdn)" | msmtp -- "$1""#,
+                "--",
+                recipient,
+            ])
+            .run()
+            .await?;
+        info!("test message to {recipient} was accepted by the SMTP server");
+        Ok(())
+    }
+}
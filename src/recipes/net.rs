@@ -0,0 +1,137 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use log::info;
+
+use crate::Session;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const CONNECT_TIMEOUT_SECS: &str = "5";
+
+/// Transport protocol of a socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    /// TCP.
+    Tcp,
+    /// UDP.
+    Udp,
+}
+
+/// A socket listening on the remote host.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ListeningPort {
+    /// Transport protocol.
+    pub protocol: Protocol,
+    /// Local address, e.g. `0.0.0.0`, `[::]` or `127.0.0.53%lo`.
+    pub address: String,
+    /// Port number.
+    pub port: u16,
+    /// Name of the process that owns the socket (only available to root).
+    pub process: Option<String>,
+}
+
+impl Session {
+    /// Check network connectivity from the remote host.
+    pub fn net(&mut self) -> Net<'_> {
+        Net(self)
+    }
+}
+
+/// Provides access to network checks executed on the remote host.
+pub struct Net<'a>(&'a mut Session);
+
+impl Net<'_> {
+    /// Check if a TCP connection to `host:port` can be established from the remote host.
+    ///
+    /// Requires `bash` and `timeout` on the remote host.
+    pub async fn port_open(&mut self, host: &str, port: u16) -> Result<bool> {
+        if host.is_empty()
+            || !host
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == ':')
+        {
+            bail!("invalid host: {host:?}");
+        }
+        let code = self
+            .0
+            .command([
+                "timeout",
+                CONNECT_TIMEOUT_SECS,
+                "bash",
+                "-c",
+                &format!("</dev/tcp/{host}/{port}"),
+            ])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        Ok(code == 0)
+    }
+
+    /// Wait until a TCP port on the remote host accepts connections.
+    pub async fn wait_for_port(&mut self, port: u16, timeout: Duration) -> Result<()> {
+        let started = Instant::now();
+        let mut logged_wait = false;
+        while !self.port_open("127.0.0.1", port).await? {
+            if started.elapsed() >= timeout {
+                bail!("timed out waiting for port {port}");
+            }
+            if !logged_wait {
+                info!("waiting for port {port}");
+                logged_wait = true;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        Ok(())
+    }
+
+    /// List TCP and UDP sockets listening on the remote host (`ss -tulnp`).
+    pub async fn listening_ports(&mut self) -> Result<Vec<ListeningPort>> {
+        let output = self
+            .0
+            .command([
+                "ss",
+                "--no-header",
+                "--tcp",
+                "--udp",
+                "--listening",
+                "--numeric",
+                "--processes",
+            ])
+            .hide_command()
+            .hide_stdout()
+            .run()
+            .await?;
+        output.stdout_lines().map(parse_ss_line).collect()
+    }
+}
+
+// Example: `tcp LISTEN 0 128 0.0.0.0:22 0.0.0.0:* users:(("sshd",pid=1,fd=3))`
+fn parse_ss_line(line: &str) -> Result<ListeningPort> {
+    let fields: Vec<_> = line.split_whitespace().collect();
+    if fields.len() < 6 {
+        bail!("unexpected ss output line: {line:?}");
+    }
+    let protocol = match fields[0] {
+        "tcp" => Protocol::Tcp,
+        "udp" => Protocol::Udp,
+        other => bail!("unexpected protocol in ss output: {other:?}"),
+    };
+    let (address, port) = fields[4]
+        .rsplit_once(':')
+        .with_context(|| format!("invalid address in ss output: {line:?}"))?;
+    let process = fields.get(6).and_then(|users| {
+        users
+            .strip_prefix("users:((\"")
+            .and_then(|rest| rest.split('"').next())
+            .map(Into::into)
+    });
+    Ok(ListeningPort {
+        protocol,
+        address: address.into(),
+        port: port
+            .parse()
+            .with_context(|| format!("invalid port in ss output: {line:?}"))?,
+        process,
+    })
+}
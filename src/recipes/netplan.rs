@@ -0,0 +1,244 @@
+use std::{fmt::Write, net::IpAddr, time::Duration};
+
+use anyhow::{bail, Result};
+use log::{info, warn};
+use tokio::time::timeout;
+
+use crate::Session;
+
+const CONFIG_PATH: &str = "/etc/netplan/90-roguewave.yaml";
+const BACKUP_PATH: &str = "/etc/netplan/90-roguewave.yaml.bak";
+const REVERT_JOB: &str = "netplan-revert";
+const APPLY_TIMEOUT: Duration = Duration::from_secs(30);
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Network configuration rendered to a netplan YAML file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetplanConfig {
+    /// Physical interfaces.
+    pub ethernets: Vec<EthernetConfig>,
+    /// VLAN interfaces.
+    pub vlans: Vec<VlanConfig>,
+    /// Bridge interfaces.
+    pub bridges: Vec<BridgeConfig>,
+}
+
+/// Address configuration of an interface.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InterfaceSettings {
+    /// Enable DHCP for IPv4.
+    pub dhcp4: bool,
+    /// Static addresses in CIDR notation, e.g. `192.168.1.10/24`.
+    pub addresses: Vec<String>,
+    /// Default gateway.
+    pub gateway: Option<IpAddr>,
+    /// DNS servers.
+    pub nameservers: Vec<IpAddr>,
+}
+
+/// Configuration of a physical interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EthernetConfig {
+    /// Interface name, e.g. `eth0`.
+    pub name: String,
+    /// Address configuration.
+    pub settings: InterfaceSettings,
+}
+
+/// Configuration of a VLAN interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VlanConfig {
+    /// Interface name, e.g. `vlan10`.
+    pub name: String,
+    /// VLAN ID.
+    pub id: u16,
+    /// Name of the underlying interface.
+    pub link: String,
+    /// Address configuration.
+    pub settings: InterfaceSettings,
+}
+
+/// Configuration of a bridge interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BridgeConfig {
+    /// Interface name, e.g. `br0`.
+    pub name: String,
+    /// Names of bridged interfaces.
+    pub interfaces: Vec<String>,
+    /// Address configuration.
+    pub settings: InterfaceSettings,
+}
+
+impl NetplanConfig {
+    /// Render the config as netplan YAML.
+    pub fn render(&self) -> Result<String> {
+        let mut out = String::new();
+        writeln!(out, "# Managed by roguewave.")?;
+        writeln!(out, "network:")?;
+        writeln!(out, "  version: 2")?;
+        if !self.ethernets.is_empty() {
+            writeln!(out, "  ethernets:")?;
+            for ethernet in &self.ethernets {
+                write_interface_name(&mut out, &ethernet.name)?;
+                write_settings(&mut out, &ethernet.settings)?;
+            }
+        }
+        if !self.vlans.is_empty() {
+            writeln!(out, "  vlans:")?;
+            for vlan in &self.vlans {
+                write_interface_name(&mut out, &vlan.name)?;
+                validate_name(&vlan.link)?;
+                writeln!(out, "      id: {}", vlan.id)?;
+                writeln!(out, "      link: {}", vlan.link)?;
+                write_settings(&mut out, &vlan.settings)?;
+            }
+        }
+        if !self.bridges.is_empty() {
+            writeln!(out, "  bridges:")?;
+            for bridge in &self.bridges {
+                write_interface_name(&mut out, &bridge.name)?;
+                for name in &bridge.interfaces {
+                    validate_name(name)?;
+                }
+                writeln!(out, "      interfaces: [{}]", bridge.interfaces.join(", "))?;
+                write_settings(&mut out, &bridge.settings)?;
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-')
+    {
+        bail!("invalid interface name: {name:?}");
+    }
+    Ok(())
+}
+
+fn write_interface_name(out: &mut String, name: &str) -> Result<()> {
+    validate_name(name)?;
+    writeln!(out, "    {name}:")?;
+    Ok(())
+}
+
+fn write_settings(out: &mut String, settings: &InterfaceSettings) -> Result<()> {
+    writeln!(out, "      dhcp4: {}", settings.dhcp4)?;
+    if !settings.addresses.is_empty() {
+        writeln!(out, "      addresses:")?;
+        for address in &settings.addresses {
+            let Some((ip, prefix)) = address.split_once('/') else {
+                bail!("address must be in CIDR notation: {address:?}");
+            };
+            if ip.parse::<IpAddr>().is_err() || prefix.parse::<u8>().is_err() {
+                bail!("invalid address: {address:?}");
+            }
+            writeln!(out, "        - {address}")?;
+        }
+    }
+    if let Some(gateway) = settings.gateway {
+        writeln!(out, "      routes:")?;
+        writeln!(out, "        - to: default")?;
+        writeln!(out, "          via: {gateway}")?;
+    }
+    if !settings.nameservers.is_empty() {
+        let nameservers: Vec<_> = settings
+            .nameservers
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        writeln!(out, "      nameservers:")?;
+        writeln!(out, "        addresses: [{}]", nameservers.join(", "))?;
+    }
+    Ok(())
+}
+
+impl Session {
+    /// Manage network configuration with netplan.
+    pub fn netplan(&mut self) -> Netplan<'_> {
+        Netplan(self)
+    }
+}
+
+/// Provides access to netplan configuration.
+pub struct Netplan<'a>(&'a mut Session);
+
+impl Netplan<'_> {
+    /// Write `config` to `/etc/netplan/90-roguewave.yaml` and apply it with rollback protection.
+    ///
+    /// Before applying, a systemd timer is scheduled to restore the previous config after
+    /// `rollback_timeout`. After applying, the connection to the host is checked and the timer
+    /// is cancelled if the host is still reachable. If the new config cuts off access to the host,
+    /// the previous config will be restored automatically. `rollback_timeout` should be at least
+    /// a minute to leave enough time for applying and checking the config.
+    ///
+    /// If `netplan apply` fails, the previous config is restored immediately and the error is
+    /// returned. If it times out, the timer is left armed and an error is returned.
+    ///
+    /// Returns `false` if the config was already up to date.
+    pub async fn apply(
+        &mut self,
+        config: &NetplanConfig,
+        rollback_timeout: Duration,
+    ) -> Result<bool> {
+        let content = config.render()?;
+        let previous = self.0.read_file_if_exists(CONFIG_PATH).await?;
+        if previous.as_deref() == Some(content.as_str()) {
+            return Ok(false);
+        }
+        let restore_script = if let Some(previous) = &previous {
+            self.0.write_file(BACKUP_PATH, previous).await?;
+            format!("mv {BACKUP_PATH} {CONFIG_PATH}")
+        } else {
+            format!("rm -f {CONFIG_PATH}")
+        };
+        self.0.write_file(CONFIG_PATH, &content).await?;
+        self.0.command(["chmod", "600", CONFIG_PATH]).run().await?;
+        if let Err(err) = self.0.command(["netplan", "generate"]).run().await {
+            self.0.command(["sh", "-c", &restore_script]).run().await?;
+            return Err(err.context("invalid netplan config"));
+        }
+
+        let revert_job = self
+            .0
+            .schedule_once(
+                REVERT_JOB,
+                rollback_timeout,
+                ["sh", "-c", &format!("{restore_script} && netplan apply")],
+            )
+            .await?;
+
+        info!("applying netplan config");
+        match timeout(APPLY_TIMEOUT, self.0.command(["netplan", "apply"]).run()).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => {
+                warn!("netplan apply failed, restoring previous config");
+                let restore = format!("{restore_script} && netplan apply");
+                self.0.command(["sh", "-c", &restore]).run().await?;
+                revert_job.cancel(self.0).await?;
+                return Err(err.context("failed to apply netplan config"));
+            }
+            Err(_) => bail!(
+                "netplan apply timed out; previous config will be restored in {} s",
+                rollback_timeout.as_secs()
+            ),
+        }
+        let check = timeout(CHECK_TIMEOUT, self.0.command(["true"]).hide_command().run()).await;
+        if !matches!(check, Ok(Ok(_))) {
+            bail!(
+                "host is unreachable after applying netplan config; \
+                previous config will be restored in {} s",
+                rollback_timeout.as_secs()
+            );
+        }
+        revert_job.cancel(self.0).await?;
+        if previous.is_some() {
+            self.0.remove_file(BACKUP_PATH).await?;
+        }
+        info!("netplan config applied");
+        Ok(true)
+    }
+}
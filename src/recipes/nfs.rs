@@ -0,0 +1,199 @@
+use anyhow::{bail, Result};
+use log::info;
+
+use crate::Session;
+
+const EXPORTS_PATH: &str = "/etc/exports";
+const FSTAB_PATH: &str = "/etc/fstab";
+
+/// A directory exported over NFS.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NfsExport {
+    /// Exported directory on the server.
+    pub path: String,
+    /// Allowed clients (host names, addresses or networks like `10.0.0.0/24`) with their
+    /// export options (e.g. `rw,sync,no_subtree_check`).
+    pub clients: Vec<(String, String)>,
+}
+
+impl NfsExport {
+    /// Render the line for `/etc/exports`.
+    pub fn render(&self) -> Result<String> {
+        validate_path(&self.path)?;
+        if self.clients.is_empty() {
+            bail!("NFS export {:?} has no clients", self.path);
+        }
+        let mut line = self.path.clone();
+        for (host, options) in &self.clients {
+            if host.is_empty() || host.chars().any(|c| c.is_whitespace() || c == '(') {
+                bail!("invalid NFS client: {host:?}");
+            }
+            if options
+                .chars()
+                .any(|c| c.is_whitespace() || c == '(' || c == ')')
+            {
+                bail!("invalid NFS export options: {options:?}");
+            }
+            line.push(' ');
+            line.push_str(host);
+            line.push('(');
+            line.push_str(options);
+            line.push(')');
+        }
+        Ok(line)
+    }
+}
+
+fn validate_path(path: &str) -> Result<()> {
+    if !path.starts_with('/') || path.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        bail!("invalid NFS path: {path:?}");
+    }
+    Ok(())
+}
+
+impl Session {
+    /// Manage NFS exports (on a server) and NFS mounts (on a client).
+    ///
+    /// A server and its clients can be set up from one script:
+    ///
+    /// ```no_run
+    /// # use roguewave::{NfsExport, Session};
+    /// # async fn f(server: &mut Session, client: &mut Session) -> anyhow::Result<()> {
+    /// server.nfs().ensure_server_installed().await?;
+    /// server
+    ///     .nfs()
+    ///     .set_export(&NfsExport {
+    ///         path: "/srv/share".into(),
+    ///         clients: vec![("10.0.0.0/24".into(), "rw,sync,no_subtree_check".into())],
+    ///     })
+    ///     .await?;
+    /// client.nfs().ensure_client_installed().await?;
+    /// client
+    ///     .nfs()
+    ///     .mount("10.0.0.1", "/srv/share", "/mnt/share", "defaults,_netdev")
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn nfs(&mut self) -> Nfs<'_> {
+        Nfs(self)
+    }
+}
+
+/// Provides access to NFS management.
+pub struct Nfs<'a>(&'a mut Session);
+
+impl Nfs<'_> {
+    /// Install the NFS server.
+    pub async fn ensure_server_installed(&mut self) -> Result<()> {
+        self.0.apt().install(&["nfs-kernel-server"]).await
+    }
+
+    /// Install NFS client tools.
+    pub async fn ensure_client_installed(&mut self) -> Result<()> {
+        self.0.apt().install(&["nfs-common"]).await
+    }
+
+    /// Add or update an export in `/etc/exports` and reload exports if it was changed.
+    /// The exported directory is created if it doesn't exist.
+    /// Returns `true` if the export was changed.
+    pub async fn set_export(&mut self, export: &NfsExport) -> Result<bool> {
+        let line = export.render()?;
+        self.0.command(["mkdir", "-p", &export.path]).run().await?;
+        let changed = self
+            .0
+            .update_managed_block(EXPORTS_PATH, &export_marker(&export.path), &line)
+            .await?;
+        if changed {
+            self.reload_exports().await?;
+        }
+        Ok(changed)
+    }
+
+    /// Remove an export previously added by [`set_export`](Self::set_export).
+    /// Returns `true` if the export was removed.
+    pub async fn remove_export(&mut self, path: &str) -> Result<bool> {
+        validate_path(path)?;
+        let changed = self
+            .0
+            .update_managed_block(EXPORTS_PATH, &export_marker(path), "")
+            .await?;
+        if changed {
+            self.reload_exports().await?;
+        }
+        Ok(changed)
+    }
+
+    /// Re-export all directories (`exportfs -ra`).
+    pub async fn reload_exports(&mut self) -> Result<()> {
+        self.0.command(["exportfs", "-ra"]).run().await?;
+        info!("reloaded NFS exports");
+        Ok(())
+    }
+
+    /// Add an `/etc/fstab` entry for `server:remote_path` at `mount_point` and mount it.
+    /// If the entry was changed and the mount point is already mounted, it's remounted.
+    /// Returns `true` if the fstab entry was changed.
+    pub async fn mount(
+        &mut self,
+        server: &str,
+        remote_path: &str,
+        mount_point: &str,
+        options: &str,
+    ) -> Result<bool> {
+        validate_path(remote_path)?;
+        validate_path(mount_point)?;
+        if server.is_empty() || server.chars().any(|c| c.is_whitespace() || c == ':') {
+            bail!("invalid NFS server: {server:?}");
+        }
+        if options.is_empty() || options.chars().any(char::is_whitespace) {
+            bail!("invalid mount options: {options:?}");
+        }
+        let line = format!("{server}:{remote_path} {mount_point} nfs {options} 0 0");
+        self.0.command(["mkdir", "-p", mount_point]).run().await?;
+        let changed = self
+            .0
+            .update_managed_block(FSTAB_PATH, &mount_marker(mount_point), &line)
+            .await?;
+        let mounted = self.is_mounted(mount_point).await?;
+        if changed && mounted {
+            self.0.command(["umount", mount_point]).run().await?;
+        }
+        if changed || !mounted {
+            self.0.command(["mount", mount_point]).run().await?;
+            info!("mounted {server}:{remote_path} at {mount_point}");
+        }
+        Ok(changed)
+    }
+
+    /// Unmount `mount_point` and remove its `/etc/fstab` entry previously added by
+    /// [`mount`](Self::mount). Returns `true` if the entry was removed.
+    pub async fn unmount(&mut self, mount_point: &str) -> Result<bool> {
+        validate_path(mount_point)?;
+        if self.is_mounted(mount_point).await? {
+            self.0.command(["umount", mount_point]).run().await?;
+        }
+        self.0
+            .update_managed_block(FSTAB_PATH, &mount_marker(mount_point), "")
+            .await
+    }
+
+    async fn is_mounted(&mut self, mount_point: &str) -> Result<bool> {
+        let code = self
+            .0
+            .command(["mountpoint", "-q", mount_point])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        Ok(code == 0)
+    }
+}
+
+fn export_marker(path: &str) -> String {
+    format!("roguewave nfs export {path}")
+}
+
+fn mount_marker(mount_point: &str) -> String {
+    format!("roguewave nfs mount {mount_point}")
+}
@@ -0,0 +1,194 @@
+use anyhow::{bail, Context, Result};
+use log::info;
+
+use crate::{Distro, Session};
+
+/// Information about a GPU reported by `nvidia-smi`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GpuInfo {
+    /// GPU index.
+    pub index: u32,
+    /// Product name, e.g. `NVIDIA A100-SXM4-40GB`.
+    pub name: String,
+    /// GPU UUID.
+    pub uuid: String,
+    /// Installed driver version.
+    pub driver_version: String,
+    /// Total memory in MiB.
+    pub memory_total_mib: u64,
+    /// Used memory in MiB.
+    pub memory_used_mib: u64,
+    /// GPU utilization in percent.
+    pub utilization_percent: Option<u32>,
+    /// GPU temperature in degrees Celsius.
+    pub temperature_celsius: Option<u32>,
+}
+
+impl GpuInfo {
+    /// Parse output of `nvidia-smi --query-gpu=index,name,uuid,driver_version,memory.total,
+    /// memory.used,utilization.gpu,temperature.gpu --format=csv,noheader,nounits`.
+    pub fn parse_csv(output: &str) -> Result<Vec<Self>> {
+        output
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+                let [index, name, uuid, driver_version, memory_total, memory_used, utilization, temperature] =
+                    fields[..]
+                else {
+                    bail!("invalid nvidia-smi output line: {line:?}");
+                };
+                // Unsupported values are reported as `[N/A]` or `[Not Supported]`.
+                let optional = |value: &str| value.parse().ok();
+                Ok(Self {
+                    index: index.parse().context("invalid GPU index")?,
+                    name: name.into(),
+                    uuid: uuid.into(),
+                    driver_version: driver_version.into(),
+                    memory_total_mib: memory_total.parse().context("invalid GPU memory")?,
+                    memory_used_mib: memory_used.parse().context("invalid GPU memory")?,
+                    utilization_percent: optional(utilization),
+                    temperature_celsius: optional(temperature),
+                })
+            })
+            .collect()
+    }
+}
+
+impl Session {
+    /// Manage NVIDIA GPU drivers and the container toolkit.
+    pub fn nvidia(&mut self) -> Nvidia<'_> {
+        Nvidia(self)
+    }
+}
+
+/// Provides access to NVIDIA driver management.
+pub struct Nvidia<'a>(&'a mut Session);
+
+impl Nvidia<'_> {
+    /// Install the NVIDIA driver unless `nvidia-smi` already works.
+    ///
+    /// On Ubuntu, `version` selects the driver branch (e.g. `"550-server"` installs
+    /// `nvidia-driver-550-server`); without it, the recommended driver is installed with
+    /// `ubuntu-drivers`. On Debian, the `nvidia-driver` package from the `non-free`
+    /// component is installed and `version` must be `None`.
+    ///
+    /// Returns `true` if the driver was installed. A reboot is usually needed afterwards.
+    pub async fn install_driver(&mut self, version: Option<&str>) -> Result<bool> {
+        if self.driver_works().await? {
+            return Ok(false);
+        }
+        let os = self.0.os_release().await?;
+        if os.is_one_of(&[Distro::Ubuntu]) {
+            match version {
+                Some(version) => {
+                    if !version
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-')
+                    {
+                        bail!("invalid NVIDIA driver version: {version:?}");
+                    }
+                    let package = format!("nvidia-driver-{version}");
+                    self.0.apt().install(&[&package]).await?;
+                }
+                None => {
+                    self.0.apt().install(&["ubuntu-drivers-common"]).await?;
+                    self.0
+                        .command(["ubuntu-drivers", "install", "--gpgpu"])
+                        .run()
+                        .await?;
+                }
+            }
+        } else if os.is_one_of(&[Distro::Debian]) {
+            if version.is_some() {
+                bail!("selecting NVIDIA driver version is not supported on Debian");
+            }
+            self.0
+                .apt()
+                .install(&["nvidia-driver", "firmware-misc-nonfree"])
+                .await?;
+        } else {
+            self.0
+                .require_os_for(
+                    "NVIDIA driver installation",
+                    &[Distro::Ubuntu, Distro::Debian],
+                )
+                .await?;
+        }
+        info!("installed NVIDIA driver, a reboot may be required");
+        Ok(true)
+    }
+
+    /// Add the NVIDIA container toolkit repository and install the toolkit.
+    pub async fn install_container_toolkit(&mut self) -> Result<()> {
+        self.0
+            .apt()
+            .add_repository(
+                "nvidia-container-toolkit",
+                "https://nvidia.github.io/libnvidia-container/gpgkey",
+                "https://nvidia.github.io/libnvidia-container/stable/deb/$(ARCH) /",
+            )
+            .await?;
+        self.0.apt().install(&["nvidia-container-toolkit"]).await
+    }
+
+    /// Register the NVIDIA runtime in the docker daemon config and restart docker.
+    pub async fn configure_docker_runtime(&mut self) -> Result<()> {
+        self.0
+            .command(["nvidia-ctk", "runtime", "configure", "--runtime=docker"])
+            .run()
+            .await?;
+        self.0
+            .command(["systemctl", "restart", "docker"])
+            .run()
+            .await?;
+        Ok(())
+    }
+
+    /// Check if `nvidia-smi` can talk to the driver.
+    pub async fn driver_works(&mut self) -> Result<bool> {
+        let code = self
+            .0
+            .command(["nvidia-smi", "--list-gpus"])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        Ok(code == 0)
+    }
+
+    /// List GPUs with their current memory usage, utilization and temperature.
+    pub async fn gpus(&mut self) -> Result<Vec<GpuInfo>> {
+        let output = self
+            .0
+            .command([
+                "nvidia-smi",
+                "--query-gpu=index,name,uuid,driver_version,memory.total,memory.used,\
+                utilization.gpu,temperature.gpu",
+                "--format=csv,noheader,nounits",
+            ])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?;
+        GpuInfo::parse_csv(&output.stdout)
+    }
+
+    /// Fail unless the driver works and at least `expected_count` GPUs are visible.
+    pub async fn verify(&mut self, expected_count: usize) -> Result<Vec<GpuInfo>> {
+        let gpus = self.gpus().await?;
+        if gpus.len() < expected_count {
+            bail!(
+                "expected {expected_count} GPUs, but nvidia-smi reports {}",
+                gpus.len()
+            );
+        }
+        for gpu in &gpus {
+            info!(
+                "GPU {}: {} (driver {}, {} MiB)",
+                gpu.index, gpu.name, gpu.driver_version, gpu.memory_total_mib
+            );
+        }
+        Ok(gpus)
+    }
+}
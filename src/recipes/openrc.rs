@@ -0,0 +1,225 @@
+use std::fmt::Write;
+
+use anyhow::{bail, Result};
+use log::info;
+
+use crate::{command::shell_quote, Distro, Session};
+
+/// An OpenRC init script for a long-running program supervised by `start-stop-daemon`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenRcScript {
+    /// Service name. The script is installed to `/etc/init.d/{name}`.
+    pub name: String,
+    /// Human-readable description.
+    pub description: Option<String>,
+    /// Absolute path to the executable.
+    pub command: String,
+    /// Arguments of the executable.
+    pub args: Vec<String>,
+    /// User (and optionally group, as `user:group`) to run the program as.
+    pub user: Option<String>,
+    /// Working directory.
+    pub directory: Option<String>,
+    /// Services that must be started before this one (`need` dependencies).
+    pub need: Vec<String>,
+    /// Services that should be started before this one if present (`after` dependencies).
+    pub after: Vec<String>,
+    /// Path of the log file for stdout and stderr of the program.
+    pub log_file: Option<String>,
+}
+
+impl OpenRcScript {
+    /// Create a script for running `command` with default settings.
+    pub fn new(name: impl Into<String>, command: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: None,
+            command: command.into(),
+            args: Vec::new(),
+            user: None,
+            directory: None,
+            need: Vec::new(),
+            after: vec!["net".into()],
+            log_file: None,
+        }
+    }
+
+    /// Render the init script.
+    pub fn render(&self) -> Result<String> {
+        validate_service_name(&self.name)?;
+        for dependency in self.need.iter().chain(&self.after) {
+            validate_service_name(dependency)?;
+        }
+        let mut out = String::new();
+        writeln!(out, "#!/sbin/openrc-run")?;
+        writeln!(out, "# Managed by roguewave.")?;
+        writeln!(out)?;
+        if let Some(description) = &self.description {
+            writeln!(out, "description={}", shell_quote(description))?;
+        }
+        writeln!(out, "command={}", shell_quote(&self.command))?;
+        if !self.args.is_empty() {
+            let args: Vec<String> = self.args.iter().map(|arg| shell_quote(arg)).collect();
+            writeln!(out, "command_args={}", shell_quote(&args.join(" ")))?;
+        }
+        writeln!(out, "command_background=true")?;
+        writeln!(out, "pidfile=\"/run/${{RC_SVCNAME}}.pid\"")?;
+        if let Some(user) = &self.user {
+            writeln!(out, "command_user={}", shell_quote(user))?;
+        }
+        if let Some(directory) = &self.directory {
+            writeln!(out, "directory={}", shell_quote(directory))?;
+        }
+        if let Some(log_file) = &self.log_file {
+            writeln!(out, "output_log={}", shell_quote(log_file))?;
+            writeln!(out, "error_log={}", shell_quote(log_file))?;
+        }
+        if !self.need.is_empty() || !self.after.is_empty() {
+            writeln!(out)?;
+            writeln!(out, "depend() {{")?;
+            if !self.need.is_empty() {
+                writeln!(out, "    need {}", self.need.join(" "))?;
+            }
+            if !self.after.is_empty() {
+                writeln!(out, "    after {}", self.after.join(" "))?;
+            }
+            writeln!(out, "}}")?;
+        }
+        Ok(out)
+    }
+}
+
+fn validate_service_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-')
+    {
+        bail!("invalid service name: {name:?}");
+    }
+    Ok(())
+}
+
+impl Session {
+    /// Manage OpenRC services (Alpine).
+    pub fn openrc(&mut self) -> OpenRc<'_> {
+        OpenRc(self)
+    }
+}
+
+/// Provides access to OpenRC service management.
+pub struct OpenRc<'a>(&'a mut Session);
+
+impl OpenRc<'_> {
+    /// Write an init script to `/etc/init.d/`. If the script was changed and the service
+    /// is running, it's restarted. Returns `true` if the script was changed.
+    pub async fn install_script(&mut self, script: &OpenRcScript) -> Result<bool> {
+        self.require_openrc().await?;
+        let path = format!("/etc/init.d/{}", script.name);
+        let changed = self.0.update_file(&path, script.render()?).await?;
+        if changed {
+            self.0
+                .command(["chmod", "755", &path])
+                .hide_command()
+                .run()
+                .await?;
+            if self.is_started(&script.name).await? {
+                self.restart(&script.name).await?;
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Check if a service is started.
+    pub async fn is_started(&mut self, service: &str) -> Result<bool> {
+        validate_service_name(service)?;
+        let code = self
+            .0
+            .command(["rc-service", service, "status"])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        Ok(code == 0)
+    }
+
+    /// Start a service.
+    pub async fn start(&mut self, service: &str) -> Result<()> {
+        self.rc_service(service, "start").await
+    }
+
+    /// Stop a service.
+    pub async fn stop(&mut self, service: &str) -> Result<()> {
+        self.rc_service(service, "stop").await
+    }
+
+    /// Restart a service.
+    pub async fn restart(&mut self, service: &str) -> Result<()> {
+        self.rc_service(service, "restart").await
+    }
+
+    /// Reload configuration of a service. Not all services support this.
+    pub async fn reload(&mut self, service: &str) -> Result<()> {
+        self.rc_service(service, "reload").await
+    }
+
+    /// Check if a service is added to `runlevel`.
+    pub async fn is_enabled(&mut self, service: &str, runlevel: &str) -> Result<bool> {
+        validate_service_name(service)?;
+        validate_service_name(runlevel)?;
+        let output = self
+            .0
+            .command(["rc-update", "show", runlevel])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?;
+        let enabled = output
+            .stdout_lines()
+            .any(|line| line.split('|').next().map(str::trim) == Some(service));
+        Ok(enabled)
+    }
+
+    /// Add a service to `runlevel` (usually `default`) so that it's started at boot.
+    /// Returns `true` if the service was added.
+    pub async fn enable(&mut self, service: &str, runlevel: &str) -> Result<bool> {
+        self.require_openrc().await?;
+        if self.is_enabled(service, runlevel).await? {
+            return Ok(false);
+        }
+        self.0
+            .command(["rc-update", "add", service, runlevel])
+            .run()
+            .await?;
+        info!("added {service} to runlevel {runlevel}");
+        Ok(true)
+    }
+
+    /// Remove a service from `runlevel`. Returns `true` if the service was removed.
+    pub async fn disable(&mut self, service: &str, runlevel: &str) -> Result<bool> {
+        self.require_openrc().await?;
+        if !self.is_enabled(service, runlevel).await? {
+            return Ok(false);
+        }
+        self.0
+            .command(["rc-update", "del", service, runlevel])
+            .run()
+            .await?;
+        info!("removed {service} from runlevel {runlevel}");
+        Ok(true)
+    }
+
+    async fn rc_service(&mut self, service: &str, action: &str) -> Result<()> {
+        self.require_openrc().await?;
+        validate_service_name(service)?;
+        self.0
+            .command(["rc-service", service, action])
+            .run()
+            .await?;
+        Ok(())
+    }
+
+    async fn require_openrc(&mut self) -> Result<()> {
+        self.0.require_os_for("OpenRC", &[Distro::Alpine]).await
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+
+use anyhow::{bail, Context, Result};
+
+use crate::Session;
+
+/// A Linux distribution.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Distro {
+    /// Debian.
+    Debian,
+    /// Ubuntu.
+    Ubuntu,
+    /// Red Hat Enterprise Linux.
+    Rhel,
+    /// CentOS.
+    CentOs,
+    /// Rocky Linux.
+    Rocky,
+    /// AlmaLinux.
+    AlmaLinux,
+    /// Fedora.
+    Fedora,
+    /// openSUSE or SUSE Linux Enterprise.
+    OpenSuse,
+    /// Alpine Linux.
+    Alpine,
+    /// Arch Linux.
+    Arch,
+    /// Another distribution, identified by its `ID` from `/etc/os-release`.
+    Other(String),
+}
+
+impl Distro {
+    /// Convert an `ID` or `ID_LIKE` value from `/etc/os-release`.
+    pub fn from_id(id: &str) -> Self {
+        match id {
+            "debian" => Self::Debian,
+            "ubuntu" => Self::Ubuntu,
+            "rhel" => Self::Rhel,
+            "centos" => Self::CentOs,
+            "rocky" => Self::Rocky,
+            "almalinux" => Self::AlmaLinux,
+            "fedora" => Self::Fedora,
+            "opensuse" | "opensuse-leap" | "opensuse-tumbleweed" | "sles" | "suse" => {
+                Self::OpenSuse
+            }
+            "alpine" => Self::Alpine,
+            "arch" => Self::Arch,
+            _ => Self::Other(id.into()),
+        }
+    }
+}
+
+impl fmt::Display for Distro {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Debian => "Debian",
+            Self::Ubuntu => "Ubuntu",
+            Self::Rhel => "RHEL",
+            Self::CentOs => "CentOS",
+            Self::Rocky => "Rocky Linux",
+            Self::AlmaLinux => "AlmaLinux",
+            Self::Fedora => "Fedora",
+            Self::OpenSuse => "openSUSE",
+            Self::Alpine => "Alpine Linux",
+            Self::Arch => "Arch Linux",
+            Self::Other(id) => id,
+        };
+        f.write_str(name)
+    }
+}
+
+/// Information from `/etc/os-release`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OsRelease {
+    /// Distribution (`ID`).
+    pub distro: Distro,
+    /// Related distributions (`ID_LIKE`).
+    pub like: Vec<Distro>,
+    /// Version, e.g. `24.04` or `9.3` (`VERSION_ID`).
+    pub version_id: Option<String>,
+    /// Release codename, e.g. `noble` (`VERSION_CODENAME`).
+    pub version_codename: Option<String>,
+    /// Human-readable name, e.g. `Ubuntu 24.04 LTS` (`PRETTY_NAME`).
+    pub pretty_name: String,
+}
+
+impl OsRelease {
+    /// Parse the contents of `/etc/os-release`.
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut id = None;
+        let mut like = Vec::new();
+        let mut version_id = None;
+        let mut version_codename = None;
+        let mut pretty_name = None;
+        for line in content.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            match key.trim() {
+                "ID" => id = Some(value.to_string()),
+                "ID_LIKE" => like = value.split_whitespace().map(Distro::from_id).collect(),
+                "VERSION_ID" => version_id = Some(value.to_string()),
+                "VERSION_CODENAME" if !value.is_empty() => {
+                    version_codename = Some(value.to_string())
+                }
+                "PRETTY_NAME" => pretty_name = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        let id = id.context("missing ID in os-release")?;
+        Ok(Self {
+            distro: Distro::from_id(&id),
+            like,
+            version_id,
+            version_codename,
+            pretty_name: pretty_name.unwrap_or(id),
+        })
+    }
+
+    /// Check if the OS is one of `distros` or is derived from one of them.
+    pub fn is_one_of(&self, distros: &[Distro]) -> bool {
+        distros
+            .iter()
+            .any(|distro| *distro == self.distro || self.like.contains(distro))
+    }
+}
+
+impl Session {
+    /// Fetch information about the remote OS from `/etc/os-release`.
+    /// The result is cached for the session.
+    pub async fn os_release(&mut self) -> Result<OsRelease> {
+        if let Some(os) = self.cache().get::<OsRelease>() {
+            return Ok(os.clone());
+        }
+        let content = self
+            .read_file_if_exists("/etc/os-release")
+            .await?
+            .context("missing /etc/os-release")?;
+        let os = OsRelease::parse(&content)?;
+        self.cache().insert(os.clone());
+        Ok(os)
+    }
+
+    /// Fail unless the remote OS is one of `distros` or is derived from one of them.
+    pub async fn require_os(&mut self, distros: &[Distro]) -> Result<()> {
+        self.require_os_for("this operation", distros).await
+    }
+
+    pub(crate) async fn require_os_for(&mut self, what: &str, distros: &[Distro]) -> Result<()> {
+        let os = self.os_release().await?;
+        if !os.is_one_of(distros) {
+            let names = distros
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" or ");
+            bail!("{what} needs {names}, but host is {}", os.pretty_name);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use anyhow::{bail, Result};
+use log::warn;
+
+use crate::{recipes::dnf::DNF_DISTROS, Distro, Session};
+
+/// A package upgraded by `Session::apply_security_updates`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PatchedPackage {
+    /// Package name.
+    pub name: String,
+    /// Installed version after the upgrade.
+    pub version: String,
+    /// IDs of the fixed CVEs. Only reported by dnf; empty on Debian and Ubuntu.
+    pub cves: Vec<String>,
+}
+
+/// Result of `Session::apply_security_updates`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecurityPatchReport {
+    /// Upgraded packages.
+    pub patched: Vec<PatchedPackage>,
+    /// Whether a reboot is needed to finish patching, e.g. after a kernel update.
+    pub reboot_required: bool,
+}
+
+impl Session {
+    /// Apply security updates only, using apt on Debian and Ubuntu or dnf on
+    /// RHEL-based distributions and Fedora, and check if a reboot is needed afterwards.
+    pub async fn apply_security_updates(&mut self) -> Result<SecurityPatchReport> {
+        let os = self.os_release().await?;
+        let patched = if os.is_one_of(&[Distro::Debian, Distro::Ubuntu]) {
+            self.apt()
+                .upgrade_security()
+                .await?
+                .into_iter()
+                .map(|package| PatchedPackage {
+                    name: package.name,
+                    version: package.candidate_version,
+                    cves: Vec::new(),
+                })
+                .collect()
+        } else if os.is_one_of(DNF_DISTROS) {
+            let updates = self.dnf().security_updates().await?;
+            if !updates.is_empty() {
+                self.dnf().upgrade_security().await?;
+            }
+            updates
+                .into_iter()
+                .map(|update| PatchedPackage {
+                    name: update.name,
+                    version: update.version,
+                    cves: update.cves,
+                })
+                .collect()
+        } else {
+            bail!("security updates are not supported on {}", os.pretty_name);
+        };
+        let reboot_required = self.reboot_required().await?;
+        if reboot_required {
+            warn!("reboot is required to finish patching");
+        }
+        Ok(SecurityPatchReport {
+            patched,
+            reboot_required,
+        })
+    }
+
+    /// Check if the host needs a reboot to apply installed updates.
+    ///
+    /// Uses `/var/run/reboot-required` on Debian and Ubuntu and `needs-restarting -r`
+    /// (from `dnf-utils`) on dnf-based distributions.
+    pub async fn reboot_required(&mut self) -> Result<bool> {
+        let os = self.os_release().await?;
+        if os.is_one_of(&[Distro::Debian, Distro::Ubuntu]) {
+            self.path_exists("/var/run/reboot-required").await
+        } else if os.is_one_of(DNF_DISTROS) {
+            let code = self
+                .command(["needs-restarting", "--reboothint"])
+                .hide_command()
+                .hide_all_output()
+                .exit_code()
+                .await?;
+            match code {
+                0 => Ok(false),
+                1 => Ok(true),
+                _ => bail!("needs-restarting failed with exit code {code}"),
+            }
+        } else {
+            bail!("reboot check is not supported on {}", os.pretty_name);
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context, Result};
+use log::info;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{Command, Session};
+
+/// An app entry of a pm2 ecosystem file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Pm2App {
+    /// App name used in pm2 commands.
+    pub name: String,
+    /// Script to run, relative to `cwd` or absolute.
+    pub script: String,
+    /// Working directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// Arguments passed to the script.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Number of instances. More than one enables cluster mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instances: Option<u32>,
+    /// Restart the app if it uses more memory than this (e.g. `500M`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_memory_restart: Option<String>,
+    /// Environment variables of the app.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+}
+
+impl Pm2App {
+    /// Create an app entry with default settings.
+    pub fn new(name: impl Into<String>, script: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            script: script.into(),
+            cwd: None,
+            args: Vec::new(),
+            instances: None,
+            max_memory_restart: None,
+            env: BTreeMap::new(),
+        }
+    }
+
+    /// Render an ecosystem file for `apps` in the JSON format accepted by pm2.
+    pub fn render_ecosystem(apps: &[Pm2App]) -> Result<String> {
+        let mut apps_json = Vec::new();
+        for app in apps {
+            let mut value = serde_json::to_value(app)?;
+            if app.instances.is_some_and(|n| n > 1) {
+                value["exec_mode"] = json!("cluster");
+            }
+            apps_json.push(value);
+        }
+        let mut out = serde_json::to_string_pretty(&json!({ "apps": apps_json }))?;
+        out.push('\n');
+        Ok(out)
+    }
+}
+
+/// Information about a process managed by pm2, parsed from `pm2 jlist`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pm2Process {
+    /// App name.
+    pub name: String,
+    /// pm2 process ID.
+    pub pm_id: u32,
+    /// OS process ID, if the process is running.
+    pub pid: Option<u32>,
+    /// Status, e.g. `online`, `stopped` or `errored`.
+    pub status: String,
+    /// Number of restarts.
+    pub restarts: u32,
+    /// Memory usage in bytes.
+    pub memory: u64,
+    /// CPU usage in percent.
+    pub cpu: f64,
+}
+
+impl Pm2Process {
+    /// Check if the process is running.
+    pub fn is_online(&self) -> bool {
+        self.status == "online"
+    }
+
+    /// Parse `pm2 jlist` output.
+    pub fn parse_jlist(output: &str) -> Result<Vec<Self>> {
+        let items: Vec<Value> =
+            serde_json::from_str(output.trim()).context("failed to parse pm2 jlist output")?;
+        items
+            .iter()
+            .map(|item| {
+                let env = &item["pm2_env"];
+                Ok(Self {
+                    name: item["name"]
+                        .as_str()
+                        .context("missing name in pm2 jlist output")?
+                        .into(),
+                    pm_id: item["pm_id"]
+                        .as_u64()
+                        .context("missing pm_id in pm2 jlist output")?
+                        .try_into()?,
+                    pid: item["pid"]
+                        .as_u64()
+                        .filter(|pid| *pid != 0)
+                        .map(u32::try_from)
+                        .transpose()?,
+                    status: env["status"].as_str().unwrap_or_default().into(),
+                    restarts: env["restart_time"].as_u64().unwrap_or(0).try_into()?,
+                    memory: item["monit"]["memory"].as_u64().unwrap_or(0),
+                    cpu: item["monit"]["cpu"].as_f64().unwrap_or(0.0),
+                })
+            })
+            .collect()
+    }
+}
+
+impl Session {
+    /// Manage Node.js apps with [pm2](https://pm2.keymetrics.io) for the specified user
+    /// (or the current user).
+    pub fn pm2(&mut self, user: Option<&str>) -> Pm2<'_> {
+        Pm2 {
+            session: self,
+            user: user.map(Into::into),
+        }
+    }
+}
+
+/// Provides access to the pm2 process manager.
+pub struct Pm2<'a> {
+    session: &'a mut Session,
+    user: Option<String>,
+}
+
+impl Pm2<'_> {
+    /// Install pm2 globally with `npm` unless it's already installed.
+    /// Node.js and npm are installed from the distribution repositories if `npm` is missing.
+    pub async fn ensure_installed(&mut self) -> Result<()> {
+        let code = self
+            .session
+            .command(["which", "pm2"])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        if code == 0 {
+            return Ok(());
+        }
+        let code = self
+            .session
+            .command(["which", "npm"])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        if code != 0 {
+            self.session.apt().install(&["nodejs", "npm"]).await?;
+        }
+        self.session
+            .command(["npm", "install", "--global", "pm2"])
+            .run()
+            .await?;
+        info!("installed pm2");
+        Ok(())
+    }
+
+    /// Make systemd resurrect the saved pm2 process list on boot (`pm2 startup`).
+    /// Returns `true` if the startup unit was installed.
+    pub async fn enable_startup(&mut self) -> Result<bool> {
+        let user = match &self.user {
+            Some(user) => user.clone(),
+            None => self.session.current_user().await?,
+        };
+        let unit = format!("pm2-{user}");
+        let code = self
+            .session
+            .command(["systemctl", "is-enabled", "--quiet", &unit])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        if code == 0 {
+            return Ok(false);
+        }
+        let home = self.session.home_dir(Some(&user)).await?;
+        self.session
+            .command(["pm2", "startup", "systemd", "-u", &user, "--hp", &home])
+            .run()
+            .await?;
+        Ok(true)
+    }
+
+    /// Write the ecosystem file for `apps` to `path`, start new apps and reload
+    /// existing ones (`pm2 startOrReload`), then save the process list so that it's
+    /// restored on boot. Returns `true` if the ecosystem file was changed.
+    ///
+    /// The file must have a `.json` extension for pm2 to recognize it.
+    pub async fn deploy(&mut self, path: &str, apps: &[Pm2App]) -> Result<bool> {
+        if !path.ends_with(".json") {
+            bail!("ecosystem file path must end with .json: {path:?}");
+        }
+        let changed = self
+            .session
+            .update_file(path, Pm2App::render_ecosystem(apps)?)
+            .await?;
+        if let (Some(user), true) = (&self.user, changed) {
+            self.session
+                .command(["chown", &format!("{user}:"), "--", path])
+                .hide_command()
+                .run()
+                .await?;
+        }
+        self.command(["startOrReload", path, "--update-env"])
+            .run()
+            .await?;
+        self.command(["save"]).run().await?;
+        Ok(changed)
+    }
+
+    /// Gracefully reload an app without downtime (in cluster mode).
+    pub async fn reload(&mut self, name: &str) -> Result<()> {
+        self.command(["reload", name]).run().await?;
+        Ok(())
+    }
+
+    /// Stop and remove an app, then save the process list.
+    pub async fn delete(&mut self, name: &str) -> Result<()> {
+        self.command(["delete", name]).run().await?;
+        self.command(["save"]).run().await?;
+        Ok(())
+    }
+
+    /// List processes managed by pm2.
+    pub async fn list(&mut self) -> Result<Vec<Pm2Process>> {
+        let output = self
+            .command(["jlist"])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?;
+        Pm2Process::parse_jlist(&output.stdout)
+    }
+
+    /// Find a process by app name.
+    pub async fn process(&mut self, name: &str) -> Result<Option<Pm2Process>> {
+        Ok(self.list().await?.into_iter().find(|p| p.name == name))
+    }
+
+    fn command<'a, S: AsRef<str>, I: IntoIterator<Item = S>>(&'a self, args: I) -> Command<'a> {
+        self.session
+            .command(["pm2"])
+            .args(args)
+            .user(self.user.as_deref())
+    }
+}
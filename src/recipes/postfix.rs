@@ -0,0 +1,203 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use log::info;
+use serde_json::Value;
+
+use crate::Session;
+
+const SASL_PASSWORD_PATH: &str = "/etc/postfix/sasl_passwd";
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Postfix configuration for a satellite system that relays all mail through
+/// another SMTP server and doesn't accept mail from the network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostfixRelayConfig {
+    /// Host name used in outgoing mail (`myhostname`).
+    pub hostname: String,
+    /// Relay host, e.g. `[smtp.example.com]:587`.
+    pub relay_host: String,
+    /// SASL username and password for the relay host.
+    pub credentials: Option<(String, String)>,
+    /// Require TLS for connections to the relay host. If `false`, TLS is used
+    /// when available.
+    pub require_tls: bool,
+}
+
+impl Session {
+    /// Manage postfix.
+    pub fn postfix(&mut self) -> Postfix<'_> {
+        Postfix(self)
+    }
+}
+
+/// Provides access to postfix management.
+pub struct Postfix<'a>(&'a mut Session);
+
+impl Postfix<'_> {
+    /// Install postfix as a satellite system without interactive prompts.
+    pub async fn ensure_installed(&mut self) -> Result<()> {
+        if self.0.apt().is_package_installed("postfix").await? {
+            return Ok(());
+        }
+        self.0
+            .apt()
+            .preseed(
+                "postfix",
+                &[("postfix/main_mailer_type", "select", "Satellite system")],
+            )
+            .await?;
+        self.0.apt().install(&["postfix", "libsasl2-modules"]).await
+    }
+
+    /// Configure postfix as a satellite relay and restart it if the configuration was changed.
+    /// Credentials are stored in `/etc/postfix/sasl_passwd` (readable by root only) and
+    /// are not logged. Returns `true` if the configuration was changed.
+    pub async fn configure_relay(&mut self, config: &PostfixRelayConfig) -> Result<bool> {
+        for value in [&config.hostname, &config.relay_host] {
+            if value.is_empty() || value.contains(char::is_whitespace) {
+                bail!("invalid postfix setting: {value:?}");
+            }
+        }
+        let mut settings = vec![
+            ("myhostname", config.hostname.as_str()),
+            ("relayhost", config.relay_host.as_str()),
+            ("inet_interfaces", "loopback-only"),
+            ("mydestination", ""),
+            (
+                "smtp_tls_security_level",
+                if config.require_tls { "encrypt" } else { "may" },
+            ),
+            ("smtp_tls_CAfile", "/etc/ssl/certs/ca-certificates.crt"),
+        ];
+        let mut changed = false;
+        if let Some((user, password)) = &config.credentials {
+            if user.contains(char::is_whitespace) || password.contains('\n') {
+                bail!("invalid SASL credentials");
+            }
+            settings.extend([
+                ("smtp_sasl_auth_enable", "yes"),
+                ("smtp_sasl_password_maps", "hash:/etc/postfix/sasl_passwd"),
+                ("smtp_sasl_security_options", "noanonymous"),
+            ]);
+            if !self.0.path_exists(SASL_PASSWORD_PATH).await? {
+                self.0
+                    .command(["install", "-m", "600", "/dev/null", SASL_PASSWORD_PATH])
+                    .hide_command()
+                    .run()
+                    .await?;
+            }
+            let content = format!("{} {user}:{password}\n", config.relay_host);
+            if self.0.update_file(SASL_PASSWORD_PATH, content).await? {
+                self.0
+                    .command(["postmap", SASL_PASSWORD_PATH])
+                    .hide_command()
+                    .run()
+                    .await?;
+                self.0
+                    .command(["chmod", "600", &format!("{SASL_PASSWORD_PATH}.db")])
+                    .hide_command()
+                    .run()
+                    .await?;
+                changed = true;
+            }
+        } else {
+            settings.push(("smtp_sasl_auth_enable", "no"));
+        }
+        for (name, value) in settings {
+            changed |= self.set_parameter(name, value).await?;
+        }
+        if changed {
+            self.0
+                .command(["systemctl", "restart", "postfix"])
+                .run()
+                .await?;
+        }
+        Ok(changed)
+    }
+
+    /// Set a `main.cf` parameter with `postconf`. Returns `true` if the value was changed.
+    pub async fn set_parameter(&mut self, name: &str, value: &str) -> Result<bool> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            bail!("invalid postfix parameter name: {name:?}");
+        }
+        let current = self
+            .0
+            .command(["postconf", "-h", name])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?;
+        if current.stdout_trimmed() == value {
+            return Ok(false);
+        }
+        self.0
+            .command(["postconf", "-e", &format!("{name}={value}")])
+            .run()
+            .await?;
+        Ok(true)
+    }
+
+    /// Send a test message to `recipient` and wait until it leaves the mail queue.
+    /// Fails with the deferral reason if the relay doesn't accept it within a minute.
+    pub async fn send_test_mail(&mut self, recipient: &str) -> Result<()> {
+        if recipient.is_empty() || recipient.contains(char::is_whitespace) {
+            bail!("invalid recipient: {recipient:?}");
+        }
+        let hostname = self
+            .0
+            .command(["hostname", "--fqdn"])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?
+            .stdout_trimmed()
+            .to_string();
+        self.0
+            .command([
+                "bash",
+                "-c",
+                r#"printf 'To: %s\nSubject: Test message from %s\n\nThis is a test message sent by roguewave.\n' "$1" "$2" | /usr/sbin/sendmail -- "$1""#,
+                "--",
+                recipient,
+                &hostname,
+            ])
+            .run()
+            .await?;
+        let started = Instant::now();
+        loop {
+            let output = self
+                .0
+                .command(["postqueue", "-j"])
+                .hide_command()
+                .hide_all_output()
+                .run()
+                .await?;
+            let pending: Vec<Value> = output
+                .stdout_lines()
+                .map(serde_json::from_str)
+                .collect::<Result<_, _>>()
+                .context("failed to parse postqueue output")?;
+            let pending: Vec<&Value> = pending
+                .iter()
+                .filter(|message| {
+                    message["recipients"]
+                        .as_array()
+                        .is_some_and(|r| r.iter().any(|r| r["address"] == recipient))
+                })
+                .collect();
+            if pending.is_empty() {
+                info!("test message to {recipient} was accepted by the relay");
+                return Ok(());
+            }
+            if started.elapsed() > QUEUE_TIMEOUT {
+                let reasons: Vec<&str> = pending
+                    .iter()
+                    .filter_map(|message| message["recipients"][0]["delay_reason"].as_str())
+                    .collect();
+                bail!("test message is still queued: {}", reasons.join("; "));
+            }
+            tokio::time::sleep(Duration::from_secs(2)).await;
+        }
+    }
+}
@@ -1,8 +1,13 @@
-use anyhow::{bail, Result};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
 use format_sql_query::QuotedData;
+use log::info;
 
 use crate::Session;
 
+const READY_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 impl Session {
     /// Execute PostgreSQL commands.
     pub fn postgres(&mut self) -> Postgres {
@@ -13,6 +18,25 @@ impl Session {
 /// Provides access to PostgreSQL commands.
 pub struct Postgres<'a>(&'a mut Session);
 
+/// Settings for `Postgres::setup_streaming_replication`.
+#[derive(Debug, Clone)]
+pub struct ReplicationConfig {
+    /// Address of the primary as reachable from the standby.
+    pub primary_address: String,
+    /// PostgreSQL port of the primary.
+    pub primary_port: u16,
+    /// Address of the standby as seen by the primary, in CIDR notation (e.g. `10.0.0.2/32`).
+    /// Used for the `pg_hba.conf` entry.
+    pub standby_address: String,
+    /// Name of the replication user.
+    pub user: String,
+    /// Password of the replication user.
+    pub password: String,
+    /// Value of `listen_addresses` on the primary (e.g. `localhost,10.0.0.1`).
+    /// If `None`, the primary listens on `localhost` and `primary_address`.
+    pub listen_addresses: Option<String>,
+}
+
 impl<'a> Postgres<'a> {
     /// Create a PostgreSQL user with the specified password.
     ///
@@ -124,4 +148,355 @@ impl<'a> Postgres<'a> {
             .await?;
         Ok(())
     }
+
+    /// Create an extension in `database` unless it already exists.
+    pub async fn ensure_extension(&mut self, database: &str, extension: &str) -> Result<()> {
+        validate_database_name(database)?;
+        if !extension
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            bail!("invalid postgres extension name");
+        }
+        let exists = !self
+            .query(
+                database,
+                &format!(
+                    "SELECT 1 FROM pg_extension WHERE extname = {}",
+                    QuotedData(extension)
+                ),
+            )
+            .await?
+            .is_empty();
+        if !exists {
+            self.execute(
+                database,
+                &format!("CREATE EXTENSION IF NOT EXISTS \"{}\"", extension),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Create a schema in `database` owned by `owner`. If the schema already exists,
+    /// its owner is changed to `owner` if necessary.
+    pub async fn ensure_schema(&mut self, database: &str, name: &str, owner: &str) -> Result<()> {
+        validate_database_name(database)?;
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            bail!("invalid postgres schema name");
+        }
+        validate_user_name(owner)?;
+        // Unquoted identifiers in DDL are folded to lower case, so catalog names are compared
+        // with folded names.
+        let name = name.to_ascii_lowercase();
+        let owner = owner.to_ascii_lowercase();
+        let current_owner = self
+            .query(
+                database,
+                &format!(
+                    "SELECT pg_get_userbyid(nspowner) FROM pg_namespace WHERE nspname = {}",
+                    QuotedData(&name)
+                ),
+            )
+            .await?;
+        if current_owner.is_empty() {
+            self.execute(
+                database,
+                &format!("CREATE SCHEMA {} AUTHORIZATION {}", name, owner),
+            )
+            .await?;
+        } else if current_owner != owner {
+            self.execute(
+                database,
+                &format!("ALTER SCHEMA {} OWNER TO {}", name, owner),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Change the owner of `database` to `owner` if necessary.
+    pub async fn set_database_owner(&mut self, database: &str, owner: &str) -> Result<()> {
+        validate_database_name(database)?;
+        validate_user_name(owner)?;
+        // Unquoted identifiers in DDL are folded to lower case, so catalog names are compared
+        // with folded names.
+        let owner = owner.to_ascii_lowercase();
+        let current_owner = self
+            .query(
+                "postgres",
+                &format!(
+                    "SELECT pg_get_userbyid(datdba) FROM pg_database WHERE datname = {}",
+                    QuotedData(&database.to_ascii_lowercase())
+                ),
+            )
+            .await?;
+        if current_owner.is_empty() {
+            bail!("postgres database {database:?} doesn't exist");
+        }
+        if current_owner != owner {
+            self.execute(
+                "postgres",
+                &format!("ALTER DATABASE {} OWNER TO {}", database, owner),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Configure this host as a streaming replication primary and initialize `standby`
+    /// as its hot standby using `pg_basebackup`.
+    ///
+    /// On the primary, WAL settings and `listen_addresses` (see `ReplicationConfig`) are
+    /// adjusted, the replication user is created and a `pg_hba.conf` entry is added
+    /// for the standby. On the standby, the existing data directory is **deleted** and
+    /// replaced by a base backup of the primary. If the standby is already initialized
+    /// as a standby, it's left unchanged.
+    pub async fn setup_streaming_replication(
+        &mut self,
+        standby: &mut Session,
+        config: &ReplicationConfig,
+    ) -> Result<()> {
+        self.configure_primary(config).await?;
+        standby.postgres().init_standby(config).await
+    }
+
+    async fn configure_primary(&mut self, config: &ReplicationConfig) -> Result<()> {
+        validate_user_name(&config.user)?;
+        if config.standby_address.chars().any(|c| c.is_whitespace()) {
+            bail!("invalid standby address");
+        }
+        let listen_addresses = match &config.listen_addresses {
+            Some(addresses) => addresses.clone(),
+            None => format!("localhost,{}", config.primary_address),
+        };
+        let mut restart_required = false;
+        for (name, value) in [
+            ("wal_level", "replica"),
+            ("listen_addresses", listen_addresses.as_str()),
+        ] {
+            if self.query("postgres", &format!("SHOW {name}")).await? != value {
+                self.execute(
+                    "postgres",
+                    &format!("ALTER SYSTEM SET {name} = {}", QuotedData(value)),
+                )
+                .await?;
+                restart_required = true;
+            }
+        }
+        if self
+            .query("postgres", "SHOW max_wal_senders")
+            .await?
+            .parse::<u32>()?
+            == 0
+        {
+            self.execute("postgres", "ALTER SYSTEM SET max_wal_senders = 10")
+                .await?;
+            restart_required = true;
+        }
+
+        self.create_user_with_password(&config.user, &config.password)
+            .await?;
+        self.execute(
+            "postgres",
+            &format!("ALTER ROLE {} WITH REPLICATION", config.user),
+        )
+        .await?;
+
+        let hba_path = self.query("postgres", "SHOW hba_file").await?;
+        let hba = self
+            .0
+            .read_file_if_exists(&hba_path)
+            .await?
+            .unwrap_or_default();
+        let entry = format!(
+            "host replication {} {} scram-sha-256",
+            config.user, config.standby_address
+        );
+        if !hba
+            .lines()
+            .any(|line| line.split_whitespace().eq(entry.split_whitespace()))
+        {
+            let mut hba = hba;
+            if !hba.is_empty() && !hba.ends_with('\n') {
+                hba.push('\n');
+            }
+            hba.push_str(&entry);
+            hba.push('\n');
+            self.0.update_file(&hba_path, hba).await?;
+            if !restart_required {
+                self.execute("postgres", "SELECT pg_reload_conf()").await?;
+            }
+        }
+        if restart_required {
+            self.0
+                .command(["systemctl", "restart", "postgresql"])
+                .run()
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn init_standby(&mut self, config: &ReplicationConfig) -> Result<()> {
+        let data_directory = self.query("postgres", "SHOW data_directory").await?;
+        if data_directory.is_empty() {
+            bail!("failed to find postgres data directory");
+        }
+        if self
+            .0
+            .path_exists(format!("{data_directory}/standby.signal"))
+            .await?
+        {
+            info!("postgres is already configured as a standby");
+            return Ok(());
+        }
+        self.0
+            .command(["systemctl", "stop", "postgresql"])
+            .run()
+            .await?;
+        self.0
+            .command(["find", &data_directory, "-mindepth", "1", "-delete"])
+            .run()
+            .await?;
+        self.0
+            .command(["env"])
+            .redacted_arg(
+                format!("PGPASSWORD={}", config.password),
+                "PGPASSWORD=<redacted>",
+            )
+            .args([
+                "pg_basebackup",
+                "--host",
+                &config.primary_address,
+                "--port",
+                &config.primary_port.to_string(),
+                "--username",
+                &config.user,
+                "--pgdata",
+                &data_directory,
+                "--wal-method=stream",
+                "--write-recovery-conf",
+                "--no-password",
+            ])
+            .prepend_args(["sudo", "--user", "postgres"])
+            .run()
+            .await?;
+        self.0
+            .command(["systemctl", "start", "postgresql"])
+            .run()
+            .await?;
+        info!("postgres standby initialized");
+        Ok(())
+    }
+
+    /// Wait until the server accepts connections (`pg_isready`).
+    pub async fn wait_ready(&mut self, timeout: Duration) -> Result<()> {
+        let started = Instant::now();
+        let mut logged_wait = false;
+        loop {
+            let code = self
+                .0
+                .command(["pg_isready", "--quiet"])
+                .hide_command()
+                .hide_all_output()
+                .exit_code()
+                .await?;
+            if code == 0 {
+                return Ok(());
+            }
+            if started.elapsed() >= timeout {
+                bail!("timed out waiting for postgres to become ready");
+            }
+            if !logged_wait {
+                info!("waiting for postgres to become ready");
+                logged_wait = true;
+            }
+            tokio::time::sleep(READY_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Fetch the server version, e.g. `16.3 (Ubuntu 16.3-1.pgdg22.04+1)`.
+    pub async fn version(&mut self) -> Result<String> {
+        self.query("postgres", "SHOW server_version").await
+    }
+
+    /// Fetch the number of client connections to the server.
+    pub async fn active_connections(&mut self) -> Result<u64> {
+        self.query(
+            "postgres",
+            "SELECT count(*) FROM pg_stat_activity WHERE backend_type = 'client backend'",
+        )
+        .await?
+        .parse()
+        .context("failed to parse connection count")
+    }
+
+    /// Fetch the time since the last transaction replayed on a standby.
+    /// Returns `None` if the server is not a standby or hasn't replayed any transactions yet.
+    ///
+    /// Note that on an idle primary this value grows even if the standby is fully caught up.
+    pub async fn replication_lag(&mut self) -> Result<Option<Duration>> {
+        let lag = self
+            .query(
+                "postgres",
+                "SELECT CASE WHEN pg_is_in_recovery() \
+                THEN EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp()) END",
+            )
+            .await?;
+        if lag.is_empty() {
+            return Ok(None);
+        }
+        let seconds: f64 = lag.parse().context("failed to parse replication lag")?;
+        Ok(Some(Duration::from_secs_f64(seconds.max(0.0))))
+    }
+
+    /// Run a query in `database` and return its trimmed unaligned output.
+    async fn query(&self, database: &str, sql: &str) -> Result<String> {
+        Ok(self
+            .0
+            .command([
+                "psql",
+                "--dbname",
+                database,
+                "--tuples-only",
+                "--no-align",
+                "--command",
+                sql,
+            ])
+            .prepend_args(["sudo", "--user", "postgres", "--login"])
+            .hide_command()
+            .hide_stdout()
+            .run()
+            .await?
+            .stdout_trimmed()
+            .to_string())
+    }
+
+    /// Execute a statement in `database`.
+    async fn execute(&self, database: &str, sql: &str) -> Result<()> {
+        self.0
+            .command(["psql", "--dbname", database, "--command", sql])
+            .prepend_args(["sudo", "--user", "postgres", "--login"])
+            .run()
+            .await?;
+        Ok(())
+    }
+}
+
+fn validate_user_name(name: &str) -> Result<()> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        bail!("invalid postgres user name");
+    }
+    Ok(())
+}
+
+fn validate_database_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+    {
+        bail!("invalid postgres database name");
+    }
+    Ok(())
 }
@@ -0,0 +1,129 @@
+use anyhow::{bail, Result};
+
+use crate::{Command, Session};
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+impl Session {
+    /// Prepare a PowerShell script for execution on a Windows host with OpenSSH server.
+    ///
+    /// The script is passed with `-EncodedCommand`, so it doesn't need any escaping for
+    /// the remote shell (whether it's `cmd.exe` or PowerShell). Use [`powershell_quote`]
+    /// to insert values (including paths) into the script. The script runs with
+    /// `$ErrorActionPreference = 'Stop'`, so any error fails the command.
+    pub fn powershell(&self, script: &str) -> Command<'_> {
+        let full_script = format!(
+            "$ErrorActionPreference = 'Stop'\n$ProgressPreference = 'SilentlyContinue'\n{script}"
+        );
+        let utf16: Vec<u8> = full_script
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect();
+        self.raw_command([
+            "powershell",
+            "-NoProfile",
+            "-NonInteractive",
+            "-EncodedCommand",
+        ])
+        .raw_arg_displayed_as(base64(&utf16), format!("{script:?}"))
+    }
+
+    /// Manage packages on a Windows host with Chocolatey.
+    pub fn choco(&mut self) -> Choco<'_> {
+        Choco(self)
+    }
+}
+
+/// Quote a string for use as a literal in a PowerShell script.
+pub fn powershell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Provides access to Chocolatey package management commands.
+///
+/// Requires Chocolatey 2.0 or newer on the remote host.
+pub struct Choco<'a>(&'a mut Session);
+
+impl Choco<'_> {
+    /// Check if a package is installed.
+    pub async fn is_package_installed(&mut self, package: &str) -> Result<bool> {
+        validate_package_name(package)?;
+        let output = self
+            .0
+            .powershell(&format!(
+                "choco list --exact --limit-output {}",
+                powershell_quote(package)
+            ))
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?;
+        let installed = output.stdout_lines().any(|line| {
+            line.split('|')
+                .next()
+                .is_some_and(|name| name.eq_ignore_ascii_case(package))
+        });
+        Ok(installed)
+    }
+
+    /// Install specified packages.
+    pub async fn install(&mut self, packages: &[&str]) -> Result<()> {
+        let mut new_packages = Vec::new();
+        for package in packages {
+            if !self.is_package_installed(package).await? {
+                new_packages.push(powershell_quote(package));
+            }
+        }
+        if !new_packages.is_empty() {
+            self.0
+                .powershell(&format!(
+                    "choco install --yes --no-progress {}",
+                    new_packages.join(" ")
+                ))
+                .run()
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Upgrade all installed packages.
+    pub async fn upgrade_all(&mut self) -> Result<()> {
+        self.0
+            .powershell("choco upgrade all --yes --no-progress")
+            .run()
+            .await?;
+        Ok(())
+    }
+}
+
+fn validate_package_name(package: &str) -> Result<()> {
+    if package.is_empty()
+        || !package
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-')
+    {
+        bail!("invalid package name: {package:?}");
+    }
+    Ok(())
+}
+
+fn base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (u32::from(bytes[0]) << 16) | (u32::from(bytes[1]) << 8) | u32::from(bytes[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,168 @@
+use anyhow::{bail, Context, Result};
+use log::info;
+
+use crate::Session;
+
+/// Whether a quota applies to a user or a group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuotaKind {
+    /// User quota.
+    User,
+    /// Group quota.
+    Group,
+}
+
+impl QuotaKind {
+    fn flag(self) -> &'static str {
+        match self {
+            Self::User => "--user",
+            Self::Group => "--group",
+        }
+    }
+}
+
+/// Disk quota limits. Zero means no limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct QuotaLimits {
+    /// Soft limit on disk space, in KiB.
+    pub block_soft_kib: u64,
+    /// Hard limit on disk space, in KiB.
+    pub block_hard_kib: u64,
+    /// Soft limit on the number of files.
+    pub inode_soft: u64,
+    /// Hard limit on the number of files.
+    pub inode_hard: u64,
+}
+
+/// Disk usage and limits of a user or group.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QuotaUsage {
+    /// User or group name.
+    pub name: String,
+    /// Used disk space, in KiB.
+    pub block_used_kib: u64,
+    /// Number of used files.
+    pub inode_used: u64,
+    /// Configured limits.
+    pub limits: QuotaLimits,
+}
+
+impl Session {
+    /// Manage disk quotas. The filesystem must be mounted with quota options
+    /// (e.g. `usrquota,grpquota`).
+    pub fn quota(&mut self) -> Quota<'_> {
+        Quota(self)
+    }
+}
+
+/// Provides access to disk quota management.
+pub struct Quota<'a>(&'a mut Session);
+
+impl Quota<'_> {
+    /// Install quota tools.
+    pub async fn ensure_installed(&mut self) -> Result<()> {
+        self.0.apt().install(&["quota"]).await
+    }
+
+    /// Set limits for a user or group on `filesystem` (a mount point or device).
+    /// Returns `true` if the limits were changed.
+    pub async fn set(
+        &mut self,
+        kind: QuotaKind,
+        name: &str,
+        filesystem: &str,
+        limits: QuotaLimits,
+    ) -> Result<bool> {
+        if name.is_empty()
+            || name
+                .chars()
+                .any(|c| !(c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-'))
+        {
+            bail!("invalid user or group name: {name:?}");
+        }
+        if self.usage(kind, name, filesystem).await?.map(|u| u.limits) == Some(limits) {
+            return Ok(false);
+        }
+        self.0
+            .command(["setquota", kind.flag(), name])
+            .args([
+                limits.block_soft_kib.to_string(),
+                limits.block_hard_kib.to_string(),
+                limits.inode_soft.to_string(),
+                limits.inode_hard.to_string(),
+            ])
+            .args([filesystem])
+            .run()
+            .await?;
+        info!("updated quota of {name} on {filesystem}");
+        Ok(true)
+    }
+
+    /// Report usage and limits of all users or groups on `filesystem`.
+    pub async fn report(&mut self, kind: QuotaKind, filesystem: &str) -> Result<Vec<QuotaUsage>> {
+        let output = self
+            .0
+            .command(["repquota", kind.flag(), "--output=csv", filesystem])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?;
+        let mut lines = output.stdout_lines();
+        let header: Vec<&str> = lines
+            .next()
+            .context("empty repquota output")?
+            .split(',')
+            .collect();
+        let column = |name: &str| {
+            header
+                .iter()
+                .position(|h| *h == name)
+                .with_context(|| format!("missing {name} column in repquota output"))
+        };
+        let block_used = column("BlockUsed")?;
+        let block_soft = column("BlockSoftLimit")?;
+        let block_hard = column("BlockHardLimit")?;
+        let file_used = column("FileUsed")?;
+        let file_soft = column("FileSoftLimit")?;
+        let file_hard = column("FileHardLimit")?;
+        let mut report = Vec::new();
+        for line in lines.filter(|line| !line.is_empty()) {
+            let fields: Vec<&str> = line.split(',').collect();
+            let number = |index: usize| -> Result<u64> {
+                let value = fields
+                    .get(index)
+                    .context("missing field in repquota output")?;
+                value
+                    .trim_matches('"')
+                    .parse()
+                    .with_context(|| format!("invalid number in repquota output: {value:?}"))
+            };
+            report.push(QuotaUsage {
+                name: fields[0].trim_matches('"').trim_start_matches('#').into(),
+                block_used_kib: number(block_used)?,
+                inode_used: number(file_used)?,
+                limits: QuotaLimits {
+                    block_soft_kib: number(block_soft)?,
+                    block_hard_kib: number(block_hard)?,
+                    inode_soft: number(file_soft)?,
+                    inode_hard: number(file_hard)?,
+                },
+            });
+        }
+        Ok(report)
+    }
+
+    /// Report usage and limits of a single user or group on `filesystem`.
+    pub async fn usage(
+        &mut self,
+        kind: QuotaKind,
+        name: &str,
+        filesystem: &str,
+    ) -> Result<Option<QuotaUsage>> {
+        Ok(self
+            .report(kind, filesystem)
+            .await?
+            .into_iter()
+            .find(|usage| usage.name == name))
+    }
+}
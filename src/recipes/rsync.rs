@@ -1,8 +1,8 @@
-use std::path::Path;
+use std::{path::Path, time::Instant};
 
 use anyhow::{bail, Context};
 
-use crate::{local, Session};
+use crate::{local, recipes::files::path_str, timings::OperationKind, Session};
 
 impl Session {
     /// Upload local files `local_paths` to the remote location `remote_parent_path`.
@@ -20,14 +20,14 @@ impl Session {
         remote_parent_path: impl AsRef<Path>,
         remote_user: Option<&str>,
     ) -> anyhow::Result<()> {
-        if !self
-            .fs
-            .metadata(remote_parent_path.as_ref())
+        let is_dir = self
+            .command(["test", "-d", path_str(remote_parent_path.as_ref())?])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
             .await?
-            .file_type()
-            .context("missing file type for remote_parent_path")?
-            .is_dir()
-        {
+            == 0;
+        if !is_dir {
             bail!(
                 "upload destination {:?} is not a directory",
                 remote_parent_path.as_ref()
@@ -43,7 +43,8 @@ impl Session {
             "--compress",
             "--delete",
         ])
-        .hide_command();
+        .hide_command()
+        .log_prefix(&self.log_prefix);
         if let Some(remote_user) = remote_user {
             if remote_user
                 .chars()
@@ -55,8 +56,11 @@ impl Session {
                 .arg("--rsync-path")
                 .arg(format!("sudo --user {remote_user} rsync"));
         }
+        let mut local_path_strings = Vec::new();
         for arg in local_paths {
-            command = command.arg(arg.as_ref().to_str().context("non-utf8 path")?);
+            let path = arg.as_ref().to_str().context("non-utf8 path")?;
+            command = command.arg(path);
+            local_path_strings.push(path.to_string());
         }
         if let Some(port) = &self.port {
             command = command.args(["--rsh", &format!("ssh -p {port}")]);
@@ -66,17 +70,21 @@ impl Session {
         } else {
             self.destination.clone()
         };
+        let remote_parent_path = remote_parent_path
+            .as_ref()
+            .to_str()
+            .context("non-utf8 path")?;
+        let _permit = self.throttle().await;
+        let started = Instant::now();
         command
-            .arg(format!(
-                "{}:{}",
-                destination,
-                remote_parent_path
-                    .as_ref()
-                    .to_str()
-                    .context("non-utf8 path")?
-            ))
+            .arg(format!("{}:{}", destination, remote_parent_path))
             .run()
             .await?;
+        self.record_timing(
+            OperationKind::Upload,
+            format!("{:?} -> {:?}", local_path_strings, remote_parent_path),
+            started.elapsed(),
+        );
 
         Ok(())
     }
@@ -0,0 +1,200 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use anyhow::{bail, Result};
+use log::info;
+
+use crate::Session;
+
+const CONFIG_PATH: &str = "/etc/samba/smb.conf";
+
+/// A share section in `smb.conf`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SambaShare {
+    /// Share name.
+    pub name: String,
+    /// Shared directory.
+    pub path: String,
+    /// Share description.
+    pub comment: Option<String>,
+    /// Whether clients can only read files.
+    pub read_only: bool,
+    /// Whether the share is listed in the browse list.
+    pub browseable: bool,
+    /// Users (or `@group`s) allowed to connect. Empty means no restriction.
+    pub valid_users: Vec<String>,
+    /// Additional parameters of the section.
+    pub extra: BTreeMap<String, String>,
+}
+
+impl SambaShare {
+    /// Create a writable, browseable share of `path`.
+    pub fn new(name: impl Into<String>, path: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            path: path.into(),
+            comment: None,
+            read_only: false,
+            browseable: true,
+            valid_users: Vec::new(),
+            extra: BTreeMap::new(),
+        }
+    }
+
+    /// Render the share section.
+    pub fn render(&self) -> Result<String> {
+        validate_name(&self.name)?;
+        let yes_no = |value: bool| if value { "yes" } else { "no" };
+        let mut out = String::new();
+        writeln!(out, "[{}]", self.name)?;
+        writeln!(out, "   path = {}", check_value(&self.path)?)?;
+        if let Some(comment) = &self.comment {
+            writeln!(out, "   comment = {}", check_value(comment)?)?;
+        }
+        writeln!(out, "   read only = {}", yes_no(self.read_only))?;
+        writeln!(out, "   browseable = {}", yes_no(self.browseable))?;
+        if !self.valid_users.is_empty() {
+            let users = self.valid_users.join(" ");
+            writeln!(out, "   valid users = {}", check_value(&users)?)?;
+        }
+        for (key, value) in &self.extra {
+            if key.is_empty() || key.contains(['=', '[', ']', '\n']) {
+                bail!("invalid Samba parameter name: {key:?}");
+            }
+            writeln!(out, "   {key} = {}", check_value(value)?)?;
+        }
+        Ok(out)
+    }
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-' || c == '$')
+    {
+        bail!("invalid Samba share name: {name:?}");
+    }
+    Ok(())
+}
+
+fn check_value(value: &str) -> Result<&str> {
+    if value.contains(['\n', '\r']) {
+        bail!("invalid Samba parameter value: {value:?}");
+    }
+    Ok(value)
+}
+
+impl Session {
+    /// Manage Samba shares and users.
+    pub fn samba(&mut self) -> Samba<'_> {
+        Samba(self)
+    }
+}
+
+/// Provides access to Samba management.
+pub struct Samba<'a>(&'a mut Session);
+
+impl Samba<'_> {
+    /// Install Samba.
+    pub async fn ensure_installed(&mut self) -> Result<()> {
+        self.0.apt().install(&["samba"]).await
+    }
+
+    /// Add or update a share section in `smb.conf` and reload Samba if it was changed.
+    /// Returns `true` if the share was changed.
+    pub async fn set_share(&mut self, share: &SambaShare) -> Result<bool> {
+        let section = share.render()?;
+        let changed = self
+            .0
+            .update_managed_block(CONFIG_PATH, &share_marker(&share.name), &section)
+            .await?;
+        if changed {
+            self.reload().await?;
+        }
+        Ok(changed)
+    }
+
+    /// Remove a share previously added by [`set_share`](Self::set_share).
+    /// Returns `true` if the share was removed.
+    pub async fn remove_share(&mut self, name: &str) -> Result<bool> {
+        validate_name(name)?;
+        let changed = self
+            .0
+            .update_managed_block(CONFIG_PATH, &share_marker(name), "")
+            .await?;
+        if changed {
+            self.reload().await?;
+        }
+        Ok(changed)
+    }
+
+    /// Check the config with `testparm` and make the running Samba daemons reload it.
+    pub async fn reload(&mut self) -> Result<()> {
+        self.0
+            .command(["testparm", "--suppress-prompt", CONFIG_PATH])
+            .hide_stdout()
+            .run()
+            .await?;
+        self.0
+            .command(["systemctl", "reload", "smbd"])
+            .run()
+            .await?;
+        info!("reloaded Samba config");
+        Ok(())
+    }
+
+    /// Set the Samba password of an existing system user, adding the user to the Samba
+    /// database if needed. The password is not displayed in logs.
+    pub async fn set_password(&mut self, user: &str, password: &str) -> Result<()> {
+        if user.is_empty()
+            || !user
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-')
+        {
+            bail!("invalid user name: {user:?}");
+        }
+        if password.contains('\n') {
+            bail!("Samba password can't contain line breaks");
+        }
+        self.0
+            .command([
+                "bash",
+                "-c",
+                r#"printf '%s\n%s\n' "$1" "$1" | smbpasswd -a -s "$2" >/dev/null"#,
+                "--",
+            ])
+            .redacted_arg(password, "<redacted>")
+            .arg(user)
+            .run()
+            .await?;
+        info!("updated Samba password of {user}");
+        Ok(())
+    }
+
+    /// Remove a user from the Samba database.
+    pub async fn remove_user(&mut self, user: &str) -> Result<()> {
+        self.0.command(["smbpasswd", "-x", user]).run().await?;
+        Ok(())
+    }
+
+    /// List users in the Samba database.
+    pub async fn users(&mut self) -> Result<Vec<String>> {
+        let output = self
+            .0
+            .command(["pdbedit", "--list"])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?;
+        Ok(output
+            .stdout_lines()
+            .filter_map(|line| line.split(':').next())
+            .filter(|user| !user.is_empty())
+            .map(String::from)
+            .collect())
+    }
+}
+
+fn share_marker(name: &str) -> String {
+    format!("roguewave samba share {name}")
+}
@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use log::info;
+
+use crate::Session;
+
+impl Session {
+    /// Schedule `command` to run once on the remote host after `delay`, using a transient
+    /// systemd timer (`systemd-run --on-active`).
+    ///
+    /// The timer is named `roguewave-{name}`. If a job with the same name is already scheduled,
+    /// it's replaced. The job runs even if the SSH connection is closed, so it can be used
+    /// for delayed reboots or for automatically reverting temporary changes.
+    pub async fn schedule_once<S: AsRef<str>, I: IntoIterator<Item = S>>(
+        &self,
+        name: &str,
+        delay: Duration,
+        command: I,
+    ) -> Result<ScheduledJob> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            bail!("invalid job name: {name:?}");
+        }
+        let job = ScheduledJob {
+            unit: format!("roguewave-{name}"),
+        };
+        job.cancel(self).await?;
+        self.command([
+            "systemd-run",
+            &format!("--on-active={}", delay.as_secs().max(1)),
+            &format!("--unit={}", job.unit),
+            "--collect",
+        ])
+        .args(command)
+        .run()
+        .await?;
+        info!("scheduled {:?} in {} s", job.unit, delay.as_secs());
+        Ok(job)
+    }
+
+    /// Install and start a persistent systemd timer `{unit}.timer` that runs the oneshot
+    /// `{unit}.service` with the `service` section (e.g. `ExecStart=...` lines)
+    /// according to the `on_calendar` expression. Returns `true` if the units were changed.
+    pub(crate) async fn install_periodic_timer(
+        &mut self,
+        unit: &str,
+        description: &str,
+        service: &str,
+        on_calendar: &str,
+    ) -> Result<bool> {
+        if on_calendar.is_empty() || on_calendar.contains('\n') {
+            bail!("invalid calendar expression: {on_calendar:?}");
+        }
+        let service = format!(
+            "# Managed by roguewave.\n\
+             [Unit]\n\
+             Description={description}\n\
+             \n\
+             [Service]\n\
+             Type=oneshot\n\
+             {service}"
+        );
+        let timer = format!(
+            "# Managed by roguewave.\n\
+             [Unit]\n\
+             Description={description} (timer)\n\
+             \n\
+             [Timer]\n\
+             OnCalendar={on_calendar}\n\
+             RandomizedDelaySec=15min\n\
+             Persistent=true\n\
+             \n\
+             [Install]\n\
+             WantedBy=timers.target\n"
+        );
+        let mut changed = self
+            .update_file(format!("/etc/systemd/system/{unit}.service"), service)
+            .await?;
+        changed |= self
+            .update_file(format!("/etc/systemd/system/{unit}.timer"), timer)
+            .await?;
+        if changed {
+            self.command(["systemctl", "daemon-reload"])
+                .hide_command()
+                .run()
+                .await?;
+        }
+        self.command(["systemctl", "enable", "--now", &format!("{unit}.timer")])
+            .run()
+            .await?;
+        Ok(changed)
+    }
+}
+
+/// A command scheduled to run once on the remote host.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ScheduledJob {
+    unit: String,
+}
+
+impl ScheduledJob {
+    /// Name of the systemd unit running the command.
+    pub fn unit(&self) -> &str {
+        &self.unit
+    }
+
+    /// Check if the job is still waiting to be executed.
+    pub async fn is_pending(&self, session: &Session) -> Result<bool> {
+        let code = session
+            .command([
+                "systemctl",
+                "is-active",
+                "--quiet",
+                &format!("{}.timer", self.unit),
+            ])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        Ok(code == 0)
+    }
+
+    /// Cancel the job if it hasn't been executed yet.
+    pub async fn cancel(&self, session: &Session) -> Result<()> {
+        if self.is_pending(session).await? {
+            session
+                .command(["systemctl", "stop", &format!("{}.timer", self.unit)])
+                .run()
+                .await?;
+            info!("cancelled {:?}", self.unit);
+        }
+        Ok(())
+    }
+}
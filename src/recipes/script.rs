@@ -0,0 +1,66 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::{CommandOutput, Session};
+
+impl Session {
+    /// Upload a local script to a temporary remote file, run it with `interpreter`
+    /// (e.g. `python3`) or directly if `interpreter` is `None` (the script must have
+    /// a shebang line then), and delete the file afterwards, even if the script fails.
+    ///
+    /// The script is run as `user` if specified, otherwise as the current user.
+    /// Fails if the script exits with a non-zero code.
+    pub async fn run_script(
+        &mut self,
+        local_path: impl AsRef<Path>,
+        interpreter: Option<&str>,
+        args: &[&str],
+        user: Option<&str>,
+    ) -> Result<CommandOutput> {
+        let local_path = local_path.as_ref();
+        let data =
+            std::fs::read(local_path).with_context(|| format!("failed to read {local_path:?}"))?;
+        let remote_path = self
+            .command(["mktemp", "/tmp/roguewave-script.XXXXXXXX"])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?
+            .stdout_trimmed()
+            .to_string();
+        let result = self
+            .run_uploaded_script(&remote_path, data, interpreter, args, user)
+            .await;
+        self.command(["rm", "-f", "--", &remote_path])
+            .hide_command()
+            .run()
+            .await?;
+        result
+    }
+
+    async fn run_uploaded_script(
+        &mut self,
+        remote_path: &str,
+        data: Vec<u8>,
+        interpreter: Option<&str>,
+        args: &[&str],
+        user: Option<&str>,
+    ) -> Result<CommandOutput> {
+        let size = data.len() as u64;
+        let permit = self.throttle().await;
+        self.write_file(remote_path, data).await?;
+        drop(permit);
+        self.stats.record_upload(size);
+        // `mktemp` creates the file readable by the owner only.
+        self.command(["chmod", "755", "--", remote_path])
+            .hide_command()
+            .run()
+            .await?;
+        let command = match interpreter {
+            Some(interpreter) => self.command([interpreter, remote_path]),
+            None => self.command([remote_path]),
+        };
+        command.args(args).user(user).run().await
+    }
+}
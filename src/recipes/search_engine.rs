@@ -0,0 +1,218 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use log::info;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::Session;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A search engine distribution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SearchEngineKind {
+    /// OpenSearch.
+    OpenSearch,
+    /// Elasticsearch.
+    Elasticsearch,
+}
+
+impl SearchEngineKind {
+    fn name(self) -> &'static str {
+        match self {
+            SearchEngineKind::OpenSearch => "opensearch",
+            SearchEngineKind::Elasticsearch => "elasticsearch",
+        }
+    }
+}
+
+/// Cluster health status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    /// Some primary shards are not allocated.
+    Red,
+    /// All primary shards are allocated, but some replicas are not.
+    Yellow,
+    /// All shards are allocated.
+    Green,
+}
+
+/// Parsed output of the `_cluster/health` endpoint.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ClusterHealth {
+    /// Name of the cluster.
+    pub cluster_name: String,
+    /// Health status.
+    pub status: HealthStatus,
+    /// Number of nodes in the cluster.
+    pub number_of_nodes: u32,
+    /// Number of active shards.
+    pub active_shards: u32,
+    /// Number of unassigned shards.
+    pub unassigned_shards: u32,
+}
+
+impl Session {
+    /// Manage an OpenSearch or Elasticsearch node.
+    pub fn search_engine(&mut self, kind: SearchEngineKind) -> SearchEngine<'_> {
+        SearchEngine {
+            session: self,
+            kind,
+            credentials: None,
+        }
+    }
+}
+
+/// Provides access to OpenSearch or Elasticsearch management.
+pub struct SearchEngine<'a> {
+    session: &'a mut Session,
+    kind: SearchEngineKind,
+    credentials: Option<(String, String)>,
+}
+
+impl SearchEngine<'_> {
+    /// Use basic authentication for HTTP requests to the cluster. The password is not logged.
+    pub fn with_credentials(mut self, user: &str, password: &str) -> Self {
+        self.credentials = Some((user.into(), password.into()));
+        self
+    }
+
+    /// Install the package from the official repository (`major_version` is e.g. `2` for
+    /// OpenSearch or `8` for Elasticsearch) and enable the service.
+    ///
+    /// OpenSearch 2.12 and later require `initial_admin_password` to be specified.
+    /// It's not logged.
+    pub async fn install(
+        &mut self,
+        major_version: &str,
+        initial_admin_password: Option<&str>,
+    ) -> Result<()> {
+        let name = self.kind.name();
+        if !self.session.apt().is_package_installed(name).await? {
+            let (key_url, repository) = match self.kind {
+                SearchEngineKind::OpenSearch => (
+                    "https://artifacts.opensearch.org/publickeys/opensearch.pgp".to_string(),
+                    format!(
+                        "https://artifacts.opensearch.org/releases/bundle/opensearch/{major_version}.x/apt stable main"
+                    ),
+                ),
+                SearchEngineKind::Elasticsearch => (
+                    "https://artifacts.elastic.co/GPG-KEY-elasticsearch".to_string(),
+                    format!("https://artifacts.elastic.co/packages/{major_version}.x/apt stable main"),
+                ),
+            };
+            self.session
+                .apt()
+                .add_repository(name, &key_url, &repository)
+                .await?;
+            let mut command = self.session.command(["env"]);
+            if let Some(password) = initial_admin_password {
+                command = command.redacted_arg(
+                    format!("OPENSEARCH_INITIAL_ADMIN_PASSWORD={password}"),
+                    "OPENSEARCH_INITIAL_ADMIN_PASSWORD=<redacted>",
+                );
+            }
+            command
+                .args(["apt-get", "install", "--yes", name])
+                .run()
+                .await?;
+        }
+        self.session
+            .command(["systemctl", "enable", name])
+            .run()
+            .await?;
+        Ok(())
+    }
+
+    /// Set JVM heap size (e.g. `2g`) in `jvm.options.d/heap.options`.
+    /// Returns `true` if the setting was changed. Call `restart` to apply it.
+    pub async fn set_heap_size(&mut self, size: &str) -> Result<bool> {
+        if size.is_empty() || !size.chars().all(|c| c.is_ascii_alphanumeric()) {
+            bail!("invalid heap size: {size:?}");
+        }
+        let path = format!("/etc/{}/jvm.options.d/heap.options", self.kind.name());
+        self.session
+            .update_file(path, format!("-Xms{size}\n-Xmx{size}\n"))
+            .await
+    }
+
+    /// Write node settings to the main config file (`opensearch.yml` or `elasticsearch.yml`).
+    /// Settings are specified as flat keys, e.g. `cluster.name`.
+    /// Returns `true` if the config was changed. Call `restart` to apply it.
+    pub async fn configure(&mut self, settings: &BTreeMap<String, Value>) -> Result<bool> {
+        let mut config = "# Managed by roguewave.\n".to_string();
+        for (key, value) in settings {
+            // JSON values are valid YAML.
+            writeln!(config, "{key}: {value}")?;
+        }
+        let path = format!("/etc/{0}/{0}.yml", self.kind.name());
+        self.session.update_file(path, config).await
+    }
+
+    /// Restart the service.
+    pub async fn restart(&mut self) -> Result<()> {
+        self.session
+            .command(["systemctl", "restart", self.kind.name()])
+            .run()
+            .await?;
+        Ok(())
+    }
+
+    /// Fetch cluster health from `endpoint` (e.g. `https://localhost:9200`) using `curl`
+    /// on the remote host. The TLS certificate is not verified because the endpoint is
+    /// expected to be local.
+    pub async fn cluster_health(&mut self, endpoint: &str) -> Result<ClusterHealth> {
+        let mut command = self
+            .session
+            .command(["curl", "--silent", "--show-error", "--fail", "--insecure"])
+            .arg(format!(
+                "{}/_cluster/health",
+                endpoint.trim_end_matches('/')
+            ))
+            .hide_command()
+            .hide_stdout();
+        if let Some((user, password)) = &self.credentials {
+            command = command
+                .arg("--user")
+                .redacted_arg(format!("{user}:{password}"), format!("{user}:<redacted>"));
+        }
+        command.run().await?.json()
+    }
+
+    /// Wait until the cluster reports at least `min_status` health.
+    pub async fn wait_healthy(
+        &mut self,
+        endpoint: &str,
+        min_status: HealthStatus,
+        timeout: Duration,
+    ) -> Result<ClusterHealth> {
+        let started = Instant::now();
+        let mut logged_wait = false;
+        loop {
+            let health = self.cluster_health(endpoint).await;
+            if let Ok(health) = &health {
+                if health.status >= min_status {
+                    return Ok(health.clone());
+                }
+            }
+            if started.elapsed() >= timeout {
+                let health = health.context("timed out waiting for cluster health")?;
+                bail!(
+                    "timed out waiting for cluster health: status is {:?}",
+                    health.status
+                );
+            }
+            if !logged_wait {
+                info!("waiting for {} cluster health", self.kind.name());
+                logged_wait = true;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
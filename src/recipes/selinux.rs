@@ -0,0 +1,206 @@
+use std::fmt;
+
+use anyhow::{bail, Context, Result};
+use log::warn;
+
+use crate::Session;
+
+const CONFIG_PATH: &str = "/etc/selinux/config";
+
+/// SELinux mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SelinuxMode {
+    /// Policy is enforced.
+    Enforcing,
+    /// Policy violations are logged but not enforced.
+    Permissive,
+    /// SELinux is disabled.
+    Disabled,
+}
+
+impl fmt::Display for SelinuxMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SelinuxMode::Enforcing => write!(f, "enforcing"),
+            SelinuxMode::Permissive => write!(f, "permissive"),
+            SelinuxMode::Disabled => write!(f, "disabled"),
+        }
+    }
+}
+
+impl SelinuxMode {
+    fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "enforcing" => Ok(SelinuxMode::Enforcing),
+            "permissive" => Ok(SelinuxMode::Permissive),
+            "disabled" => Ok(SelinuxMode::Disabled),
+            _ => bail!("unknown SELinux mode: {value:?}"),
+        }
+    }
+}
+
+impl Session {
+    /// Manage SELinux.
+    pub fn selinux(&mut self) -> Selinux<'_> {
+        Selinux(self)
+    }
+}
+
+/// Provides access to SELinux management commands.
+pub struct Selinux<'a>(&'a mut Session);
+
+impl Selinux<'_> {
+    /// Check if SELinux is enabled on the remote system.
+    /// Returns `false` if SELinux tools are not installed.
+    pub async fn is_enabled(&mut self) -> Result<bool> {
+        let code = self
+            .0
+            .command(["selinuxenabled"])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        Ok(code == 0)
+    }
+
+    /// Fetch the current SELinux mode (`getenforce`).
+    pub async fn mode(&mut self) -> Result<SelinuxMode> {
+        let output = self
+            .0
+            .command(["getenforce"])
+            .hide_command()
+            .hide_stdout()
+            .run()
+            .await?;
+        SelinuxMode::parse(&output.stdout)
+    }
+
+    /// Change the current SELinux mode (`setenforce`). The change is not persisted across reboots,
+    /// use `set_persistent_mode` for that.
+    ///
+    /// SELinux can't be disabled or enabled at runtime, so only `Enforcing` and `Permissive` are
+    /// accepted.
+    pub async fn set_mode(&mut self, mode: SelinuxMode) -> Result<()> {
+        let value = match mode {
+            SelinuxMode::Enforcing => "1",
+            SelinuxMode::Permissive => "0",
+            SelinuxMode::Disabled => bail!("SELinux can't be disabled at runtime"),
+        };
+        if self.mode().await? != mode {
+            self.0.command(["setenforce", value]).run().await?;
+        }
+        Ok(())
+    }
+
+    /// Set the SELinux mode used after reboot in `/etc/selinux/config`.
+    /// Returns `true` if the config was changed.
+    pub async fn set_persistent_mode(&mut self, mode: SelinuxMode) -> Result<bool> {
+        let config = self
+            .0
+            .read_file_if_exists(CONFIG_PATH)
+            .await?
+            .with_context(|| format!("missing {CONFIG_PATH}"))?;
+        let mut found = false;
+        let mut new_config = String::new();
+        for line in config.lines() {
+            if line.trim_start().starts_with("SELINUX=") {
+                found = true;
+                let current = line.trim_start().trim_start_matches("SELINUX=");
+                if SelinuxMode::parse(current).is_ok_and(|current| current == mode) {
+                    return Ok(false);
+                }
+                new_config.push_str(&format!("SELINUX={mode}\n"));
+            } else {
+                new_config.push_str(line);
+                new_config.push('\n');
+            }
+        }
+        if !found {
+            new_config.push_str(&format!("SELINUX={mode}\n"));
+        }
+        self.0.update_file(CONFIG_PATH, new_config).await?;
+        warn!("SELinux mode changed to {mode} in {CONFIG_PATH}, reboot is required to apply it");
+        Ok(true)
+    }
+
+    /// Fetch the value of an SELinux boolean.
+    pub async fn boolean(&mut self, name: &str) -> Result<bool> {
+        let output = self
+            .0
+            .command(["getsebool", name])
+            .hide_command()
+            .hide_stdout()
+            .run()
+            .await?;
+        // Output format: "httpd_can_network_connect --> off"
+        match output.stdout.trim().rsplit(' ').next() {
+            Some("on") => Ok(true),
+            Some("off") => Ok(false),
+            _ => bail!("unexpected getsebool output: {:?}", output.stdout),
+        }
+    }
+
+    /// Persistently set the value of an SELinux boolean (`setsebool -P`).
+    pub async fn set_boolean(&mut self, name: &str, value: bool) -> Result<()> {
+        if self.boolean(name).await? != value {
+            let value = if value { "on" } else { "off" };
+            self.0
+                .command(["setsebool", "-P", name, value])
+                .run()
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Add or modify a file context rule (`semanage fcontext`) and apply it to existing files
+    /// under `path` using `restorecon`.
+    ///
+    /// `spec` is a regular expression of paths, e.g. `/srv/www(/.*)?`.
+    pub async fn set_file_context(
+        &mut self,
+        spec: &str,
+        context_type: &str,
+        path: &str,
+    ) -> Result<()> {
+        let output = self
+            .0
+            .command([
+                "semanage",
+                "fcontext",
+                "--add",
+                "--type",
+                context_type,
+                spec,
+            ])
+            .allow_failure()
+            .hide_stderr()
+            .run()
+            .await?;
+        if output.exit_code != 0 {
+            if !output.stderr.contains("already defined") {
+                bail!("semanage failed: {}", output.stderr.trim());
+            }
+            self.0
+                .command([
+                    "semanage",
+                    "fcontext",
+                    "--modify",
+                    "--type",
+                    context_type,
+                    spec,
+                ])
+                .run()
+                .await?;
+        }
+        self.restorecon(path).await
+    }
+
+    /// Restore default SELinux contexts for `path` recursively (`restorecon -R`).
+    pub async fn restorecon(&mut self, path: &str) -> Result<()> {
+        self.0
+            .command(["restorecon", "-R", "-v", path])
+            .run()
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+use std::fmt::Write;
+
+use anyhow::{bail, Result};
+use log::info;
+
+use crate::Session;
+
+const SSHD_CONFIG_PATH: &str = "/etc/ssh/sshd_config";
+
+/// An SFTP-only user confined to a chroot directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SftpUser {
+    /// User name.
+    pub name: String,
+    /// Chroot directory. Defaults to `/srv/sftp/{name}`.
+    ///
+    /// sshd requires the directory and all of its parents to be owned by root and not
+    /// writable by anyone else, so the user can only write to `writable_dirs`.
+    pub chroot_dir: String,
+    /// Subdirectories of the chroot directory owned by the user. Defaults to `upload`.
+    pub writable_dirs: Vec<String>,
+    /// Public keys allowed to log in, in the `authorized_keys` format.
+    pub authorized_keys: Vec<String>,
+}
+
+impl SftpUser {
+    /// Create a user description with default settings.
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self {
+            chroot_dir: format!("/srv/sftp/{name}"),
+            writable_dirs: vec!["upload".into()],
+            authorized_keys: Vec::new(),
+            name,
+        }
+    }
+
+    /// Render the sshd `Match` block for the user.
+    pub fn render_match_block(&self) -> Result<String> {
+        let mut out = String::new();
+        writeln!(out, "Match User {}", self.name)?;
+        writeln!(out, "    ChrootDirectory {}", self.chroot_dir)?;
+        writeln!(out, "    ForceCommand internal-sftp")?;
+        writeln!(out, "    AllowTcpForwarding no")?;
+        writeln!(out, "    AllowAgentForwarding no")?;
+        writeln!(out, "    X11Forwarding no")?;
+        writeln!(out, "    PermitTTY no")?;
+        Ok(out)
+    }
+
+    fn validate(&self) -> Result<()> {
+        validate_name(&self.name)?;
+        let valid_path = |path: &str| {
+            !path.is_empty()
+                && path
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "._-/".contains(c))
+                && !path.split('/').any(|part| part == "..")
+        };
+        if !self.chroot_dir.starts_with('/') || !valid_path(&self.chroot_dir) {
+            bail!("invalid chroot directory: {:?}", self.chroot_dir);
+        }
+        for dir in &self.writable_dirs {
+            if dir.starts_with('/') || !valid_path(dir) {
+                bail!("invalid writable directory: {dir:?}");
+            }
+        }
+        if self.authorized_keys.iter().any(|key| key.contains('\n')) {
+            bail!("authorized key must be a single line");
+        }
+        Ok(())
+    }
+}
+
+impl Session {
+    /// Manage SFTP-only users confined to a chroot directory.
+    pub fn sftp_users(&mut self) -> SftpUsers<'_> {
+        SftpUsers(self)
+    }
+}
+
+/// Provides access to SFTP-only user management.
+pub struct SftpUsers<'a>(&'a mut Session);
+
+impl SftpUsers<'_> {
+    /// Create or update an SFTP-only user: the account (with a `nologin` shell and the chroot
+    /// directory as home), the directories, `authorized_keys` and a `Match` block
+    /// in `/etc/ssh/sshd_config`. sshd is reloaded if its config was changed.
+    /// Returns `true` if the sshd config was changed.
+    ///
+    /// The `Match` block is appended to the end of `sshd_config`, so global settings
+    /// must not be added after it.
+    pub async fn ensure(&mut self, user: &SftpUser) -> Result<bool> {
+        user.validate()?;
+        let chroot = &user.chroot_dir;
+        if !self.0.user_exists(&user.name).await? {
+            self.0
+                .command([
+                    "useradd",
+                    "--no-create-home",
+                    "--home-dir",
+                    chroot,
+                    "--shell",
+                    "/usr/sbin/nologin",
+                    &user.name,
+                ])
+                .run()
+                .await?;
+            info!("created SFTP user {:?}", user.name);
+        }
+        self.0
+            .command(["mkdir", "-p", "--", chroot])
+            .hide_command()
+            .run()
+            .await?;
+        self.0
+            .command(["chown", "root:root", "--", chroot])
+            .hide_command()
+            .run()
+            .await?;
+        self.0
+            .command(["chmod", "755", "--", chroot])
+            .hide_command()
+            .run()
+            .await?;
+        let owner = format!("{0}:{0}", user.name);
+        for dir in &user.writable_dirs {
+            let path = format!("{chroot}/{dir}");
+            self.0
+                .command(["mkdir", "-p", "--", &path])
+                .hide_command()
+                .run()
+                .await?;
+            self.0
+                .command(["chown", &owner, "--", &path])
+                .hide_command()
+                .run()
+                .await?;
+        }
+        self.set_authorized_keys(user).await?;
+
+        let block = user.render_match_block()?;
+        self.update_sshd_config(&user.name, &block).await
+    }
+
+    /// Remove the `Match` block and the account of an SFTP-only user.
+    /// Files in the chroot directory are kept.
+    pub async fn remove(&mut self, name: &str) -> Result<()> {
+        validate_name(name)?;
+        self.update_sshd_config(name, "").await?;
+        if self.0.user_exists(name).await? {
+            self.0.command(["userdel", name]).run().await?;
+            info!("removed SFTP user {name:?}");
+        }
+        Ok(())
+    }
+
+    async fn set_authorized_keys(&mut self, user: &SftpUser) -> Result<()> {
+        let ssh_dir = format!("{}/.ssh", user.chroot_dir);
+        let path = format!("{ssh_dir}/authorized_keys");
+        self.0
+            .command(["mkdir", "-p", "--", &ssh_dir])
+            .hide_command()
+            .run()
+            .await?;
+        let mut keys = String::new();
+        for key in &user.authorized_keys {
+            writeln!(keys, "{key}")?;
+        }
+        self.0.update_file(&path, keys).await?;
+        let owner = format!("{0}:{0}", user.name);
+        self.0
+            .command(["chown", "-R", &owner, "--", &ssh_dir])
+            .hide_command()
+            .run()
+            .await?;
+        self.0
+            .command(["chmod", "700", "--", &ssh_dir])
+            .hide_command()
+            .run()
+            .await?;
+        self.0
+            .command(["chmod", "600", "--", &path])
+            .hide_command()
+            .run()
+            .await?;
+        Ok(())
+    }
+
+    /// Update the user's block in `sshd_config`, validate the config with `sshd -t`
+    /// (restoring the previous version if it's invalid) and reload sshd.
+    async fn update_sshd_config(&mut self, name: &str, block: &str) -> Result<bool> {
+        let previous = self.0.read_file_if_exists(SSHD_CONFIG_PATH).await?;
+        let changed = self
+            .0
+            .update_managed_block(SSHD_CONFIG_PATH, &format!("roguewave sftp {name}"), block)
+            .await?;
+        if !changed {
+            return Ok(false);
+        }
+        let code = self.0.command(["sshd", "-t"]).exit_code().await?;
+        if code != 0 {
+            if let Some(previous) = previous {
+                self.0.update_file(SSHD_CONFIG_PATH, previous).await?;
+            }
+            bail!("sshd config is invalid after updating the block for {name:?}");
+        }
+        // The service is called `ssh` on Debian and Ubuntu and `sshd` elsewhere.
+        let code = self
+            .0
+            .command(["systemctl", "is-active", "--quiet", "ssh"])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        let service = if code == 0 { "ssh" } else { "sshd" };
+        self.0
+            .command(["systemctl", "reload", service])
+            .run()
+            .await?;
+        Ok(true)
+    }
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || name.starts_with('-')
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+    {
+        bail!("invalid user name: {name:?}");
+    }
+    Ok(())
+}
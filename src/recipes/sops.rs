@@ -0,0 +1,94 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use log::{debug, info};
+
+use crate::{local::LocalCommand, Session};
+
+impl Session {
+    /// Decrypt a local sops-encrypted file and write the plaintext to `remote_path`
+    /// with permissions `mode` (e.g. `0o600`). Returns `true` if the remote file was changed.
+    ///
+    /// Decryption happens locally (sops picks up age/GPG/KMS keys from the local environment),
+    /// so keys never reach the remote host. The plaintext is never logged.
+    /// It's written to a private temporary file first and then moved into place.
+    ///
+    /// If `owner` is specified, the file is owned by that user
+    /// (requires `sudo` available on the remote system).
+    pub async fn deploy_sops_file(
+        &mut self,
+        local_path: impl AsRef<Path>,
+        remote_path: &str,
+        mode: u32,
+        owner: Option<&str>,
+    ) -> Result<bool> {
+        if let Some(owner) = owner {
+            if owner.is_empty()
+                || owner
+                    .chars()
+                    .any(|c| !(c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-'))
+            {
+                bail!("unsafe user: {owner:?}");
+            }
+        }
+        let local_path = local_path.as_ref();
+        let plaintext = LocalCommand::new([
+            "sops",
+            "--decrypt",
+            local_path.to_str().context("non-utf8 path")?,
+        ])
+        .hide_command()
+        .redact_stdout()
+        .log_prefix(&self.log_prefix)
+        .run()
+        .await
+        .with_context(|| format!("failed to decrypt {local_path:?}"))?
+        .stdout;
+
+        let temp_path = self
+            .command(["mktemp"])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?
+            .stdout_trimmed()
+            .to_string();
+        self.write_file(&temp_path, plaintext).await?;
+
+        let root = owner.map(|_| "root");
+        let unchanged = self
+            .command(["cmp", "--silent", "--", &temp_path, remote_path])
+            .user(root)
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?
+            == 0;
+        if unchanged {
+            self.command(["rm", "-f", "--", &temp_path])
+                .hide_command()
+                .run()
+                .await?;
+            debug!("{remote_path:?} is up to date");
+            return Ok(false);
+        }
+        self.command(["chmod", &format!("{mode:o}"), "--", &temp_path])
+            .hide_command()
+            .run()
+            .await?;
+        if let Some(owner) = owner {
+            self.command(["chown", &format!("{owner}:"), "--", &temp_path])
+                .user(root)
+                .hide_command()
+                .run()
+                .await?;
+        }
+        self.command(["mv", "-f", "--", &temp_path, remote_path])
+            .user(root)
+            .hide_command()
+            .run()
+            .await?;
+        info!("deployed decrypted {local_path:?} to {remote_path:?}");
+        Ok(true)
+    }
+}
@@ -0,0 +1,95 @@
+use anyhow::{bail, Result};
+use serde_json::{Map, Value};
+
+use crate::{Command, Session};
+
+impl Session {
+    /// Work with an SQLite database file at `path` on the remote host. `path` should be absolute.
+    ///
+    /// Requires the `sqlite3` command line tool (see `Sqlite::ensure_installed`).
+    pub fn sqlite(&mut self, path: &str) -> Sqlite<'_> {
+        Sqlite {
+            session: self,
+            path: path.into(),
+            user: None,
+        }
+    }
+}
+
+/// A row returned by an SQLite query, mapping column names to values.
+pub type SqliteRow = Map<String, Value>;
+
+/// Provides access to an SQLite database on the remote host.
+pub struct Sqlite<'a> {
+    session: &'a mut Session,
+    path: String,
+    user: Option<String>,
+}
+
+impl Sqlite<'_> {
+    /// Access the database as another remote user, using `sudo`.
+    pub fn user(mut self, user: Option<&str>) -> Self {
+        self.user = user.map(Into::into);
+        self
+    }
+
+    /// Install the `sqlite3` command line tool.
+    pub async fn ensure_installed(&mut self) -> Result<()> {
+        self.session.apt().install(&["sqlite3"]).await
+    }
+
+    /// Run a query and return the resulting rows.
+    pub async fn query(&mut self, sql: &str) -> Result<Vec<SqliteRow>> {
+        let output = self
+            .command(&["-json", "-bail"], sql)
+            .hide_stdout()
+            .run()
+            .await?;
+        if output.stdout_trimmed().is_empty() {
+            return Ok(Vec::new());
+        }
+        output.json()
+    }
+
+    /// Execute SQL statements.
+    pub async fn execute(&mut self, sql: &str) -> Result<()> {
+        self.command(&["-bail"], sql).run().await?;
+        Ok(())
+    }
+
+    /// Create a consistent copy of the database at `destination` on the remote host
+    /// using the `.backup` command. The database may be in use while the backup is created.
+    pub async fn backup(&mut self, destination: &str) -> Result<()> {
+        if destination.contains(['\'', '\n']) {
+            bail!("unsupported backup path: {destination:?}");
+        }
+        self.command(&[], &format!(".backup '{destination}'"))
+            .run()
+            .await?;
+        Ok(())
+    }
+
+    /// Check database integrity (`PRAGMA integrity_check`).
+    /// Returns a list of problems, which is empty if the database is intact.
+    pub async fn integrity_check(&mut self) -> Result<Vec<String>> {
+        let output = self
+            .command(&[], "PRAGMA integrity_check")
+            .hide_stdout()
+            .run()
+            .await?;
+        let problems: Vec<String> = output.stdout_lines().map(Into::into).collect();
+        if problems == ["ok"] {
+            return Ok(Vec::new());
+        }
+        Ok(problems)
+    }
+
+    fn command(&self, options: &[&str], sql: &str) -> Command<'_> {
+        self.session
+            .command(["sqlite3"])
+            .args(options)
+            .arg(&self.path)
+            .arg(sql)
+            .user(self.user.as_deref())
+    }
+}
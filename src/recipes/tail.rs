@@ -0,0 +1,132 @@
+use std::mem;
+
+use anyhow::{Context, Result};
+use log::info;
+use tokio::io::AsyncReadExt;
+
+use crate::{CommandChild, Session};
+
+/// Follows the file until the shell exits. `tail` checks `--pid` about once a second.
+const FOLLOW_SCRIPT: &str = r#"tail --pid=$$ "$@" < /dev/null &
+exec cat > /dev/null"#;
+
+impl Session {
+    /// Read the last lines of a remote file, optionally following new content
+    /// (e.g. to watch a log file during a deploy).
+    ///
+    /// ```no_run
+    /// # async fn f(session: &roguewave::Session) -> anyhow::Result<()> {
+    /// let mut lines = session.tail("/var/log/app.log").follow(true).lines(100).start().await?;
+    /// while let Some(line) = lines.next_line().await? {
+    ///     if line.contains("started") {
+    ///         break;
+    ///     }
+    /// }
+    /// lines.stop().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn tail(&self, path: impl Into<String>) -> Tail<'_> {
+        Tail {
+            session: self,
+            path: path.into(),
+            follow: false,
+            lines: 10,
+        }
+    }
+}
+
+/// Settings of a `tail` invocation. Use `Session::tail` to create it.
+pub struct Tail<'a> {
+    session: &'a Session,
+    path: String,
+    follow: bool,
+    lines: u64,
+}
+
+impl<'a> Tail<'a> {
+    /// Keep waiting for new lines after reaching the end of the file. The file is followed
+    /// by name, so it keeps working after log rotation.
+    pub fn follow(mut self, follow: bool) -> Self {
+        self.follow = follow;
+        self
+    }
+
+    /// Set the number of existing lines to output first (default is 10).
+    pub fn lines(mut self, lines: u64) -> Self {
+        self.lines = lines;
+        self
+    }
+
+    /// Start reading the file.
+    pub async fn start(self) -> Result<TailLines<'a>> {
+        let lines = self.lines.to_string();
+        let command = if self.follow {
+            // `tail` stops shortly after the shell (replaced by `cat`) exits,
+            // which happens when `TailLines::stop` closes stdin.
+            self.session
+                .command(["sh", "-c", FOLLOW_SCRIPT, "sh"])
+                .args(["--lines", &lines, "--follow=name", "--retry"])
+        } else {
+            self.session.command(["tail", "--lines", &lines])
+        };
+        info!(
+            "{}tailing {:?}{}",
+            self.session.log_prefix,
+            self.path,
+            if self.follow { " (following)" } else { "" }
+        );
+        let child = command
+            .args(["--", &self.path])
+            .hide_command()
+            .spawn()
+            .await?;
+        Ok(TailLines {
+            child,
+            buffer: Vec::new(),
+        })
+    }
+}
+
+/// Lines produced by a running `tail`.
+pub struct TailLines<'a> {
+    child: CommandChild<'a>,
+    buffer: Vec<u8>,
+}
+
+impl TailLines<'_> {
+    /// Wait for the next line. Returns `None` when `tail` exits
+    /// (which only happens when not following the file).
+    pub async fn next_line(&mut self) -> Result<Option<String>> {
+        loop {
+            if let Some(end) = self.buffer.iter().position(|&byte| byte == b'\n') {
+                let mut line: Vec<u8> = self.buffer.drain(..=end).collect();
+                line.pop();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                return Ok(Some(
+                    String::from_utf8(line).context("tail output is not valid UTF-8")?,
+                ));
+            }
+            let mut chunk = [0; 4096];
+            let len = self.child.stdout().read(&mut chunk).await?;
+            if len == 0 {
+                if self.buffer.is_empty() {
+                    return Ok(None);
+                }
+                let line = mem::take(&mut self.buffer);
+                return Ok(Some(
+                    String::from_utf8(line).context("tail output is not valid UTF-8")?,
+                ));
+            }
+            self.buffer.extend_from_slice(&chunk[..len]);
+        }
+    }
+
+    /// Stop reading and wait for `tail` to exit.
+    pub async fn stop(self) -> Result<()> {
+        self.child.wait().await?;
+        Ok(())
+    }
+}
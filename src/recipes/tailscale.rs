@@ -0,0 +1,87 @@
+use std::net::IpAddr;
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+use crate::Session;
+
+impl Session {
+    /// Manage Tailscale mesh VPN.
+    pub fn tailscale(&mut self) -> Tailscale<'_> {
+        Tailscale(self)
+    }
+}
+
+/// Provides access to Tailscale commands.
+pub struct Tailscale<'a>(&'a mut Session);
+
+impl Tailscale<'_> {
+    /// Install Tailscale using the official install script, unless it's already installed.
+    pub async fn ensure_installed(&mut self) -> Result<()> {
+        if self.0.path_exists("/usr/bin/tailscale").await? {
+            return Ok(());
+        }
+        self.0.apt().install(&["curl"]).await?;
+        self.0
+            .command([
+                "bash",
+                "-c",
+                "set -o pipefail; curl -fsSL https://tailscale.com/install.sh | sh",
+            ])
+            .run()
+            .await?;
+        Ok(())
+    }
+
+    /// Check if the node is connected to the tailnet. Returns `false` if `tailscale status`
+    /// fails or its output can't be parsed (e.g. when the daemon is not running).
+    pub async fn is_up(&mut self) -> Result<bool> {
+        let output = self
+            .0
+            .command(["tailscale", "status", "--json"])
+            .hide_command()
+            .hide_stdout()
+            .allow_failure()
+            .run()
+            .await?;
+        if output.exit_code != 0 {
+            return Ok(false);
+        }
+        let Ok(status) = output.json::<Value>() else {
+            return Ok(false);
+        };
+        Ok(status.get("BackendState").and_then(Value::as_str) == Some("Running"))
+    }
+
+    /// Connect the node to the tailnet using a pre-authentication key, unless it's already
+    /// connected, and return the assigned IPv4 address. The key is not logged.
+    pub async fn up(&mut self, auth_key: &str, hostname: Option<&str>) -> Result<IpAddr> {
+        if !self.is_up().await? {
+            let mut command = self
+                .0
+                .command(["tailscale", "up"])
+                .redacted_arg(format!("--auth-key={auth_key}"), "--auth-key=<redacted>");
+            if let Some(hostname) = hostname {
+                command = command.arg(format!("--hostname={hostname}"));
+            }
+            command.run().await?;
+            if !self.is_up().await? {
+                bail!("tailscale is not running after `tailscale up`");
+            }
+        }
+        self.ip().await
+    }
+
+    /// Fetch the IPv4 address assigned to the node in the tailnet.
+    pub async fn ip(&mut self) -> Result<IpAddr> {
+        self.0
+            .command(["tailscale", "ip", "-4"])
+            .hide_command()
+            .hide_stdout()
+            .run()
+            .await?
+            .stdout_trimmed()
+            .parse()
+            .context("failed to parse tailscale ip output")
+    }
+}
@@ -0,0 +1,172 @@
+use anyhow::{bail, Result};
+use log::info;
+
+use crate::{Command, Session};
+
+impl<'a> Command<'a> {
+    /// Start the command as a transient systemd service (`systemd-run`) and return
+    /// immediately.
+    ///
+    /// Unlike a regular command, the service keeps running if the SSH connection is closed,
+    /// and its output goes to the journal. The service is named `roguewave-{name}`;
+    /// a previous service with the same name is stopped first. If `restart_on_failure`
+    /// is set, systemd restarts the command when it exits with an error.
+    pub async fn transient_unit(
+        self,
+        name: &str,
+        restart_on_failure: bool,
+    ) -> Result<TransientUnit> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            bail!("invalid unit name: {name:?}");
+        }
+        let session = self.session;
+        let unit = TransientUnit {
+            unit: format!("roguewave-{name}.service"),
+        };
+        unit.remove(session).await?;
+        let mut args = vec![
+            "systemd-run".to_string(),
+            format!("--unit={}", unit.unit),
+            "--remain-after-exit".into(),
+            "--quiet".into(),
+        ];
+        if restart_on_failure {
+            args.push("--property=Restart=on-failure".into());
+        }
+        args.push("--".into());
+        self.prepend_args(args).run().await?;
+        info!("started {:?}", unit.unit);
+        Ok(unit)
+    }
+}
+
+/// A command running as a transient systemd service.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TransientUnit {
+    unit: String,
+}
+
+/// State of a transient systemd service.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TransientUnitStatus {
+    /// systemd `ActiveState`, e.g. `active` or `failed`.
+    pub active_state: String,
+    /// systemd `SubState`, e.g. `running` or `exited`.
+    pub sub_state: String,
+    /// Exit code of the command if it has exited.
+    pub exit_code: Option<i32>,
+}
+
+impl TransientUnitStatus {
+    /// Check if the command is still running (or is about to be restarted).
+    pub fn is_running(&self) -> bool {
+        matches!(
+            self.active_state.as_str(),
+            "active" | "activating" | "reloading"
+        ) && self.sub_state != "exited"
+    }
+
+    /// Check if the command has exited successfully.
+    pub fn succeeded(&self) -> bool {
+        self.sub_state == "exited" && self.exit_code == Some(0)
+    }
+}
+
+impl TransientUnit {
+    /// Open a handle to a service previously started with `transient_unit(name, ..)`.
+    pub fn from_name(name: &str) -> Self {
+        Self {
+            unit: format!("roguewave-{name}.service"),
+        }
+    }
+
+    /// Name of the systemd unit.
+    pub fn unit(&self) -> &str {
+        &self.unit
+    }
+
+    /// Fetch the current state of the service.
+    pub async fn status(&self, session: &Session) -> Result<TransientUnitStatus> {
+        let output = session
+            .command([
+                "systemctl",
+                "show",
+                &self.unit,
+                "--property=ActiveState,SubState,ExecMainCode,ExecMainStatus",
+            ])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?;
+        let property = |name: &str| {
+            output.stdout_lines().find_map(|line| {
+                let (key, value) = line.split_once('=')?;
+                (key == name).then(|| value.to_string())
+            })
+        };
+        // ExecMainCode is 0 while the process is running and 1 (CLD_EXITED) after
+        // a normal exit.
+        let exited = property("ExecMainCode").as_deref() == Some("1");
+        Ok(TransientUnitStatus {
+            active_state: property("ActiveState").unwrap_or_default(),
+            sub_state: property("SubState").unwrap_or_default(),
+            exit_code: if exited {
+                property("ExecMainStatus").and_then(|code| code.parse().ok())
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Fetch up to `lines` last lines of the service output from the journal.
+    pub async fn logs(&self, session: &Session, lines: u32) -> Result<String> {
+        let output = session
+            .command([
+                "journalctl",
+                "--unit",
+                &self.unit,
+                "--output=cat",
+                "--no-pager",
+                "--lines",
+                &lines.to_string(),
+            ])
+            .hide_command()
+            .hide_stdout()
+            .run()
+            .await?;
+        Ok(output.stdout)
+    }
+
+    /// Stop the service if it's running.
+    pub async fn stop(&self, session: &Session) -> Result<()> {
+        session
+            .command(["systemctl", "stop", &self.unit])
+            .run()
+            .await?;
+        info!("stopped {:?}", self.unit);
+        Ok(())
+    }
+
+    /// Stop the service and forget its state.
+    pub async fn remove(&self, session: &Session) -> Result<()> {
+        session
+            .command(["systemctl", "stop", &self.unit])
+            .hide_command()
+            .hide_all_output()
+            .allow_failure()
+            .run()
+            .await?;
+        session
+            .command(["systemctl", "reset-failed", &self.unit])
+            .hide_command()
+            .hide_all_output()
+            .allow_failure()
+            .run()
+            .await?;
+        Ok(())
+    }
+}
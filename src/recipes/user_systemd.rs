@@ -0,0 +1,155 @@
+use anyhow::{bail, Result};
+use log::info;
+
+use crate::{Command, CommandOutput, Session};
+
+impl Session {
+    /// Manage systemd user services (`systemctl --user`) of `user`.
+    ///
+    /// The user's service manager only runs while the user is logged in, unless lingering
+    /// is enabled with [`enable_linger`](UserSystemd::enable_linger).
+    pub fn user_systemd(&mut self, user: &str) -> UserSystemd<'_> {
+        UserSystemd {
+            session: self,
+            user: user.into(),
+        }
+    }
+}
+
+/// Provides access to systemd user services of a user.
+pub struct UserSystemd<'a> {
+    session: &'a mut Session,
+    user: String,
+}
+
+impl UserSystemd<'_> {
+    /// Check if lingering is enabled for the user.
+    pub async fn is_linger_enabled(&mut self) -> Result<bool> {
+        let output = self
+            .session
+            .command([
+                "loginctl",
+                "show-user",
+                &self.user,
+                "--property=Linger",
+                "--value",
+            ])
+            .hide_command()
+            .hide_all_output()
+            .allow_failure()
+            .run()
+            .await?;
+        Ok(output.exit_code == 0 && output.stdout_trimmed() == "yes")
+    }
+
+    /// Enable lingering so that the user's service manager is started at boot and keeps
+    /// running without a login session. Returns `true` if lingering was enabled by this call.
+    pub async fn enable_linger(&mut self) -> Result<bool> {
+        if self.is_linger_enabled().await? {
+            return Ok(false);
+        }
+        self.session
+            .command(["loginctl", "enable-linger", &self.user])
+            .run()
+            .await?;
+        // Wait for the user's service manager to start.
+        let uid = self.session.user_id(&self.user).await?;
+        self.session
+            .command([
+                "timeout",
+                "30",
+                "bash",
+                "-c",
+                &format!("until [ -S /run/user/{uid}/bus ]; do sleep 0.5; done"),
+            ])
+            .hide_command()
+            .run()
+            .await?;
+        info!("enabled lingering for {}", self.user);
+        Ok(true)
+    }
+
+    /// Run `systemctl --user` with `args` as the user.
+    pub async fn systemctl<S: AsRef<str>, I: IntoIterator<Item = S>>(
+        &mut self,
+        args: I,
+    ) -> Result<CommandOutput> {
+        self.command(args).await?.run().await
+    }
+
+    /// Check if a user unit is active.
+    pub async fn is_active(&mut self, unit: &str) -> Result<bool> {
+        let code = self
+            .command(["is-active", "--quiet", unit])
+            .await?
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        Ok(code == 0)
+    }
+
+    /// Write a unit file to `~/.config/systemd/user/` of the user and reload the user's
+    /// service manager if it was changed. Returns `true` if the unit file was changed.
+    pub async fn install_unit(&mut self, name: &str, content: &str) -> Result<bool> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "@._-".contains(c))
+            || !name.contains('.')
+        {
+            bail!("invalid unit name: {name:?}");
+        }
+        let dir = format!(
+            "{}/.config/systemd/user",
+            self.session.home_dir(Some(&self.user)).await?
+        );
+        self.session
+            .command(["mkdir", "-p", &dir])
+            .user(Some(&self.user))
+            .hide_command()
+            .run()
+            .await?;
+        let path = format!("{dir}/{name}");
+        let changed = self.session.update_file(&path, content).await?;
+        if changed {
+            self.session
+                .command(["chown", &format!("{}:", self.user), "--", &path])
+                .hide_command()
+                .run()
+                .await?;
+            self.systemctl(["daemon-reload"]).await?;
+        }
+        Ok(changed)
+    }
+
+    /// Enable and start a user unit.
+    pub async fn enable_now(&mut self, unit: &str) -> Result<()> {
+        self.systemctl(["enable", "--now", unit]).await?;
+        Ok(())
+    }
+
+    /// Restart a user unit.
+    pub async fn restart(&mut self, unit: &str) -> Result<()> {
+        self.systemctl(["restart", unit]).await?;
+        Ok(())
+    }
+
+    async fn command<S: AsRef<str>, I: IntoIterator<Item = S>>(
+        &self,
+        args: I,
+    ) -> Result<Command<'_>> {
+        let uid = self.session.user_id(&self.user).await?;
+        Ok(self
+            .session
+            .command([
+                "env".into(),
+                format!("XDG_RUNTIME_DIR=/run/user/{uid}"),
+                format!("DBUS_SESSION_BUS_ADDRESS=unix:path=/run/user/{uid}/bus"),
+                "systemctl".into(),
+                "--user".into(),
+            ])
+            .args(args)
+            .user(Some(&self.user)))
+    }
+}
@@ -0,0 +1,109 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+use log::info;
+use serde_json::Value;
+
+use crate::Session;
+
+/// Where to get the version of a deployed application.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionSource {
+    /// Run the command on the remote host (e.g. `["/opt/app/bin/app", "--version"]`).
+    Command(Vec<String>),
+    /// Fetch the URL from the remote host with `curl` (e.g. `http://127.0.0.1:8080/version`).
+    ///
+    /// The response may be plain text or a JSON object with a `version` field.
+    Endpoint(String),
+}
+
+impl VersionSource {
+    /// Run `binary --version`.
+    pub fn binary(binary: impl Into<String>) -> Self {
+        Self::Command(vec![binary.into(), "--version".into()])
+    }
+}
+
+/// Error returned by [`Session::check_app_version`] when the deployed version
+/// differs from the expected one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionMismatch {
+    /// Version the caller expected.
+    pub expected: String,
+    /// Version reported by the application.
+    pub actual: String,
+}
+
+impl fmt::Display for VersionMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "version mismatch: expected {:?}, got {:?}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for VersionMismatch {}
+
+impl Session {
+    /// Query the version reported by a deployed application.
+    pub async fn app_version(&self, source: &VersionSource) -> Result<String> {
+        let output = match source {
+            VersionSource::Command(command) => {
+                if command.is_empty() {
+                    bail!("empty version command");
+                }
+                self.command(command)
+                    .hide_command()
+                    .hide_all_output()
+                    .run()
+                    .await?
+            }
+            VersionSource::Endpoint(url) => {
+                self.command(["curl", "--silent", "--show-error", "--fail", url])
+                    .hide_command()
+                    .hide_all_output()
+                    .run()
+                    .await?
+            }
+        };
+        let text = output.stdout_trimmed();
+        if let Ok(Value::Object(object)) = serde_json::from_str::<Value>(text) {
+            if let Some(Value::String(version)) = object.get("version") {
+                return Ok(version.clone());
+            }
+        }
+        match output.last_line() {
+            Some(line) => Ok(line.trim().to_string()),
+            None => bail!("application didn't report a version"),
+        }
+    }
+
+    /// Check that the deployed application reports the `expected` version.
+    ///
+    /// The version matches if the reported string is equal to `expected` or contains it
+    /// as a separate word (e.g. `app 1.2.3` or `v1.2.3` matches `1.2.3`).
+    /// On mismatch, returns a [`VersionMismatch`] error that can be extracted
+    /// with `anyhow::Error::downcast_ref`.
+    pub async fn check_app_version(&self, source: &VersionSource, expected: &str) -> Result<()> {
+        let actual = self.app_version(source).await?;
+        if !version_matches(&actual, expected) {
+            return Err(VersionMismatch {
+                expected: expected.into(),
+                actual,
+            }
+            .into());
+        }
+        info!("application version is {expected}");
+        Ok(())
+    }
+}
+
+fn version_matches(actual: &str, expected: &str) -> bool {
+    let expected = expected.trim().trim_start_matches('v');
+    actual == expected
+        || actual
+            .split(|c: char| c.is_whitespace() || c == ',' || c == '(' || c == ')')
+            .any(|word| word.trim_start_matches('v') == expected)
+}
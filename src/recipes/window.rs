@@ -0,0 +1,208 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use log::info;
+
+use crate::Session;
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+/// Day of the week.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Weekday {
+    /// Monday.
+    Monday,
+    /// Tuesday.
+    Tuesday,
+    /// Wednesday.
+    Wednesday,
+    /// Thursday.
+    Thursday,
+    /// Friday.
+    Friday,
+    /// Saturday.
+    Saturday,
+    /// Sunday.
+    Sunday,
+}
+
+impl Weekday {
+    const ALL: [Self; 7] = [
+        Self::Monday,
+        Self::Tuesday,
+        Self::Wednesday,
+        Self::Thursday,
+        Self::Friday,
+        Self::Saturday,
+        Self::Sunday,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    /// The day after this one.
+    pub fn next(self) -> Self {
+        Self::ALL[(self.index() + 1) % 7]
+    }
+
+    /// The day before this one.
+    pub fn previous(self) -> Self {
+        Self::ALL[(self.index() + 6) % 7]
+    }
+}
+
+/// A recurring period of time when disruptive operations are allowed, in the local
+/// timezone of the host.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MaintenanceWindow {
+    start: u32,
+    end: u32,
+    days: [bool; 7],
+}
+
+impl MaintenanceWindow {
+    /// A window that is open every day from `start` to `end`, specified as `HH:MM`.
+    /// If `end` is earlier than `start`, the window spans midnight, e.g. `23:00`–`02:00`.
+    pub fn daily(start: &str, end: &str) -> Result<Self> {
+        let start = parse_time(start)?;
+        let end = parse_time(end)?;
+        if start == end {
+            bail!("maintenance window can't start and end at the same time");
+        }
+        Ok(Self {
+            start,
+            end,
+            days: [true; 7],
+        })
+    }
+
+    /// Only open the window on the specified days. A window spanning midnight belongs to
+    /// the day it starts on.
+    pub fn on_days(mut self, days: &[Weekday]) -> Self {
+        self.days = [false; 7];
+        for day in days {
+            self.days[day.index()] = true;
+        }
+        self
+    }
+
+    fn starts_on(&self, day: Weekday) -> bool {
+        self.days[day.index()]
+    }
+
+    /// Check if the window is open at `seconds` after midnight on `day`.
+    pub fn contains(&self, day: Weekday, seconds: u32) -> bool {
+        if self.start < self.end {
+            self.starts_on(day) && (self.start..self.end).contains(&seconds)
+        } else {
+            (self.starts_on(day) && seconds >= self.start)
+                || (self.starts_on(day.previous()) && seconds < self.end)
+        }
+    }
+
+    /// Time from `seconds` after midnight on `day` until the window opens next time.
+    /// Returns `None` if the window is never open.
+    pub fn time_until_open(&self, day: Weekday, seconds: u32) -> Option<Duration> {
+        let mut candidate_day = day;
+        for offset in 0..=7 {
+            let open_at = offset * SECONDS_PER_DAY + self.start;
+            if self.starts_on(candidate_day) && open_at > seconds {
+                return Some(Duration::from_secs((open_at - seconds).into()));
+            }
+            candidate_day = candidate_day.next();
+        }
+        None
+    }
+}
+
+/// What to do if an operation guarded by a maintenance window is attempted while the window
+/// is closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutsideWindow {
+    /// Return an error.
+    Fail,
+    /// Wait until the window opens, but fail if it doesn't open within the specified time.
+    Wait(Duration),
+}
+
+impl Session {
+    /// Return when the `window` is open according to the remote clock in the host's
+    /// local timezone. If the window is closed, fail or wait for it depending on `policy`.
+    ///
+    /// Call it before a group of disruptive operations, or use `Command::only_during`
+    /// to guard a single command. Note that the window may close while
+    /// the operations are still running.
+    pub async fn only_during(
+        &self,
+        window: &MaintenanceWindow,
+        policy: OutsideWindow,
+    ) -> Result<()> {
+        let started = Instant::now();
+        let mut logged_wait = false;
+        loop {
+            let (day, seconds) = self.remote_local_time().await?;
+            if window.contains(day, seconds) {
+                return Ok(());
+            }
+            let wait = window
+                .time_until_open(day, seconds)
+                .context("maintenance window is never open")?;
+            let OutsideWindow::Wait(timeout) = policy else {
+                bail!(
+                    "outside of maintenance window (opens in {} min)",
+                    wait.as_secs().div_ceil(60)
+                );
+            };
+            if started.elapsed() + wait > timeout {
+                bail!(
+                    "maintenance window opens in {} min, which exceeds the wait timeout",
+                    wait.as_secs().div_ceil(60)
+                );
+            }
+            if !logged_wait {
+                info!(
+                    "waiting {} min for maintenance window",
+                    wait.as_secs().div_ceil(60)
+                );
+                logged_wait = true;
+            }
+            // Check the remote clock again because it may drift relative to the local one.
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    async fn remote_local_time(&self) -> Result<(Weekday, u32)> {
+        let output = self
+            .command(["date", "+%u %H %M %S"])
+            .hide_command()
+            .hide_all_output()
+            .run()
+            .await?;
+        let parse = || -> Option<(Weekday, u32)> {
+            let mut parts = output.stdout.split_whitespace().map(|s| s.parse::<u32>());
+            // `%u` is 1 for Monday.
+            let day = parts.next()?.ok()?.checked_sub(1)?;
+            let day = *Weekday::ALL.get(day as usize)?;
+            let hours = parts.next()?.ok()?;
+            let minutes = parts.next()?.ok()?;
+            let seconds = parts.next()?.ok()?;
+            Some((day, hours * 3600 + minutes * 60 + seconds))
+        };
+        parse().with_context(|| format!("unexpected date output: {:?}", output.stdout))
+    }
+}
+
+/// Parse `HH:MM` into seconds after midnight.
+fn parse_time(value: &str) -> Result<u32> {
+    let parse = || -> Option<u32> {
+        let (hours, minutes) = value.split_once(':')?;
+        if hours.len() != 2 || minutes.len() != 2 {
+            return None;
+        }
+        let hours = hours.parse::<u32>().ok().filter(|h| *h < 24)?;
+        let minutes = minutes.parse::<u32>().ok().filter(|m| *m < 60)?;
+        Some(hours * 3600 + minutes * 60)
+    };
+    parse().with_context(|| format!("invalid time (expected HH:MM): {value:?}"))
+}
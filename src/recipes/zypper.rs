@@ -0,0 +1,105 @@
+use anyhow::{bail, Result};
+use log::warn;
+
+use crate::{Distro, Session};
+
+const ZYPPER_EXIT_INF_REBOOT_NEEDED: i32 = 102;
+const ZYPPER_EXIT_INF_RESTART_NEEDED: i32 = 103;
+
+impl Session {
+    /// Execute zypper package management commands (SLES and openSUSE).
+    pub fn zypper(&mut self) -> Zypper<'_> {
+        Zypper(self)
+    }
+}
+
+/// Provides access to zypper package management commands.
+pub struct Zypper<'a>(&'a mut Session);
+
+impl Zypper<'_> {
+    /// Refresh repositories.
+    pub async fn refresh(&mut self) -> Result<()> {
+        self.require_zypper().await?;
+        self.0
+            .command(["zypper", "--non-interactive", "refresh"])
+            .run()
+            .await?;
+        Ok(())
+    }
+
+    /// Check if a package is installed.
+    pub async fn is_installed(&mut self, package: &str) -> Result<bool> {
+        let code = self
+            .0
+            .command(["rpm", "--query", package])
+            .hide_command()
+            .hide_all_output()
+            .exit_code()
+            .await?;
+        Ok(code == 0)
+    }
+
+    /// Install specified packages.
+    pub async fn install(&mut self, packages: &[&str]) -> Result<()> {
+        self.require_zypper().await?;
+        let mut new_packages = Vec::new();
+        for package in packages {
+            if !self.is_installed(package).await? {
+                new_packages.push(package);
+            }
+        }
+        if !new_packages.is_empty() {
+            self.0
+                .command(["zypper", "--non-interactive", "install"])
+                .args(new_packages)
+                .run()
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Remove specified packages.
+    pub async fn remove(&mut self, packages: &[&str]) -> Result<()> {
+        self.require_zypper().await?;
+        let mut installed_packages = Vec::new();
+        for package in packages {
+            if self.is_installed(package).await? {
+                installed_packages.push(package);
+            }
+        }
+        if !installed_packages.is_empty() {
+            self.0
+                .command(["zypper", "--non-interactive", "remove"])
+                .args(installed_packages)
+                .run()
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Install all needed patches. If zypper updates itself first,
+    /// patching is repeated. Returns `true` if a reboot is required.
+    pub async fn patch(&mut self) -> Result<bool> {
+        self.require_zypper().await?;
+        loop {
+            let code = self
+                .0
+                .command(["zypper", "--non-interactive", "patch"])
+                .exit_code()
+                .await?;
+            match code {
+                0 => return Ok(false),
+                ZYPPER_EXIT_INF_REBOOT_NEEDED => {
+                    warn!("reboot is required to finish patching");
+                    return Ok(true);
+                }
+                ZYPPER_EXIT_INF_RESTART_NEEDED => continue,
+                _ => bail!("zypper patch failed with exit code {code}"),
+            }
+        }
+    }
+
+    async fn require_zypper(&mut self) -> Result<()> {
+        self.0.require_os_for("zypper", &[Distro::OpenSuse]).await
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    future::Future,
+    process,
+    sync::{Arc, Mutex, PoisonError, Weak},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::bail;
+use log::{error, info, warn};
+
+use crate::{summary::SessionStats, SessionFuture};
+
+type CleanupHook = Box<dyn FnOnce() -> SessionFuture<'static, ()> + Send>;
+
+const KILL_TIMEOUT: Duration = Duration::from_secs(10);
+/// Runs `"$@"` as a leader of a new process group and keeps a PID file `"$0-<pid>.pid"`
+/// while it's running. Stdin is passed through fd 3 because background commands
+/// get `/dev/null` as stdin.
+const WRAPPER_SCRIPT: &str = r#"exec 3<&0
+setsid "$@" <&3 3<&- &
+pid=$!
+exec 3<&-
+f="$0-$pid.pid"
+: > "$f"
+wait "$pid"
+code=$?
+rm -f -- "$f"
+exit "$code""#;
+/// Sends `SIGTERM` to process groups recorded in PID files `"$0-<pid>.pid"`.
+const KILL_SCRIPT: &str = r#"for f in "$0"-*.pid; do
+    [ -e "$f" ] || continue
+    pid=${f%.pid}
+    pid=${pid##*-}
+    kill -TERM -- "-$pid" 2>/dev/null
+    rm -f -- "$f"
+done"#;
+
+tokio::task_local! {
+    static CURRENT_RUN: Arc<RunState>;
+}
+
+/// State of a `run` call, shared with the sessions opened in it.
+pub(crate) struct RunState {
+    id: String,
+    hooks: Mutex<Vec<(String, CleanupHook)>>,
+    sessions: Mutex<Vec<RunSession>>,
+}
+
+/// A session opened in a `run` call.
+struct RunSession {
+    destination: String,
+    connection: Weak<openssh::Session>,
+    stats: Arc<SessionStats>,
+}
+
+impl RunState {
+    fn new() -> anyhow::Result<Self> {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+        Ok(Self {
+            id: format!("{}-{nanos}", process::id()),
+            hooks: Mutex::default(),
+            sessions: Mutex::default(),
+        })
+    }
+
+    /// State of the `run` call the current task is executed in.
+    pub(crate) fn current() -> Option<Arc<Self>> {
+        CURRENT_RUN.try_with(Arc::clone).ok()
+    }
+
+    pub(crate) fn register_session(
+        &self,
+        destination: &str,
+        connection: &Arc<openssh::Session>,
+        stats: &Arc<SessionStats>,
+    ) {
+        self.sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(RunSession {
+                destination: destination.into(),
+                connection: Arc::downgrade(connection),
+                stats: stats.clone(),
+            });
+    }
+
+    /// Wrapper that runs a remote command in its own process group and records
+    /// the group ID in a PID file, so that the command can be killed on interruption.
+    pub(crate) fn command_wrapper(&self) -> [String; 4] {
+        [
+            "sh".into(),
+            "-c".into(),
+            WRAPPER_SCRIPT.into(),
+            self.pid_file_prefix(),
+        ]
+    }
+
+    fn pid_file_prefix(&self) -> String {
+        format!("/tmp/roguewave-run-{}", self.id)
+    }
+
+    /// Send `SIGTERM` to the process groups of remote commands that are still running.
+    async fn kill_remote_commands(&self) {
+        let sessions: Vec<_> = self
+            .sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .filter_map(|session| {
+                Some((session.destination.clone(), session.connection.upgrade()?))
+            })
+            .collect();
+        for (destination, connection) in sessions {
+            let kill = connection
+                .command("sh")
+                .arg("-c")
+                .arg(KILL_SCRIPT)
+                .arg(self.pid_file_prefix())
+                .status();
+            match tokio::time::timeout(KILL_TIMEOUT, kill).await {
+                Ok(Ok(status)) if status.success() => {}
+                Ok(Ok(status)) => {
+                    warn!("failed to stop remote commands on {destination}: {status}");
+                }
+                Ok(Err(err)) => warn!("failed to stop remote commands on {destination}: {err}"),
+                Err(_) => warn!("timed out stopping remote commands on {destination}"),
+            }
+        }
+    }
+}
+
+/// Run the main future of a program, stopping it on Ctrl-C.
+///
+/// Remote commands executed by sessions opened inside `run` (and their forks) are started
+/// in their own process groups. When Ctrl-C is received, these process groups are sent
+/// `SIGTERM` and `main` is dropped, which cancels all in-flight operations. Then hooks
+/// registered with `on_interrupt` are executed in reverse order of registration, and
+/// a report with the summary of each session is logged.
+/// Returns an error if the run was interrupted.
+///
+/// ```no_run
+/// # #[tokio::main]
+/// # async fn main() -> anyhow::Result<()> {
+/// roguewave::run(async {
+///     let session = roguewave::Session::connect("root@example.com").await?;
+///     session.command(["sleep", "100"]).run().await?;
+///     Ok(())
+/// })
+/// .await
+/// # }
+/// ```
+///
+/// Each call of `run` has its own hooks and sessions, so `run` calls may be nested or
+/// executed in parallel. Sessions opened in tasks spawned from `main` are only tracked
+/// if they are forked from a tracked session.
+pub async fn run<T>(main: impl Future<Output = anyhow::Result<T>>) -> anyhow::Result<T> {
+    let started = Instant::now();
+    let state = Arc::new(RunState::new()?);
+    // Boxed so that it can be dropped after stopping remote commands.
+    let mut main = Box::pin(CURRENT_RUN.scope(state.clone(), main));
+    let result = tokio::select! {
+        result = &mut main => Some(result),
+        signal = tokio::signal::ctrl_c() => {
+            signal?;
+            None
+        }
+    };
+    if let Some(result) = result {
+        return result;
+    }
+
+    warn!(
+        "interrupted after {:.1} s, stopping remote commands",
+        started.elapsed().as_secs_f64()
+    );
+    // Connections are still open while `main` is alive.
+    state.kill_remote_commands().await;
+    drop(main);
+
+    let hooks = std::mem::take(&mut *state.hooks.lock().unwrap_or_else(PoisonError::into_inner));
+    info!("running {} cleanup hooks", hooks.len());
+    let mut failed = 0;
+    for (name, hook) in hooks.into_iter().rev() {
+        match hook().await {
+            Ok(()) => info!("cleanup {name:?} done"),
+            Err(err) => {
+                failed += 1;
+                error!("cleanup {name:?} failed: {err:?}");
+            }
+        }
+    }
+    for session in state
+        .sessions
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+    {
+        info!(
+            "[{}] session summary: {}",
+            session.destination,
+            session.stats.summary()
+        );
+    }
+    if failed > 0 {
+        bail!("interrupted by user ({failed} cleanup hooks failed)");
+    }
+    bail!("interrupted by user");
+}
+
+/// Register a hook that will be executed if the current `run` call is interrupted with Ctrl-C.
+///
+/// Hooks are registered in the innermost `run` call that executes the current task
+/// and are discarded when it finishes. Outside of `run` (e.g. in a task spawned
+/// with `tokio::spawn`), the hook is discarded with a warning.
+/// To access a remote host, use a session dedicated to the hook
+/// (e.g. created with `Session::fork`).
+pub fn on_interrupt(
+    name: impl Into<String>,
+    hook: impl FnOnce() -> SessionFuture<'static, ()> + Send + 'static,
+) {
+    let name = name.into();
+    match RunState::current() {
+        Some(run) => run
+            .hooks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((name, Box::new(hook))),
+        None => warn!("cleanup hook {name:?} is registered outside of `run` and is ignored"),
+    }
+}
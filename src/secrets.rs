@@ -0,0 +1,155 @@
+//! Fetching secrets from external stores at run time.
+//!
+//! Secrets are plain strings. Pass them to remote commands with
+//! [`Command::redacted_arg`](crate::Command::redacted_arg) so they don't appear in logs:
+//! ```no_run
+//! use roguewave::{secrets::{SecretsProvider, Vault, VaultAuth}, Session};
+//!
+//! # #[tokio::main]
+//! # async fn main() -> anyhow::Result<()> {
+//! let vault = Vault::new("https://vault.example.com:8200", VaultAuth::Token("...".into()));
+//! let password = vault.secret("secret/data/app", "db_password").await?;
+//! let session = Session::connect("root@example.com").await?;
+//! session
+//!     .command(["app-ctl", "set-password"])
+//!     .redacted_arg(&password, "<redacted>")
+//!     .run()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//! Built-in providers use `curl` on the local machine.
+
+use std::{collections::HashMap, future::Future, sync::Mutex};
+
+use anyhow::{bail, Context};
+use serde_json::{json, Map, Value};
+
+use crate::{local::curl_config, LocalCommand};
+
+/// A source of secrets.
+pub trait SecretsProvider {
+    /// Fetch the value of `key` stored at `path`.
+    fn secret(&self, path: &str, key: &str) -> impl Future<Output = anyhow::Result<String>> + Send;
+}
+
+/// Authentication method for [`Vault`].
+#[derive(Clone)]
+pub enum VaultAuth {
+    /// A Vault token.
+    Token(String),
+    /// AppRole credentials (the role is expected to be mounted at `auth/approle`).
+    AppRole {
+        /// Role ID.
+        role_id: String,
+        /// Secret ID.
+        secret_id: String,
+    },
+}
+
+/// Fetches secrets from HashiCorp Vault KV secrets engines (version 1 or 2).
+///
+/// For KV version 2, `path` must include the `data/` segment (e.g. `secret/data/app`).
+/// Secrets are fetched once and cached for the lifetime of the `Vault` object.
+pub struct Vault {
+    address: String,
+    auth: VaultAuth,
+    token: Mutex<Option<String>>,
+    cache: Mutex<HashMap<String, Map<String, Value>>>,
+}
+
+impl Vault {
+    /// Create a Vault provider for the server at `address`, e.g. `https://vault.example.com:8200`.
+    pub fn new(address: impl Into<String>, auth: VaultAuth) -> Self {
+        Self {
+            address: address.into().trim_end_matches('/').to_string(),
+            auth,
+            token: Mutex::new(None),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn request(
+        &self,
+        path: &str,
+        token: Option<&str>,
+        body: Option<Value>,
+    ) -> anyhow::Result<Value> {
+        // Token and body are passed through stdin to keep them out of the process list.
+        let url = format!("{}/v1/{}", self.address, path);
+        let header = token.map(|token| format!("X-Vault-Token: {token}"));
+        let body = body.map(|body| body.to_string());
+        let mut options = vec![("url", url.as_str())];
+        if let Some(header) = &header {
+            options.push(("header", header.as_str()));
+        }
+        if let Some(body) = &body {
+            options.push(("data", body.as_str()));
+        }
+        let output = LocalCommand::new(["curl", "--fail", "--silent", "--show-error"])
+            .args(["--config", "-"])
+            .stdin(curl_config(options))
+            .hide_command()
+            .redact_stdout()
+            .run()
+            .await
+            .with_context(|| format!("vault request to {path:?} failed"))?;
+        output.json()
+    }
+
+    async fn token(&self) -> anyhow::Result<String> {
+        if let Some(token) = self.token.lock().unwrap().clone() {
+            return Ok(token);
+        }
+        let token = match &self.auth {
+            VaultAuth::Token(token) => token.clone(),
+            VaultAuth::AppRole { role_id, secret_id } => {
+                let response = self
+                    .request(
+                        "auth/approle/login",
+                        None,
+                        Some(json!({ "role_id": role_id, "secret_id": secret_id })),
+                    )
+                    .await?;
+                response["auth"]["client_token"]
+                    .as_str()
+                    .context("missing client token in vault login response")?
+                    .to_string()
+            }
+        };
+        *self.token.lock().unwrap() = Some(token.clone());
+        Ok(token)
+    }
+}
+
+impl SecretsProvider for Vault {
+    async fn secret(&self, path: &str, key: &str) -> anyhow::Result<String> {
+        let path = path.trim_matches('/');
+        let cached = self.cache.lock().unwrap().get(path).cloned();
+        let data = match cached {
+            Some(data) => data,
+            None => {
+                let token = self.token().await?;
+                let mut response = self.request(path, Some(&token), None).await?;
+                let data = if response["data"]["metadata"].is_object() {
+                    response["data"]["data"].take()
+                } else {
+                    response["data"].take()
+                };
+                let Value::Object(data) = data else {
+                    bail!("unexpected vault response for {path:?}");
+                };
+                self.cache
+                    .lock()
+                    .unwrap()
+                    .insert(path.to_string(), data.clone());
+                data
+            }
+        };
+        match data.get(key) {
+            Some(Value::String(value)) => Ok(value.clone()),
+            Some(value) => Ok(value.to_string()),
+            None => bail!("secret {path:?} has no key {key:?}"),
+        }
+    }
+}
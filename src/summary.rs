@@ -0,0 +1,124 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use log::info;
+
+use crate::Session;
+
+/// Counters of operations performed in a session.
+#[derive(Debug)]
+pub(crate) struct SessionStats {
+    started: Instant,
+    commands: AtomicU64,
+    failed_commands: AtomicU64,
+    files_changed: AtomicU64,
+    bytes_uploaded: AtomicU64,
+    bytes_downloaded: AtomicU64,
+}
+
+impl Default for SessionStats {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            commands: AtomicU64::new(0),
+            failed_commands: AtomicU64::new(0),
+            files_changed: AtomicU64::new(0),
+            bytes_uploaded: AtomicU64::new(0),
+            bytes_downloaded: AtomicU64::new(0),
+        }
+    }
+}
+
+impl SessionStats {
+    pub(crate) fn record_command(&self, failed: bool) {
+        self.commands.fetch_add(1, Ordering::Relaxed);
+        if failed {
+            self.failed_commands.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_file_changed(&self) {
+        self.files_changed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_upload(&self, bytes: u64) {
+        self.bytes_uploaded.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_download(&self, bytes: u64) {
+        self.bytes_downloaded.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn summary(&self) -> SessionSummary {
+        SessionSummary {
+            commands: self.commands.load(Ordering::Relaxed),
+            failed_commands: self.failed_commands.load(Ordering::Relaxed),
+            files_changed: self.files_changed.load(Ordering::Relaxed),
+            bytes_uploaded: self.bytes_uploaded.load(Ordering::Relaxed),
+            bytes_downloaded: self.bytes_downloaded.load(Ordering::Relaxed),
+            duration: self.started.elapsed(),
+        }
+    }
+}
+
+/// Summary of operations performed in a session.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SessionSummary {
+    /// Number of executed remote commands.
+    pub commands: u64,
+    /// Number of commands that failed (not counting failures allowed by `allow_failure`).
+    pub failed_commands: u64,
+    /// Number of remote files created or changed by `update_file` and recipes using it.
+    pub files_changed: u64,
+    /// Bytes uploaded over SFTP.
+    pub bytes_uploaded: u64,
+    /// Bytes downloaded over SFTP.
+    pub bytes_downloaded: u64,
+    /// Time since the session was opened.
+    pub duration: Duration,
+}
+
+impl fmt::Display for SessionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} commands", self.commands)?;
+        if self.failed_commands > 0 {
+            write!(f, " ({} failed)", self.failed_commands)?;
+        }
+        write!(
+            f,
+            ", {} files changed, {:.1} MB uploaded, {:.1} MB downloaded in {:.1} s",
+            self.files_changed,
+            self.bytes_uploaded as f64 / 1_000_000.0,
+            self.bytes_downloaded as f64 / 1_000_000.0,
+            self.duration.as_secs_f64()
+        )
+    }
+}
+
+impl Session {
+    /// Summary of operations performed in this session so far.
+    pub fn summary(&self) -> SessionSummary {
+        self.stats.summary()
+    }
+
+    /// Log a one-line summary of operations performed in this session.
+    pub fn log_summary(&self) {
+        info!("{}session summary: {}", self.log_prefix, self.summary());
+    }
+
+    /// Log the summary automatically when the session is dropped. Disabled by default.
+    pub fn set_log_summary_on_drop(&mut self, enabled: bool) {
+        self.log_summary_on_drop = enabled;
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if self.log_summary_on_drop {
+            self.log_summary();
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::{future::Future, panic, sync::Arc};
+
+use anyhow::{anyhow, Context};
+use log::{error, info};
+use tokio::{sync::Semaphore, task::JoinSet};
+
+use crate::Session;
+
+const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Tasks spawned with `Session::spawn_task`.
+pub(crate) struct TaskSet {
+    join_set: JoinSet<(String, anyhow::Result<()>)>,
+    semaphore: Arc<Semaphore>,
+}
+
+impl Default for TaskSet {
+    fn default() -> Self {
+        Self {
+            join_set: JoinSet::new(),
+            semaphore: Arc::new(Semaphore::new(DEFAULT_CONCURRENCY)),
+        }
+    }
+}
+
+impl Session {
+    /// Run `task` in the background with its own session forked from this session
+    /// (see `fork`). Use `join_tasks` to wait for completion and collect errors.
+    ///
+    /// At most 4 tasks run at the same time by default; see `set_task_concurrency`.
+    /// Tasks that are still running when the session is dropped are aborted.
+    pub fn spawn_task<F, Fut>(&mut self, name: impl Into<String>, task: F)
+    where
+        F: FnOnce(Session) -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let name = name.into();
+        let parts = self.fork_parts();
+        let semaphore = self.tasks.semaphore.clone();
+        self.tasks.join_set.spawn(async move {
+            let result = async {
+                let _permit = semaphore.acquire_owned().await?;
+                info!("{}starting task {name:?}", parts.log_prefix);
+                let session = parts.connect().await?;
+                task(session).await
+            }
+            .await;
+            (name, result)
+        });
+    }
+
+    /// Set the maximum number of tasks spawned with `spawn_task` that run at the same time.
+    /// Only affects tasks spawned after this call.
+    pub fn set_task_concurrency(&mut self, limit: usize) {
+        self.tasks.semaphore = Arc::new(Semaphore::new(limit));
+    }
+
+    /// Wait for all tasks spawned with `spawn_task`. Returns the first error if any task
+    /// failed (after all tasks are finished). If a task panicked, the panic is resumed
+    /// after all tasks are finished.
+    pub async fn join_tasks(&mut self) -> anyhow::Result<()> {
+        let mut first_error = None;
+        let mut first_panic = None;
+        while let Some(joined) = self.tasks.join_set.join_next().await {
+            let (name, result) = match joined {
+                Ok(joined) => joined,
+                Err(err) if err.is_panic() => {
+                    error!("{}task panicked", self.log_prefix);
+                    first_panic.get_or_insert(err.into_panic());
+                    continue;
+                }
+                Err(err) => {
+                    error!("{}task was cancelled", self.log_prefix);
+                    if first_error.is_none() {
+                        first_error = Some(Err(anyhow!("task was cancelled: {err}")));
+                    }
+                    continue;
+                }
+            };
+            match result {
+                Ok(()) => info!("{}task {name:?} finished", self.log_prefix),
+                Err(err) => {
+                    error!("{}task {name:?} failed: {err:?}", self.log_prefix);
+                    if first_error.is_none() {
+                        first_error = Some(Err(err).context(format!("task {name:?} failed")));
+                    }
+                }
+            }
+        }
+        if let Some(panic) = first_panic {
+            panic::resume_unwind(panic);
+        }
+        first_error.unwrap_or(Ok(()))
+    }
+}
@@ -0,0 +1,79 @@
+use std::{cmp::Reverse, sync::PoisonError, time::Duration};
+
+use log::info;
+
+use crate::Session;
+
+/// Kind of an operation performed in a session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OperationKind {
+    /// Execution of a remote command.
+    Command,
+    /// Upload of local files.
+    Upload,
+    /// Download of remote files.
+    Download,
+}
+
+/// Wall-clock duration of an operation performed in a session.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Timing {
+    /// Kind of the operation.
+    pub kind: OperationKind,
+    /// Description of the operation (e.g. the command with its arguments).
+    pub description: String,
+    /// Wall-clock duration of the operation.
+    pub duration: Duration,
+}
+
+impl Session {
+    /// Durations of all commands and transfers performed in this session, in order of completion.
+    pub fn timings(&self) -> Vec<Timing> {
+        self.timings
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Up to `count` slowest operations performed in this session, slowest first.
+    pub fn slowest_operations(&self, count: usize) -> Vec<Timing> {
+        let mut timings = self.timings();
+        timings.sort_by_key(|timing| Reverse(timing.duration));
+        timings.truncate(count);
+        timings
+    }
+
+    /// Log a summary of up to `count` slowest operations performed in this session.
+    pub fn log_slowest_operations(&self, count: usize) {
+        let slowest = self.slowest_operations(count);
+        if slowest.is_empty() {
+            return;
+        }
+        info!("{}slowest operations:", self.log_prefix);
+        for timing in slowest {
+            info!(
+                "{}{:>10.3} s  {:?} {}",
+                self.log_prefix,
+                timing.duration.as_secs_f64(),
+                timing.kind,
+                timing.description
+            );
+        }
+    }
+
+    pub(crate) fn record_timing(
+        &self,
+        kind: OperationKind,
+        description: String,
+        duration: Duration,
+    ) {
+        self.timings
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Timing {
+                kind,
+                description,
+                duration,
+            });
+    }
+}
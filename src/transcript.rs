@@ -0,0 +1,104 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+use serde_json::json;
+
+use crate::CommandOutput;
+
+/// Format of a transcript file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptFormat {
+    /// Human-readable text.
+    Text,
+    /// One JSON object per executed command.
+    JsonLines,
+}
+
+/// A recorder of all executed commands and their full output.
+///
+/// Use `Session::set_transcript` to enable recording. The same `Transcript` can be shared
+/// by multiple sessions to produce a single file for the whole run.
+/// Redacted arguments are recorded as their placeholders.
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    format: TranscriptFormat,
+    file: Arc<Mutex<File>>,
+}
+
+pub(crate) struct TranscriptEntry<'a> {
+    pub host: &'a str,
+    pub command: &'a [String],
+    pub started_at: SystemTime,
+    pub duration: Duration,
+    pub output: &'a CommandOutput,
+}
+
+impl Transcript {
+    /// Create a transcript file at `path`. If the file exists, new records will be appended to it.
+    pub fn create(path: impl AsRef<Path>, format: TranscriptFormat) -> anyhow::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.as_ref())?;
+        Ok(Self {
+            format,
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    pub(crate) fn record(&self, entry: TranscriptEntry<'_>) -> anyhow::Result<()> {
+        let started_at = entry
+            .started_at
+            .duration_since(UNIX_EPOCH)
+            .map_or(0.0, |d| d.as_secs_f64());
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| anyhow!("transcript lock poisoned"))?;
+        match self.format {
+            TranscriptFormat::Text => {
+                writeln!(file, "=== [{}] {:?}", entry.host, entry.command)?;
+                writeln!(
+                    file,
+                    "started at: {:.3} (unix time), duration: {:.3} s, exit code: {}",
+                    started_at,
+                    entry.duration.as_secs_f64(),
+                    entry.output.exit_code
+                )?;
+                for (name, output) in [
+                    ("stdout", &entry.output.stdout),
+                    ("stderr", &entry.output.stderr),
+                ] {
+                    if output.is_empty() {
+                        continue;
+                    }
+                    writeln!(file, "--- {name}")?;
+                    write!(file, "{output}")?;
+                    if !output.ends_with('\n') {
+                        writeln!(file, "[eof]")?;
+                    }
+                }
+            }
+            TranscriptFormat::JsonLines => {
+                let record = json!({
+                    "host": entry.host,
+                    "command": entry.command,
+                    "started_at": started_at,
+                    "duration": entry.duration.as_secs_f64(),
+                    "exit_code": entry.output.exit_code,
+                    "stdout": entry.output.stdout,
+                    "stderr": entry.output.stderr,
+                });
+                writeln!(file, "{record}")?;
+            }
+        }
+        file.flush()?;
+        Ok(())
+    }
+}
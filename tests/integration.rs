@@ -1,8 +1,17 @@
 use anyhow::{bail, Context};
-use roguewave::Session;
+use openssh::{KnownHosts, SessionBuilder};
+use roguewave::{
+    CommandTimeout, Distro, JobState, LocalCommand, MaintenanceWindow, OutputLine, OutputOverflow,
+    OutsideWindow, Protocol, RetryPolicy, Session, SkewAction, SpecialBits, TransferOptions,
+    VersionMismatch, VersionSource,
+};
+use roguewave::pool::SessionPool;
 use std::env;
 use std::io::{stdout, Write};
-use std::sync::Once;
+use std::net::IpAddr;
+use std::sync::{Arc, Once};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
 fn setup_logger() {
     static START: Once = Once::new();
@@ -17,7 +26,7 @@ fn setup_logger() {
     });
 }
 
-#[tokio::test]
+#[tokio::test(flavor = "multi_thread")]
 async fn integration_test() -> anyhow::Result<()> {
     setup_logger();
     let destination = match env::var("ROGUEWAVE_INTEGRATION_TEST_DESTINATION") {
@@ -36,10 +45,23 @@ async fn integration_test() -> anyhow::Result<()> {
         }
     };
 
-    let mut session = Session::connect(destination).await?;
+    let mut session = Session::connect(&destination).await?;
     test_commands(&mut session).await?;
     test_env(&mut session).await?;
     test_apt(&mut session).await?;
+    test_lock(&mut session).await?;
+    test_files(&mut session).await?;
+    test_dns(&mut session).await?;
+    test_net(&mut session).await?;
+    test_app_version(&mut session).await?;
+    test_os(&mut session).await?;
+    test_batch(&mut session).await?;
+    test_tasks(&mut session).await?;
+    test_expect(&mut session).await?;
+    test_summary(&mut session).await?;
+    test_jobs(&mut session).await?;
+    test_command_only(&destination).await?;
+    test_pool(&destination).await?;
     Ok(())
 }
 
@@ -113,6 +135,229 @@ async fn test_commands(session: &mut Session) -> anyhow::Result<()> {
         "cat: /tmp/10: No such file or directory\n"
     );
 
+    session
+        .command(["echo", "ERROR: something went wrong"])
+        .fail_if_stdout_contains("ERROR:")
+        .run()
+        .await
+        .unwrap_err();
+    session
+        .command(["echo", "OK"])
+        .succeed_if(|output| output.stdout_trimmed() == "OK")
+        .run()
+        .await?;
+
+    assert_eq!(
+        session.command(["nice"]).low_priority().run().await?.stdout,
+        "19\n"
+    );
+
+    assert_eq!(
+        session.command(["cat"]).stdin("hello").run().await?.stdout,
+        "hello"
+    );
+    assert_eq!(
+        session
+            .command(["wc", "-c"])
+            .stdin_reader(&[0u8; 100_000][..])
+            .run()
+            .await?
+            .stdout_trimmed(),
+        "100000"
+    );
+    session.command(["true"]).stdin("ignored").run().await?;
+
+    let err = session
+        .command(["sleep", "10"])
+        .timeout(Duration::from_secs(1))
+        .run()
+        .await
+        .unwrap_err();
+    assert!(err.downcast_ref::<CommandTimeout>().is_some());
+    let err = LocalCommand::new(["sleep", "10"])
+        .timeout(Duration::from_secs(1))
+        .run()
+        .await
+        .unwrap_err();
+    assert!(err.downcast_ref::<CommandTimeout>().is_some());
+    session
+        .command(["true"])
+        .timeout(Duration::from_secs(10))
+        .run()
+        .await?;
+
+    let mut lines = Vec::new();
+    let output = session
+        .command(["sh", "-c", "echo a; echo b >&2; printf c"])
+        .run_streaming(|line| lines.push(line))
+        .await?;
+    assert_eq!(output.stdout, "a\nc");
+    lines.sort_by_key(|line| format!("{line:?}"));
+    assert_eq!(
+        lines,
+        [
+            OutputLine::Stderr("b".into()),
+            OutputLine::Stdout("a".into()),
+            OutputLine::Stdout("c".into()),
+        ]
+    );
+
+    let flaky = "test -e /tmp/retry-marker || { touch /tmp/retry-marker; exit 1; }";
+    session
+        .command(["sh", "-c", flaky])
+        .retry_with_backoff(RetryPolicy {
+            initial_delay: Duration::from_millis(100),
+            ..RetryPolicy::new(1)
+        })
+        .run()
+        .await?;
+    session
+        .command(["false"])
+        .retries(1)
+        .run()
+        .await
+        .unwrap_err();
+
+    let process = session
+        .command(["sh", "-c", "echo started; exec sleep 30"])
+        .spawn_detached("/tmp/detached.log")
+        .await?;
+    assert!(process.is_running(session).await?);
+    process.kill(session).await?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(!process.is_running(session).await?);
+    assert_eq!(
+        session
+            .read_file_if_exists("/tmp/detached.log")
+            .await?
+            .as_deref(),
+        Some("started\n")
+    );
+    session
+        .command(["sleep", "30"])
+        .timeout(Duration::from_secs(1))
+        .spawn_detached("/tmp/detached.log")
+        .await
+        .unwrap_err();
+
+    let output = session
+        .shell_script("set -e\nname=\"$1\"\necho \"hello, $name\"\necho done\n")
+        .arg("a b")
+        .run()
+        .await?;
+    assert_eq!(output.stdout, "hello, a b\ndone\n");
+
+    let local_script = env::temp_dir().join("roguewave-script-test");
+    std::fs::write(
+        &local_script,
+        "echo \"$0 $1\" | grep -q roguewave-script && whoami\n",
+    )?;
+    let output = session
+        .run_script(&local_script, Some("sh"), &["x"], Some("nobody"))
+        .await?;
+    assert_eq!(output.stdout, "nobody\n");
+    std::fs::remove_file(&local_script)?;
+
+    let closed = MaintenanceWindow::daily("02:00", "04:00")?.on_days(&[]);
+    session
+        .only_during(&closed, OutsideWindow::Wait(Duration::from_secs(1)))
+        .await
+        .unwrap_err();
+    session
+        .command(["touch", "/tmp/window-marker"])
+        .only_during(closed, OutsideWindow::Fail)
+        .run()
+        .await
+        .unwrap_err();
+    assert!(!session.path_exists("/tmp/window-marker").await?);
+
+    let mut child = session
+        .command([
+            "sh",
+            "-c",
+            "read x; echo \"got $x\"; read y; echo \"got $y\"",
+        ])
+        .spawn()
+        .await?;
+    child.stdin().write_all(b"a\n").await?;
+    child.stdin().flush().await?;
+    let mut buf = [0; 6];
+    child.stdout().read_exact(&mut buf).await?;
+    assert_eq!(&buf, b"got a\n");
+    child.stdin().write_all(b"b\n").await?;
+    assert_eq!(child.wait().await?.stdout, "got b\n");
+
+    let seq = || session.command(["seq", "1000"]).hide_stdout();
+    let output = seq()
+        .max_output_size(10, OutputOverflow::Truncate)
+        .run()
+        .await?;
+    assert_eq!(output.stdout, "1\n2\n3\n4\n5\n");
+    assert_eq!(output.stdout_overflow, None);
+    seq()
+        .max_output_size(10, OutputOverflow::Fail)
+        .run()
+        .await
+        .unwrap_err();
+    let full = seq().run().await?.stdout;
+    let output = seq()
+        .max_output_size(10, OutputOverflow::SpillToFile)
+        .run()
+        .await?;
+    let overflow_path = output.stdout_overflow.context("missing overflow file")?;
+    let overflow = std::fs::read_to_string(&overflow_path)?;
+    assert_eq!(output.stdout + &overflow, full);
+    std::fs::remove_file(overflow_path)?;
+
+    let local_dump = env::temp_dir().join("roguewave-stdout-test");
+    let output = session
+        .command(["sh", "-c", "head -c 100000 /dev/zero; echo done >&2"])
+        .stdout_to_file(&local_dump)
+        .run()
+        .await?;
+    assert_eq!(output.stdout, "");
+    assert_eq!(output.stderr, "done\n");
+    assert_eq!(std::fs::read(&local_dump)?, vec![0; 100_000]);
+    std::fs::remove_file(&local_dump)?;
+
+    session.command(["test", "-t", "1"]).pty(true).run().await?;
+    let (command, stdin) = session
+        .command(["sh", "-c", "echo ready; read x; echo \"got $x\""])
+        .pty(true)
+        .stdin_sender();
+    let output = command
+        .run_streaming(|line| {
+            if line == OutputLine::Stdout("ready\r".into()) {
+                stdin.send("hi\n");
+            }
+        })
+        .await?;
+    assert!(output.stdout.contains("got hi"));
+
+    session
+        .command(["mkdir", "-p", "/tmp/dir with 'quotes'"])
+        .run()
+        .await?;
+    assert_eq!(
+        session
+            .command(["pwd"])
+            .current_dir("/tmp/dir with 'quotes'")
+            .run()
+            .await?
+            .stdout,
+        "/tmp/dir with 'quotes'\n"
+    );
+    assert_eq!(
+        session
+            .command(["pwd"])
+            .current_dir("/tmp")
+            .user(Some("user1"))
+            .run()
+            .await?
+            .stdout,
+        "/tmp\n"
+    );
+
     Ok(())
 }
 
@@ -122,7 +367,309 @@ async fn test_apt(session: &mut Session) -> anyhow::Result<()> {
     session.apt().install(&["rolldice"]).await?;
     assert!(session.apt().is_package_installed("rolldice").await?);
     session.command(["rolldice"]).run().await?;
+    let packages = session.apt().installed_packages().await?;
+    assert!(packages.iter().any(|p| p.name == "rolldice"));
+    session.apt().upgradable_packages().await?;
+
+    let mut progress = Vec::new();
+    session
+        .apt()
+        .install_with_progress(&["sl"], |update| progress.push(update.percent))
+        .await?;
+    assert!(!progress.is_empty());
+
+    Ok(())
+}
+
+async fn test_lock(session: &mut Session) -> anyhow::Result<()> {
+    let guard = session.lock("test").acquire(Duration::from_secs(1)).await?;
+    session
+        .lock("test")
+        .acquire(Duration::from_secs(1))
+        .await
+        .unwrap_err();
+    guard.release().await?;
+    let guard = session.lock("test").acquire(Duration::from_secs(1)).await?;
+    guard.release().await?;
+
+    // A lock left behind by a crashed run.
+    session
+        .command(["mkdir", "/tmp/roguewave-lock-test"])
+        .run()
+        .await?;
+    let guard = session
+        .lock("test")
+        .stale_after(Duration::ZERO)
+        .acquire(Duration::from_secs(1))
+        .await?;
+    guard.release().await?;
+    Ok(())
+}
+
+async fn test_files(session: &mut Session) -> anyhow::Result<()> {
+    assert_eq!(session.read_file_if_exists("/tmp/11").await?, None);
+    assert!(session.update_file("/tmp/11", "OK11\n").await?);
+    assert!(!session.update_file("/tmp/11", "OK11\n").await?);
+    assert_eq!(
+        session.read_file_if_exists("/tmp/11").await?.as_deref(),
+        Some("OK11\n")
+    );
+
+    assert!(session.has_sftp());
+    let binary = [0u8, 255, 10, 13];
+    session.write_file("/tmp/binary", binary).await?;
+    assert_eq!(session.read_file("/tmp/binary").await?, binary);
+    assert!(session.modified_time("/tmp/binary").await?.elapsed()? < Duration::from_secs(60));
+    session.remove_file("/tmp/binary").await?;
+    assert!(!session.path_exists("/tmp/binary").await?);
 
+    let mut lines = session.tail("/tmp/11").lines(5).start().await?;
+    assert_eq!(lines.next_line().await?.as_deref(), Some("OK11"));
+    assert_eq!(lines.next_line().await?, None);
+    lines.stop().await?;
+
+    let mut lines = session.tail("/tmp/11").follow(true).start().await?;
+    assert_eq!(lines.next_line().await?.as_deref(), Some("OK11"));
+    session
+        .command(["sh", "-c", "echo followed >> /tmp/11"])
+        .run()
+        .await?;
+    assert_eq!(lines.next_line().await?.as_deref(), Some("followed"));
+    lines.stop().await?;
+
+    let local_path = env::temp_dir().join("roguewave-artifact-test");
+    std::fs::write(&local_path, "artifact\n")?;
+    assert!(session.upload_artifact(&local_path, "/tmp/12").await?);
+    assert!(!session.upload_artifact(&local_path, "/tmp/12").await?);
+    assert_eq!(
+        session.read_file_if_exists("/tmp/12").await?.as_deref(),
+        Some("artifact\n")
+    );
+
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    std::fs::write(&local_path, &data)?;
+    let options = TransferOptions {
+        chunk_size: 4096,
+        window: 4,
+        compress: false,
+    };
+    session
+        .upload_file_parallel(&local_path, "/tmp/14", options)
+        .await?;
+    assert_eq!(session.fs().read("/tmp/14").await?.as_ref(), &data[..]);
+    std::fs::remove_file(&local_path)?;
+    session
+        .download_file_parallel("/tmp/14", &local_path, options)
+        .await?;
+    assert_eq!(std::fs::read(&local_path)?, data);
+    let options = TransferOptions {
+        compress: true,
+        ..options
+    };
+    session
+        .upload_file_parallel(&local_path, "/tmp/15", options)
+        .await?;
+    session
+        .download_file_parallel("/tmp/15", &local_path, options)
+        .await?;
+    assert_eq!(std::fs::read(&local_path)?, data);
+
+    assert!(
+        session
+            .env_file("/tmp/13.env")
+            .set("B", "two words")
+            .await?
+    );
+    assert!(session.env_file("/tmp/13.env").set("A", "1").await?);
+    assert!(!session.env_file("/tmp/13.env").set("A", "1").await?);
+    assert_eq!(
+        session.read_file_if_exists("/tmp/13.env").await?.as_deref(),
+        Some("# Managed by roguewave.\nA=1\nB=\"two words\"\n")
+    );
+    let vars = session.env_file("/tmp/13.env").read().await?;
+    assert_eq!(vars["B"], "two words");
+    assert!(session.env_file("/tmp/13.env").remove("B").await?);
+    assert!(!session.env_file("/tmp/13.env").remove("B").await?);
+
+    session
+        .command(["mkdir", "-p", "/tmp/shared"])
+        .run()
+        .await?;
+    let bits = SpecialBits {
+        setgid: true,
+        sticky: true,
+        ..SpecialBits::default()
+    };
+    assert!(session.set_special_bits("/tmp/shared", bits).await?);
+    assert!(!session.set_special_bits("/tmp/shared", bits).await?);
+    assert_eq!(session.special_bits("/tmp/shared").await?, bits);
+    Ok(())
+}
+
+async fn test_dns(session: &mut Session) -> anyhow::Result<()> {
+    let ip: IpAddr = "10.1.2.3".parse()?;
+    assert!(session.dns().set_host("roguewave-test", ip).await?);
+    assert!(!session.dns().set_host("roguewave-test", ip).await?);
+    assert_eq!(session.dns().resolve("roguewave-test").await?, [ip]);
+    assert!(session.dns().remove_host("roguewave-test").await?);
+    assert!(!session.dns().remove_host("roguewave-test").await?);
+    assert!(session.dns().resolve("roguewave-test").await?.is_empty());
+    Ok(())
+}
+
+async fn test_net(session: &mut Session) -> anyhow::Result<()> {
+    assert!(session.net().port_open("127.0.0.1", 22).await?);
+    assert!(!session.net().port_open("127.0.0.1", 1).await?);
+    session
+        .net()
+        .wait_for_port(22, Duration::from_secs(1))
+        .await?;
+    let ports = session.net().listening_ports().await?;
+    assert!(ports.iter().any(|p| p.protocol == Protocol::Tcp
+        && p.port == 22
+        && p.process.as_deref() == Some("sshd")));
+    Ok(())
+}
+
+async fn test_app_version(session: &mut Session) -> anyhow::Result<()> {
+    let source = VersionSource::Command(vec!["echo".into(), "app v1.2.3 (abcdef)".into()]);
+    assert_eq!(session.app_version(&source).await?, "app v1.2.3 (abcdef)");
+    session.check_app_version(&source, "1.2.3").await?;
+    let err = session
+        .check_app_version(&source, "1.2.4")
+        .await
+        .unwrap_err();
+    let mismatch = err.downcast_ref::<VersionMismatch>().unwrap();
+    assert_eq!(mismatch.expected, "1.2.4");
+    Ok(())
+}
+
+async fn test_os(session: &mut Session) -> anyhow::Result<()> {
+    let os = session.os_release().await?;
+    assert_eq!(os.distro, Distro::Ubuntu);
+    session.require_os(&[Distro::Debian]).await?;
+    let err = session.require_os(&[Distro::Rocky]).await.unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("needs Rocky Linux, but host is Ubuntu"));
+
+    // The test container shares the clock with the host.
+    let skew = session
+        .check_clock_skew(Duration::from_secs(5), SkewAction::Fail)
+        .await?;
+    assert!(!skew.exceeds(Duration::from_secs(5)));
+    Ok(())
+}
+
+async fn test_batch(session: &mut Session) -> anyhow::Result<()> {
+    let mut batch = session.batch();
+    batch.add(["echo", "a b"]);
+    batch.add(["sh", "-c", "printf 'no newline'; echo err >&2; exit 3"]);
+    batch.add(["true"]);
+    let outputs = batch.run().await?;
+    assert_eq!(outputs.len(), 3);
+    assert_eq!(outputs[0].stdout, "a b\n");
+    assert_eq!(outputs[1].stdout, "no newline");
+    assert_eq!(outputs[1].stderr, "err\n");
+    assert_eq!(outputs[1].exit_code, 3);
+    assert_eq!(outputs[2].exit_code, 0);
+    Ok(())
+}
+
+async fn test_tasks(session: &mut Session) -> anyhow::Result<()> {
+    for i in 0..3 {
+        session.spawn_task(format!("write {i}"), move |mut session| async move {
+            session
+                .update_file(format!("/tmp/task{i}"), i.to_string())
+                .await?;
+            Ok(())
+        });
+    }
+    session.join_tasks().await?;
+    assert_eq!(
+        session.read_file_if_exists("/tmp/task2").await?.as_deref(),
+        Some("2")
+    );
+
+    session.spawn_task("fail", |session| async move {
+        session.command(["false"]).run().await?;
+        Ok(())
+    });
+    session.spawn_task("slow", |mut session| async move {
+        session.command(["sleep", "1"]).run().await?;
+        session.update_file("/tmp/task_slow", "done").await?;
+        Ok(())
+    });
+    assert!(session.join_tasks().await.is_err());
+    // The error is returned only after the other tasks are finished.
+    assert_eq!(
+        session
+            .read_file_if_exists("/tmp/task_slow")
+            .await?
+            .as_deref(),
+        Some("done")
+    );
+    Ok(())
+}
+
+async fn test_expect(session: &mut Session) -> anyhow::Result<()> {
+    let output = session
+        .expect([
+            "sh",
+            "-c",
+            "printf 'Name? '; read name; echo \"hello $name\"",
+        ])
+        .respond("Name?", "bob")
+        .run()
+        .await?;
+    assert_eq!(output.stdout, "Name? hello bob\n");
+    let err = session
+        .expect(["sleep", "5"])
+        .respond("never", "x")
+        .timeout(Duration::from_millis(500))
+        .run()
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("timed out"));
+    Ok(())
+}
+
+async fn test_summary(session: &mut Session) -> anyhow::Result<()> {
+    let mut session = session.fork().await?;
+    session.command(["rm", "-f", "/tmp/summary"]).run().await?;
+    assert!(session.command(["false"]).run().await.is_err());
+    session.command(["false"]).allow_failure().run().await?;
+    session.update_file("/tmp/summary", "1").await?;
+    let summary = session.summary();
+    assert_eq!(summary.commands, 3);
+    assert_eq!(summary.failed_commands, 1);
+    assert_eq!(summary.files_changed, 1);
+    Ok(())
+}
+
+async fn test_jobs(session: &mut Session) -> anyhow::Result<()> {
+    session
+        .jobs()
+        .start(
+            "test-job",
+            ["sh", "-c", "echo started; sleep 1; echo done; exit 3"],
+        )
+        .await?;
+    assert!(session
+        .jobs()
+        .list()
+        .await?
+        .contains(&"test-job".to_string()));
+    assert!(session.jobs().remove("test-job").await.is_err());
+
+    let mut other = session.fork().await?;
+    let status = other
+        .jobs()
+        .wait("test-job", Duration::from_millis(200))
+        .await?;
+    assert_eq!(status.state, JobState::Finished { exit_code: 3 });
+    assert_eq!(status.output, "started\ndone\n");
+    other.jobs().remove("test-job").await?;
     Ok(())
 }
 
@@ -139,6 +686,26 @@ async fn test_env(session: &mut Session) -> anyhow::Result<()> {
     assert_eq!(session.shell(None).await?, "/bin/bash");
     assert_eq!(get_shell_config(session).await?, "/bin/bash");
 
+    session
+        .set_persistent_env(Some("user1"), "RW_MULTILINE", "a\nb=c")
+        .await?;
+    let env = session.env(Some("user1")).await?;
+    assert_eq!(env.get("RW_MULTILINE").unwrap(), "a\nb=c");
+
+    assert!(session.set_persistent_env(None, "RW_TEST", "a b").await?);
+    assert!(!session.set_persistent_env(None, "RW_TEST", "a b").await?);
+    let profile = session
+        .read_file_if_exists("/root/.profile")
+        .await?
+        .unwrap();
+    assert!(profile.contains("# BEGIN roguewave env RW_TEST\nexport RW_TEST='a b'\n"));
+    assert!(session.remove_persistent_env(None, "RW_TEST").await?);
+    let profile = session
+        .read_file_if_exists("/root/.profile")
+        .await?
+        .unwrap();
+    assert!(!profile.contains("RW_TEST"));
+
     Ok(())
 }
 
@@ -154,3 +721,57 @@ async fn get_shell_config(session: &mut Session) -> anyhow::Result<String> {
         .context("invalid getent output")
         .map(Into::into)
 }
+
+async fn test_command_only(destination: &str) -> anyhow::Result<()> {
+    let mut session = Session::connect_command_only(destination).await?;
+    assert!(!session.has_sftp());
+    assert_eq!(session.read_file_if_exists("/tmp/shell-fs").await?, None);
+    assert!(session.update_file("/tmp/shell-fs", "a b\n").await?);
+    assert!(!session.update_file("/tmp/shell-fs", "a b\n").await?);
+    assert_eq!(
+        session
+            .read_file_if_exists("/tmp/shell-fs")
+            .await?
+            .as_deref(),
+        Some("a b\n")
+    );
+    let binary = [0u8, 255, 10, 13];
+    session.write_file("/tmp/shell-fs", binary).await?;
+    assert_eq!(session.read_file("/tmp/shell-fs").await?, binary);
+    session.modified_time("/tmp/shell-fs").await?;
+    session.remove_file("/tmp/shell-fs").await?;
+    assert!(!session.path_exists("/tmp/shell-fs").await?);
+    session.read_file("/tmp/shell-fs").await.unwrap_err();
+
+    let fork = session.fork().await?;
+    assert!(!fork.has_sftp());
+    Ok(())
+}
+
+async fn test_pool(destination: &str) -> anyhow::Result<()> {
+    // The same host under two names, so that the pool sees two destinations.
+    let other = destination.replace("127.0.0.1", "localhost");
+    let mut builder = SessionBuilder::default();
+    builder.known_hosts_check(KnownHosts::Add);
+    let pool = Arc::new(SessionPool::with_builder(builder, 1));
+
+    let session = pool.get(destination).await?;
+    session.command(["true"]).run().await?;
+    let waiter = tokio::spawn({
+        let pool = pool.clone();
+        async move {
+            let session = pool.get(&other).await?;
+            session.command(["true"]).run().await?;
+            anyhow::Ok(())
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(!waiter.is_finished());
+    // The idle session to the first destination must be closed to let the waiter connect.
+    drop(session);
+    tokio::time::timeout(Duration::from_secs(30), waiter).await???;
+
+    let session = pool.get(destination).await?;
+    session.command(["true"]).run().await?;
+    Ok(())
+}
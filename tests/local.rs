@@ -30,6 +30,20 @@ async fn test_local_command() -> anyhow::Result<()> {
     assert_eq!(output.stdout, "arg1 arg2 arg3\n");
     assert_eq!(output.stderr, "");
 
+    let output = LocalCommand::new(["printf", "  line1\nline2\n\n"])
+        .run()
+        .await?;
+    assert_eq!(output.stdout_trimmed(), "line1\nline2");
+    assert_eq!(
+        output.stdout_lines().collect::<Vec<_>>(),
+        ["  line1", "line2", ""]
+    );
+    assert_eq!(output.last_line(), Some("line2"));
+    let value: Vec<i32> = LocalCommand::new(["echo", "[1, 2]"]).run().await?.json()?;
+    assert_eq!(value, [1, 2]);
+    let output = LocalCommand::new(["cat"]).stdin("in1\nin2").run().await?;
+    assert_eq!(output.stdout, "in1\nin2\n");
+
     LocalCommand::new(["cat", "/tmp/21"])
         .run()
         .await